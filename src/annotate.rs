@@ -0,0 +1,315 @@
+//! Annotations drawn over the capture, for pointing things out while sharing the screen.
+//!
+//! A key (D by default) toggles drawing with the left button: freehand, or with Ctrl held a
+//! straight line, with Alt an arrow and with both a rectangle. Everything is kept in image pixels,
+//! so annotations stay on what they were drawn on when the view moves.
+
+use {
+    crate::{
+        config::{DrawConfig, Keys},
+        input,
+        schedule::Scheduler,
+        view::{screen_size, ImageState, Viewport},
+    },
+    gtk::{
+        cairo::{Context, LineCap, LineJoin},
+        gdk::{self, ModifierType},
+        glib::clone,
+        prelude::*,
+    },
+    std::rc::Rc,
+};
+
+/// Length of arrow heads, in line widths.
+const ARROW_HEAD: f64 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Freehand,
+    Line,
+    Arrow,
+    Rectangle,
+}
+
+impl Shape {
+    /// What a drag draws with `modifiers` held when it starts.
+    pub fn from_modifiers(modifiers: ModifierType) -> Self {
+        match (
+            modifiers.contains(ModifierType::CONTROL_MASK),
+            modifiers.contains(ModifierType::MOD1_MASK),
+        ) {
+            (false, false) => Self::Freehand,
+            (true, false) => Self::Line,
+            (false, true) => Self::Arrow,
+            (true, true) => Self::Rectangle,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stroke {
+    pub shape: Shape,
+    pub color: (f64, f64, f64, f64),
+    /// Line width in image pixels.
+    pub width: f64,
+    /// Points drawn through in image pixels. Shapes other than freehand only have a start and
+    /// an end.
+    pub points: Vec<(f64, f64)>,
+}
+
+/// Strokes in the order they were drawn.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Annotations {
+    strokes: Vec<Stroke>,
+    /// Whether the last stroke is still being drawn.
+    active: bool,
+}
+
+impl Annotations {
+    /// Start drawing `shape` at `pos`.
+    pub fn begin(
+        &mut self,
+        shape: Shape,
+        color: (f64, f64, f64, f64),
+        width: f64,
+        pos: (f64, f64),
+    ) {
+        let points = match shape {
+            Shape::Freehand => vec![pos],
+            _ => vec![pos, pos],
+        };
+        self.strokes.push(Stroke {
+            shape,
+            color,
+            width,
+            points,
+        });
+        self.active = true;
+    }
+
+    /// Continue the stroke being drawn to `pos`.
+    pub fn extend(&mut self, pos: (f64, f64)) {
+        let stroke = match (self.active, self.strokes.last_mut()) {
+            (true, Some(stroke)) => stroke,
+            _ => return,
+        };
+        match stroke.shape {
+            Shape::Freehand => stroke.points.push(pos),
+            _ => stroke.points[1] = pos,
+        }
+    }
+
+    pub fn end(&mut self) {
+        self.active = false;
+    }
+
+    pub fn is_drawing(&self) -> bool {
+        self.active
+    }
+
+    /// Remove the last stroke. Returns `false` if there was none.
+    pub fn undo(&mut self) -> bool {
+        self.active = false;
+        self.strokes.pop().is_some()
+    }
+
+    pub fn strokes(&self) -> &[Stroke] {
+        &self.strokes
+    }
+}
+
+/// The two ends of the barbs of an arrow from `from` to `to` with a head `length` long, or `None`
+/// for an arrow without a direction.
+fn arrow_head(from: (f64, f64), to: (f64, f64), length: f64) -> Option<[(f64, f64); 2]> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    if dx == 0.0 && dy == 0.0 {
+        return None;
+    }
+    let angle = dy.atan2(dx) + std::f64::consts::PI;
+    let spread = 25f64.to_radians();
+    let barb = |a: f64| (to.0 + length * a.cos(), to.1 + length * a.sin());
+    Some([barb(angle - spread), barb(angle + spread)])
+}
+
+/// Draw `annotations` on a screen showing the image through `t`.
+pub fn draw(ctx: &Context, annotations: &Annotations, t: &Viewport) {
+    ctx.save().ok();
+    let (x, y) = t.origin();
+    ctx.translate(x, y);
+    ctx.scale(t.scale, t.scale);
+    ctx.set_line_cap(LineCap::Round);
+    ctx.set_line_join(LineJoin::Round);
+    for stroke in annotations.strokes() {
+        let (r, g, b, a) = stroke.color;
+        ctx.set_source_rgba(r, g, b, a);
+        ctx.set_line_width(stroke.width);
+        let (first, last) = match (stroke.points.first(), stroke.points.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => continue,
+        };
+        match stroke.shape {
+            Shape::Freehand => {
+                ctx.move_to(first.0, first.1);
+                stroke.points.iter().for_each(|&(x, y)| ctx.line_to(x, y));
+            }
+            Shape::Line => {
+                ctx.move_to(first.0, first.1);
+                ctx.line_to(last.0, last.1);
+            }
+            Shape::Arrow => {
+                ctx.move_to(first.0, first.1);
+                ctx.line_to(last.0, last.1);
+                if let Some([a, b]) = arrow_head(first, last, stroke.width * ARROW_HEAD) {
+                    ctx.move_to(a.0, a.1);
+                    ctx.line_to(last.0, last.1);
+                    ctx.line_to(b.0, b.1);
+                }
+            }
+            Shape::Rectangle => {
+                ctx.rectangle(first.0, first.1, last.0 - first.0, last.1 - first.1);
+            }
+        }
+        let _ = ctx.stroke();
+    }
+    ctx.restore().ok();
+}
+
+/// Draw annotations on `area` over the view in `window`, toggled and taken back with the keys
+/// in `keys`.
+pub fn connect(
+    window: &gtk::ApplicationWindow,
+    area: &gtk::DrawingArea,
+    scheduler: &Rc<Scheduler>,
+    state: &Rc<ImageState>,
+    keys: Keys,
+    config: DrawConfig,
+) {
+    scheduler.redraw_above(area);
+    area.connect_draw(clone!(@strong state => move |area, ctx| {
+        let size = screen_size(area);
+        draw(ctx, &state.annotations.borrow(), &state.viewport(size));
+        Inhibit(false)
+    }));
+
+    window.connect_key_press_event(
+        clone!(@strong area, @strong scheduler, @strong state => move |window, evt| {
+            let key = input::keysym(evt);
+            if key == keys.draw {
+                let drawing = !state.drawing.get();
+                state.drawing.set(drawing);
+                state.annotations.borrow_mut().end();
+                // An enlarged cursor already hides the real one
+                if let (None, Some(gdk_window)) = (state.cursor_size.get(), window.window()) {
+                    let cursor = match drawing {
+                        true => Some(gdk::Cursor::for_display(
+                            &WidgetExt::display(window),
+                            gdk::CursorType::Crosshair,
+                        )),
+                        false => None,
+                    };
+                    gdk_window.set_cursor(cursor.as_ref());
+                }
+            } else if key == keys.undo {
+                let mut annotations = state.annotations.borrow_mut();
+                annotations.undo();
+                if annotations.strokes().is_empty() {
+                    area.hide();
+                }
+                scheduler.redraw();
+            } else {
+                return Inhibit(false);
+            }
+            Inhibit(true)
+        }),
+    );
+
+    window.connect_button_press_event(
+        clone!(@strong area, @strong scheduler, @strong state => move |window, evt| {
+            if !state.drawing.get() || evt.button() != 1 {
+                return Inhibit(false);
+            }
+            let size = screen_size(window);
+            let t = state.viewport(size);
+            state.annotations.borrow_mut().begin(
+                Shape::from_modifiers(evt.state()),
+                config.color,
+                config.width / t.scale,
+                t.screen_to_image(evt.position()),
+            );
+            area.show();
+            scheduler.redraw();
+            Inhibit(false)
+        }),
+    );
+
+    window.connect_motion_notify_event(
+        clone!(@strong scheduler, @strong state => move |window, evt| {
+            if !state.annotations.borrow().is_drawing() {
+                return Inhibit(false);
+            }
+            let size = screen_size(window);
+            let pos = state.viewport(size).screen_to_image(evt.position());
+            state.annotations.borrow_mut().extend(pos);
+            scheduler.redraw();
+            Inhibit(false)
+        }),
+    );
+
+    window.connect_button_release_event(clone!(@strong state => move |_, evt| {
+        if evt.button() == 1 {
+            state.annotations.borrow_mut().end();
+        }
+        Inhibit(false)
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strokes_are_drawn_and_undone() {
+        let mut annotations = Annotations::default();
+        let color = (1.0, 0.0, 0.0, 1.0);
+        annotations.begin(Shape::Freehand, color, 2.0, (0.0, 0.0));
+        annotations.extend((1.0, 1.0));
+        annotations.extend((2.0, 1.0));
+        annotations.end();
+        annotations.begin(Shape::Arrow, color, 2.0, (5.0, 5.0));
+        annotations.extend((6.0, 6.0));
+        annotations.extend((8.0, 5.0));
+        annotations.end();
+        // Nothing is being drawn any more
+        annotations.extend((9.0, 9.0));
+
+        let strokes = annotations.strokes();
+        assert_eq!(strokes[0].points, [(0.0, 0.0), (1.0, 1.0), (2.0, 1.0)]);
+        assert_eq!(strokes[1].points, [(5.0, 5.0), (8.0, 5.0)]);
+
+        assert!(annotations.undo());
+        assert_eq!(annotations.strokes().len(), 1);
+        assert!(annotations.undo());
+        assert!(!annotations.undo());
+    }
+
+    #[test]
+    fn arrow_heads_point_back_along_the_arrow() {
+        let [a, b] = arrow_head((0.0, 0.0), (10.0, 0.0), 4.0).unwrap();
+        assert!(a.0 < 10.0 && b.0 < 10.0);
+        assert!((a.1 + b.1).abs() < 1e-9);
+        assert!(((a.0 - 10.0).hypot(a.1) - 4.0).abs() < 1e-9);
+        assert_eq!(arrow_head((1.0, 1.0), (1.0, 1.0), 4.0), None);
+    }
+
+    #[test]
+    fn modifiers_pick_the_shape() {
+        assert_eq!(
+            Shape::from_modifiers(ModifierType::empty()),
+            Shape::Freehand
+        );
+        assert_eq!(
+            Shape::from_modifiers(ModifierType::CONTROL_MASK | ModifierType::MOD1_MASK),
+            Shape::Rectangle
+        );
+    }
+}
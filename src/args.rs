@@ -0,0 +1,349 @@
+use {
+    crate::{
+        capture,
+        config::MEMORY_BUDGET_MIB,
+        input::PanButton,
+        logging::LogFormat,
+        magnifier::Corner,
+        power::PowerMode,
+        tree::Rect,
+        view::{Dock, Geometry, Lens},
+    },
+    clap::{Parser, ValueEnum},
+    std::{net::SocketAddr, num::ParseFloatError, path::PathBuf},
+};
+
+/// What shows the overlay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Backend {
+    #[default]
+    Gtk,
+    /// wlr-layer-shell and shared memory, without GTK's windows, see [`crate::wayland`]
+    #[cfg(feature = "wayland")]
+    Wayland,
+    /// A fullscreen winit window drawn with wgpu, without GTK's windows, see
+    /// [`crate::windowed`]
+    #[cfg(feature = "winit")]
+    Winit,
+}
+
+/// Length on screen, in logical pixels or physical millimeters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Px(f64),
+    Mm(f64),
+}
+
+impl Length {
+    /// Logical pixels on an output with `px_per_mm` logical pixels per millimeter.
+    pub fn to_px(self, px_per_mm: f64) -> f64 {
+        match self {
+            Self::Px(px) => px,
+            Self::Mm(mm) => mm * px_per_mm,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Increase log verbosity (-v info, -vv debug with timings, -vvv trace)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// How log lines on stderr are formatted
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Read settings from this file instead of ~/.config/sway-boomer/config.toml
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Zoom an existing image instead of taking a screenshot
+    #[arg(long, value_name = "PATH")]
+    pub file: Option<PathBuf>,
+
+    /// Capture and cover the output with this name, such as DP-1, instead of the focused one
+    #[arg(long, value_name = "NAME", conflicts_with = "file")]
+    pub output: Option<String>,
+
+    /// Capture every output and cover each with its own overlay (sway only)
+    #[arg(
+        long,
+        conflicts_with_all = ["file", "output", "bench", "headless", "remote", "midi"]
+    )]
+    pub all_outputs: bool,
+
+    /// Capture only this region, `X,Y WxH` in layout coordinates as slurp prints it, and cover
+    /// just that part of the screen (sway only)
+    #[arg(
+        short = 'g',
+        long,
+        value_name = "REGION",
+        value_parser = parse_capture_region,
+        conflicts_with_all = ["file", "output", "all_outputs", "output_at_cursor", "geometry", "bench"]
+    )]
+    pub region: Option<Rect>,
+
+    /// Drag out the region to capture with slurp first, see `--region`
+    #[arg(
+        long,
+        conflicts_with_all = ["region", "file", "output", "all_outputs", "output_at_cursor", "geometry", "bench"]
+    )]
+    pub select: bool,
+
+    /// Capture only the focused window, like `--region` with where it is
+    #[arg(long, conflicts_with_all = ["region", "select", "file", "output", "all_outputs", "output_at_cursor", "geometry", "bench"])]
+    pub window: bool,
+
+    /// Capture only the sway container with this id, see `swaymsg -t get_tree`
+    #[arg(long, value_name = "ID", conflicts_with_all = ["window", "region", "select", "file", "output", "all_outputs", "output_at_cursor", "geometry", "bench"])]
+    pub con_id: Option<i64>,
+
+    /// Show on the output under the pointer rather than the focused one (sway only)
+    #[arg(
+        long,
+        conflicts_with_all = ["file", "output", "all_outputs", "bench", "headless", "remote", "midi"]
+    )]
+    pub output_at_cursor: bool,
+
+    /// Leave the pointer in the capture, where it was
+    #[arg(long, conflicts_with = "file")]
+    pub cursor: bool,
+
+    /// Initial zoom level
+    #[arg(long, default_value_t = 1.0, value_parser = parse_scale)]
+    pub scale: f64,
+
+    /// Lowest zoom level, below 1 to see the whole capture small, instead of `min_scale` from
+    /// the config
+    #[arg(long, value_name = "SCALE", value_parser = parse_scale)]
+    pub min_scale: Option<f64>,
+
+    /// Highest zoom level, instead of `max_scale` from the config
+    #[arg(long, value_name = "SCALE", value_parser = parse_scale)]
+    pub max_scale: Option<f64>,
+
+    /// Replay a zoom/pan script offscreen and print per-frame timings instead of showing the overlay
+    #[arg(long)]
+    pub bench: bool,
+
+    /// Render a single frame to this PNG without opening a window
+    #[arg(long, value_name = "PATH")]
+    pub headless: Option<PathBuf>,
+
+    /// Stay running without a window and capture and show the overlay on `--toggle`, hiding it
+    /// again on quit
+    #[arg(long, conflicts_with_all = ["file", "bench", "headless", "remote", "midi"])]
+    pub daemon: bool,
+
+    /// Keep a small window in this corner showing what's around the pointer magnified, instead of
+    /// covering the output once
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CORNER",
+        num_args = 0..=1,
+        default_missing_value = "bottom-right",
+        conflicts_with_all = ["file", "bench", "headless", "daemon", "all_outputs", "output_at_cursor", "region", "select", "window"]
+    )]
+    pub magnifier: Option<Corner>,
+
+    /// Show the overlay through GTK, with `wayland` straight on wlr-layer-shell, or with `winit`
+    /// in a fullscreen window drawn with wgpu. Both only have the keys, buttons and scrolling of
+    /// the view itself
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Backend::Gtk,
+        conflicts_with_all = ["bench", "headless", "daemon", "magnifier", "all_outputs", "output_at_cursor", "remote", "midi"]
+    )]
+    pub backend: Backend,
+
+    /// Have the running `--daemon` show or hide its overlay, then exit
+    #[arg(long, conflicts_with = "daemon")]
+    pub toggle: bool,
+
+    /// Send a command such as `zoom-in` or `scale 2` to the running overlay's control socket,
+    /// then exit
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["daemon", "toggle"])]
+    pub send: Option<String>,
+
+    /// View offset for --headless
+    #[arg(long, value_name = "X,Y", value_parser = parse_point, requires = "headless")]
+    pub offset: Option<(f64, f64)>,
+
+    /// Radius of the highlight, in logical pixels or with an `mm` suffix in millimeters on the
+    /// output, so it looks the same size on any screen
+    #[arg(long, value_name = "LENGTH", value_parser = parse_length)]
+    pub highlight_radius: Option<Length>,
+
+    /// Let the highlight trail behind the pointer, catching up over about this many milliseconds
+    #[arg(long, value_name = "MS")]
+    pub highlight_lag: Option<f64>,
+
+    /// Draw the highlight around this point for --headless
+    #[arg(long, value_name = "X,Y", value_parser = parse_point, requires = "headless")]
+    pub highlight_at: Option<(f64, f64)>,
+
+    /// Memory the capture and its scaled copies may use, in MiB. Larger captures are downscaled
+    #[arg(long, value_name = "MIB", default_value_t = MEMORY_BUDGET_MIB)]
+    pub memory_budget: usize,
+
+    /// Keep the focused widget or text caret in the middle of the view, or in the magnifier
+    #[cfg(feature = "atspi")]
+    #[arg(long)]
+    pub follow_focus: bool,
+
+    /// Read recognized text out loud through speech-dispatcher
+    #[cfg(feature = "ocr")]
+    #[arg(long)]
+    pub speak: bool,
+
+    /// Rhai script to run, defining any of on_capture, on_key and on_draw_overlay
+    #[cfg(feature = "script")]
+    #[arg(long, value_name = "PATH")]
+    pub script: Option<PathBuf>,
+
+    /// Pan along with the pointer, keeping what it points at in the middle of the view
+    #[arg(long, conflicts_with = "dock")]
+    pub follow_mouse: bool,
+
+    /// Lock the view onto this region of the capture, so panning and zooming don't move it
+    #[arg(long, value_name = "X,Y,WxH", value_parser = parse_region, conflicts_with = "dock")]
+    pub lock: Option<(f64, f64, f64, f64)>,
+
+    /// Leave the screen as it is and show the magnified view in a band along this edge
+    #[arg(long, value_enum)]
+    pub dock: Option<Dock>,
+
+    /// Magnify only through a lens of this shape around the pointer, leaving the rest as it is
+    #[arg(long, value_enum)]
+    pub lens: Option<Lens>,
+
+    /// Hide the pointer and draw an enlarged one of this height instead
+    #[arg(long, value_name = "PX")]
+    pub cursor_size: Option<f64>,
+
+    /// Mouse button that pans when dragging, in addition to the middle button
+    #[arg(long, value_enum, default_value_t = PanButton::Left)]
+    pub pan_button: PanButton,
+
+    /// Snap the edges of the view to nearby window edges while panning (sway only)
+    #[arg(long)]
+    pub snap: bool,
+
+    /// Glide over to windows as they get focus, or magnify them with `--magnifier` (sway only)
+    #[arg(long)]
+    pub follow_windows: bool,
+
+    /// Stay open when another workspace is shown, a window goes fullscreen or the output goes
+    /// away, instead of closing then since the capture is out of date
+    #[arg(long)]
+    pub no_auto_close: bool,
+
+    /// Accept remote control commands over TCP on this address, such as 0.0.0.0:7701
+    #[arg(long, value_name = "ADDR")]
+    pub remote: Option<SocketAddr>,
+
+    /// Token remote clients have to send first, instead of `remote.token` from the config file.
+    /// Without either a random one is logged at startup, shown with -v
+    #[arg(long, value_name = "TOKEN", requires = "remote")]
+    pub remote_token: Option<String>,
+
+    /// Raw MIDI device, such as /dev/snd/midiC1D0, to take controls from as set up in the config file
+    #[arg(long, value_name = "DEVICE")]
+    pub midi: Option<PathBuf>,
+
+    /// Only cover part of the output: left-half, right-half, top-half, bottom-half or X,Y,WxH
+    #[arg(long, value_name = "GEOMETRY", value_parser = parse_geometry)]
+    pub geometry: Option<Geometry>,
+
+    /// Skip pre-scaling and animations to save battery
+    #[arg(long, value_enum, default_value_t = PowerMode::Auto)]
+    pub power_mode: PowerMode,
+}
+
+impl Args {
+    /// Whether only part of the layout is captured, see `--region`.
+    pub fn captures_region(&self) -> bool {
+        self.region.is_some() || self.select || self.window || self.con_id.is_some()
+    }
+
+    pub fn memory_budget_bytes(&self) -> usize {
+        self.memory_budget.saturating_mul(1024 * 1024)
+    }
+}
+
+fn parse_geometry(s: &str) -> Result<Geometry, String> {
+    match s {
+        "left-half" => Ok(Geometry::LeftHalf),
+        "right-half" => Ok(Geometry::RightHalf),
+        "top-half" => Ok(Geometry::TopHalf),
+        "bottom-half" => Ok(Geometry::BottomHalf),
+        _ => parse_region(s).map(|(x, y, w, h)| {
+            Geometry::Rect(x as i32, y as i32, w.round() as i32, h.round() as i32)
+        }),
+    }
+}
+
+fn parse_capture_region(s: &str) -> Result<Rect, String> {
+    capture::parse_region(s).ok_or_else(|| "expected X,Y WxH".into())
+}
+
+fn parse_region(s: &str) -> Result<(f64, f64, f64, f64), String> {
+    let (pos, size) = s.rsplit_once(',').ok_or("expected X,Y,WxH")?;
+    let (x, y) = parse_point(pos)?;
+    let (w, h) = size.split_once('x').ok_or("expected X,Y,WxH")?;
+    let parse = |v: &str| v.trim().parse().map_err(|e: ParseFloatError| e.to_string());
+    let (w, h) = (parse(w)?, parse(h)?);
+    match w > 0.0 && h > 0.0 {
+        true => Ok((x, y, w, h)),
+        false => Err("width and height must be positive".into()),
+    }
+}
+
+fn parse_length(s: &str) -> Result<Length, String> {
+    let s = s.trim();
+    let (value, unit): (&str, fn(f64) -> Length) = match s.strip_suffix("mm") {
+        Some(mm) => (mm, Length::Mm),
+        None => (s.strip_suffix("px").unwrap_or(s), Length::Px),
+    };
+    match value.trim().parse::<f64>() {
+        Ok(v) if v > 0.0 => Ok(unit(v)),
+        Ok(_) => Err("length must be positive".into()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_point(s: &str) -> Result<(f64, f64), String> {
+    let (x, y) = s.split_once(',').ok_or("expected X,Y")?;
+    let parse = |v: &str| v.trim().parse().map_err(|e: ParseFloatError| e.to_string());
+    Ok((parse(x)?, parse(y)?))
+}
+
+fn parse_scale(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(scale) if scale > 0.0 && scale.is_finite() => Ok(scale),
+        Ok(_) => Err("scale must be positive".into()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales_must_be_positive_and_finite() {
+        assert_eq!(parse_scale("2.5"), Ok(2.5));
+        for bad in ["0", "-1", "inf", "NaN", "two"] {
+            assert!(parse_scale(bad).is_err(), "{} was accepted", bad);
+        }
+        assert!(Args::try_parse_from(["sway-boomer", "--scale", "0"]).is_err());
+        let args = Args::try_parse_from(["sway-boomer", "--scale", "3"]).unwrap();
+        assert_eq!(args.scale, 3.0);
+        assert!(Args::try_parse_from(["sway-boomer", "--min-scale", "-1"]).is_err());
+        assert!(Args::try_parse_from(["sway-boomer", "--max-scale", "inf"]).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+//! Close the overlay once what it shows is out of date: when another workspace is shown on the
+//! captured output, a window goes fullscreen on it, or it's disconnected.
+//!
+//! This follows sway's IPC events, so it's off outside of sway, and with `--no-auto-close`.
+
+use {
+    crate::{capture, sway::Connection, tree::Rect, Error},
+    gtk::{
+        glib::{self, clone, Continue},
+        prelude::*,
+    },
+    serde::Deserialize,
+    std::thread,
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Workspace {
+    output: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Container {
+    /// 0 when not fullscreen, 1 on its output and 2 across all of them.
+    fullscreen_mode: u8,
+    rect: Option<Rect>,
+}
+
+/// Workspace, window and output events all have a `change`, the rest depends on the kind.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Event {
+    change: String,
+    /// Workspace events: the workspace now focused, and the one focused before.
+    current: Option<Workspace>,
+    old: Option<Workspace>,
+    /// Window events: the window that changed.
+    container: Option<Container>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    /// The capture is out of date, for this reason.
+    Outdated(&'static str),
+    /// Something about the outputs changed, output events don't say what.
+    Outputs,
+}
+
+/// What `event` means for a capture of the output called `output`, which is at `rect`.
+fn change(event: &Event, output: &str, rect: &Rect) -> Option<Change> {
+    match (event.change.as_str(), &event.current, &event.container) {
+        // Focus moving over from another output leaves what's shown on this one as it was
+        ("focus", Some(current), _)
+            if current.output == output
+                && event.old.as_ref().is_none_or(|old| old.output == output) =>
+        {
+            Some(Change::Outdated("another workspace is shown"))
+        }
+        ("fullscreen_mode", _, Some(container)) if container.fullscreen_mode != 0 => {
+            match container.rect.is_none_or(|r| rect.contains(r.center())) {
+                true => Some(Change::Outdated("a window went fullscreen")),
+                false => None,
+            }
+        }
+        // Only output events have nothing but `change`
+        ("unspecified", None, None) => Some(Change::Outputs),
+        _ => None,
+    }
+}
+
+/// Pass the reason to `close` once something makes the capture of `output` out of date.
+fn listen(output: &str, close: impl Fn(&'static str) -> bool) -> Result<(), Error> {
+    let rect = capture::output_rect(output)?;
+    let events = Connection::connect()?.subscribe::<Event>(&["workspace", "window", "output"])?;
+    for event in events {
+        let reason = match change(&event?, output, &rect) {
+            Some(Change::Outdated(reason)) => reason,
+            Some(Change::Outputs) => match capture::output_rect(output) {
+                Ok(now) if now == rect => continue,
+                _ => "the output was disconnected or moved",
+            },
+            None => continue,
+        };
+        close(reason);
+        break;
+    }
+    Ok(())
+}
+
+/// Close `window`, showing a capture of `output`, once the capture is out of date.
+pub fn connect(window: &gtk::ApplicationWindow, output: String) {
+    let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    thread::spawn(move || {
+        if let Err(e) = listen(&output, |reason| tx.send(reason).is_ok()) {
+            tracing::warn!(error = %e, "not closing by itself when the capture is out of date");
+        }
+    });
+    rx.attach(
+        None,
+        clone!(@weak window => @default-return Continue(false), move |reason| {
+            tracing::info!(reason, "closing, the capture is out of date");
+            // The application quits with its last window unless it's a daemon
+            window.close();
+            Continue(false)
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_on_the_captured_output_close() {
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        };
+        let parse = |json| serde_json::from_str::<Event>(json).unwrap();
+        let on = |json| change(&parse(json), "DP-1", &rect);
+
+        assert!(on(
+            r#"{"change": "focus", "current": {"name": "2", "output": "DP-1"},
+                "old": {"name": "1", "output": "DP-1"}}"#
+        )
+        .is_some());
+        assert_eq!(
+            on(
+                r#"{"change": "focus", "current": {"name": "1", "output": "DP-1"},
+                "old": {"name": "3", "output": "HDMI-A-1"}}"#
+            ),
+            None
+        );
+        assert_eq!(
+            on(r#"{"change": "focus", "current": {"name": "3", "output": "HDMI-A-1"}}"#),
+            None
+        );
+        assert!(on(
+            r#"{"change": "fullscreen_mode", "container": {"fullscreen_mode": 1,
+                "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080}}}"#
+        )
+        .is_some());
+        assert_eq!(
+            on(
+                r#"{"change": "fullscreen_mode", "container": {"fullscreen_mode": 1,
+                "rect": {"x": 1920, "y": 0, "width": 1920, "height": 1080}}}"#
+            ),
+            None
+        );
+        assert_eq!(on(r#"{"change": "title", "container": {}}"#), None);
+        assert_eq!(on(r#"{"change": "unspecified"}"#), Some(Change::Outputs));
+    }
+}
@@ -0,0 +1,131 @@
+//! `--bench`: replay a fixed zoom/pan script offscreen and print how long each stage takes.
+
+use {
+    crate::{
+        capture::{self, Frame},
+        config::SCALE_DELTA,
+        pyramid::Pyramid,
+        render::{self, ViewCache},
+        view::ImageState,
+        Error,
+    },
+    gtk::{
+        cairo::{Context, Format, ImageSurface},
+        glib,
+    },
+    std::time::{Duration, Instant},
+};
+
+/// Scale the script zooms in to, fixed so timings stay comparable whatever the zoom range is.
+const TARGET_SCALE: f64 = 3.0;
+/// Number of frames spent panning in a circle at the target scale.
+const PAN_FRAMES: usize = 60;
+const PAN_RADIUS: f64 = 200.0;
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let ret = f();
+    (ret, start.elapsed())
+}
+
+/// Scale and offset of every frame: zoom in step by step, pan around, zoom back out.
+fn script() -> Vec<(f64, (f64, f64))> {
+    let steps = ((TARGET_SCALE - 1.0) / SCALE_DELTA).round() as usize;
+    let zoom_in = (0..=steps).map(|i| (1.0 + i as f64 * SCALE_DELTA, (0.0, 0.0)));
+    let pan = (0..PAN_FRAMES).map(|i| {
+        let a = i as f64 / PAN_FRAMES as f64 * std::f64::consts::TAU;
+        (TARGET_SCALE, (PAN_RADIUS * a.cos(), PAN_RADIUS * a.sin()))
+    });
+    let zoom_out = (0..=steps)
+        .rev()
+        .map(|i| (1.0 + i as f64 * SCALE_DELTA, (0.0, 0.0)));
+    zoom_in.chain(pan).chain(zoom_out).collect()
+}
+
+pub fn run(output: Option<&str>, budget: usize) -> Result<(), Error> {
+    let (img, capture_time) = timed(|| capture::screenshot(output, false));
+    let (frame, decode_time) = timed(|| img?.into_frame());
+    let frame = frame?;
+    println!("capture {:>9.2} ms", ms(capture_time));
+    println!("decode  {:>9.2} ms", ms(decode_time));
+    replay(frame, budget)
+}
+
+/// Run the script against an already decoded `frame`.
+pub fn replay(frame: Frame, budget: usize) -> Result<(), Error> {
+    let ctx = glib::MainContext::new();
+    let _owner = ctx.acquire()?;
+    ctx.with_thread_default(|| {
+        let pyramid = Pyramid::new(frame, budget, || {});
+        let (width, height) = pyramid.size();
+        let surface = ImageSurface::create(Format::Rgb24, width as i32, height as i32)?;
+        let cr = Context::new(&surface)?;
+        let state = ImageState::default();
+
+        println!();
+        println!("frame  scale  scale ms  paint ms");
+        let (mut scale_total, mut paint_total) = (Duration::ZERO, Duration::ZERO);
+        let script = script();
+        for (i, (scale, offset)) in script.iter().enumerate() {
+            state.scale.set(*scale);
+            state.offset.set(*offset);
+
+            // Wait for the worker, as a live session would until the sharp copy shows up
+            let ((), scale_time) = timed(|| {
+                pyramid.get(*scale);
+                while pyramid.is_busy() {
+                    ctx.iteration(true);
+                }
+            });
+            let ((), paint_time) = timed(|| {
+                // Without a cache from the frame before, so every frame is painted
+                let size = pyramid.size();
+                render::draw(&cr, Some(&pyramid), size, &state, &ViewCache::default());
+                surface.flush();
+            });
+
+            scale_total += scale_time;
+            paint_total += paint_time;
+            println!(
+                "{:>5}  {:>5.2}  {:>8.2}  {:>8.2}",
+                i,
+                scale,
+                ms(scale_time),
+                ms(paint_time)
+            );
+        }
+
+        let n = script.len() as u32;
+        println!(
+            "mean          {:>8.2}  {:>8.2}",
+            ms(scale_total / n),
+            ms(paint_total / n)
+        );
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        gtk::gdk_pixbuf::{Colorspace, Pixbuf},
+    };
+
+    #[test]
+    fn script_returns_to_start() {
+        let script = script();
+        assert_eq!(script.first(), Some(&(1.0, (0.0, 0.0))));
+        assert_eq!(script.first(), script.last());
+    }
+
+    #[test]
+    fn replay_runs_offscreen() {
+        let pixbuf = Pixbuf::new(Colorspace::Rgb, false, 8, 32, 16).unwrap();
+        replay(Frame::from_pixbuf(&pixbuf).unwrap(), usize::MAX).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+//! Picks how the capture gets on screen: through [`gpu::Renderer`] when the `GLArea` has a
+//! working context, or by painting a [`Pyramid`] with cairo on a plain `DrawingArea` otherwise.
+
+#[cfg(feature = "gl")]
+use crate::gpu;
+use {
+    crate::{
+        capture::Frame,
+        pyramid::Pyramid,
+        render::{self, ViewCache},
+        stats::{Report, Stats},
+        view::{screen_size, ImageState},
+    },
+    gtk::{glib::clone, prelude::*},
+    std::{cell::RefCell, rc::Rc, time::Instant},
+};
+
+/// Stand-in for builds without the `gl` feature that never initializes, so cairo is always used.
+#[cfg(not(feature = "gl"))]
+mod gpu {
+    use crate::{capture::Frame, view::ImageState, Error};
+
+    pub enum Renderer {}
+
+    impl Renderer {
+        pub fn new(_uses_es: bool) -> Result<Self, Error> {
+            Err(Error::Gl("built without the `gl` feature".into()))
+        }
+
+        pub fn upload(&mut self, _frame: &Frame) -> Result<(), Error> {
+            match *self {}
+        }
+
+        pub fn draw(&self, _viewport: (i32, i32), _device_scale: i32, _state: &ImageState) {
+            match *self {}
+        }
+
+        pub fn destroy(self) {
+            match self {}
+        }
+    }
+}
+
+pub struct Canvas {
+    budget: usize,
+    low_power: bool,
+    /// What the capture is shown through.
+    state: Rc<ImageState>,
+    redraw: Rc<dyn Fn()>,
+    frame: RefCell<Option<Frame>>,
+    gl: RefCell<Option<gpu::Renderer>>,
+    /// Capture waiting to be uploaded from within the GL context.
+    pending: RefCell<Option<Frame>>,
+    pyramid: RefCell<Option<Pyramid>>,
+    view: ViewCache,
+    /// Holds the `gl` and `cairo` drawing widgets, showing the one in use.
+    stack: RefCell<Option<gtk::Stack>>,
+    stats: Stats,
+}
+
+impl Canvas {
+    /// `budget` bounds the memory of the cairo path, `redraw` is called when it has new copies.
+    /// In `low_power` mode the cairo path doesn't pre-scale, otherwise it prepares copies for the
+    /// scales a zoom step of `state` away from the current one.
+    pub fn new<F: Fn() + 'static>(
+        budget: usize,
+        low_power: bool,
+        state: Rc<ImageState>,
+        redraw: F,
+    ) -> Rc<Self> {
+        Rc::new(Self {
+            budget,
+            low_power,
+            state,
+            redraw: Rc::new(redraw),
+            frame: RefCell::new(None),
+            gl: RefCell::new(None),
+            pending: RefCell::new(None),
+            pyramid: RefCell::new(None),
+            view: ViewCache::default(),
+            stack: RefCell::new(None),
+            stats: Stats::default(),
+        })
+    }
+
+    /// Show `frame`, replacing whatever was shown before.
+    pub fn set_frame(&self, frame: Frame) {
+        self.state.pixels.set(Some((frame.width(), frame.height())));
+        self.frame.replace(Some(frame.clone()));
+        match self.gl.borrow().is_some() {
+            true => {
+                self.pending.replace(Some(frame));
+            }
+            false => self.use_cairo(frame),
+        }
+    }
+
+    /// The capture being shown, at full resolution.
+    pub fn frame(&self) -> Option<Frame> {
+        self.frame.borrow().clone()
+    }
+
+    /// How drawing has been going lately.
+    pub fn report(&self) -> Report {
+        match (self.gl.borrow().is_some(), self.pyramid.borrow().as_ref()) {
+            (true, _) => {
+                // A single texture of the whole capture
+                let texture = self
+                    .frame
+                    .borrow()
+                    .as_ref()
+                    .map_or(0, |f| f.width() as usize * f.height() as usize * 4);
+                self.stats.report("gl", texture, None)
+            }
+            (false, Some(pyramid)) => {
+                self.stats
+                    .report("cairo", pyramid.bytes(), pyramid.last_scaled())
+            }
+            (false, None) => self.stats.report("cairo", 0, None),
+        }
+    }
+
+    fn use_cairo(&self, frame: Frame) {
+        let redraw = self.redraw.clone();
+        let pyramid = Pyramid::new(frame, self.budget, move || redraw())
+            .with_step(self.state.zoom_step.get());
+        self.view.clear();
+        self.pyramid.replace(Some(match self.low_power {
+            true => pyramid.without_prescaling(),
+            false => pyramid,
+        }));
+    }
+
+    /// Stop using GL, moving a capture that hasn't been uploaded yet over to cairo.
+    fn downgrade(&self, reason: &dyn std::fmt::Display) {
+        tracing::warn!(%reason, "OpenGL rendering unavailable, falling back to cairo");
+        if let Some(renderer) = self.gl.take() {
+            renderer.destroy();
+        }
+        if let Some(frame) = self.pending.take() {
+            self.use_cairo(frame);
+        }
+        if let Some(stack) = self.stack.borrow().as_ref() {
+            stack.set_visible_child_name("cairo");
+        }
+    }
+
+    /// Draw on the children of `stack`: a `GLArea` named `gl` and a `DrawingArea` named `cairo`,
+    /// which is switched to if GL doesn't work out.
+    pub fn connect(self: &Rc<Self>, stack: &gtk::Stack) {
+        let state = &self.state;
+        let child = |name| {
+            stack
+                .child_by_name(name)
+                .unwrap_or_else(|| panic!("canvas has no {} child", name))
+        };
+        let area: gtk::GLArea = child("gl").downcast().unwrap();
+        let fallback: gtk::DrawingArea = child("cairo").downcast().unwrap();
+        self.stack.replace(Some(stack.clone()));
+
+        area.connect_realize(clone!(@strong self as canvas => move |area| {
+            area.make_current();
+            if let Some(e) = area.error() {
+                canvas.downgrade(&e);
+                return;
+            }
+            let uses_es = area.context().map(|c| c.uses_es()).unwrap_or(false);
+            match gpu::Renderer::new(uses_es) {
+                Ok(renderer) => {
+                    canvas.gl.replace(Some(renderer));
+                }
+                Err(e) => canvas.downgrade(&e),
+            }
+        }));
+
+        area.connect_unrealize(clone!(@strong self as canvas => move |area| {
+            area.make_current();
+            if let Some(renderer) = canvas.gl.take() {
+                renderer.destroy();
+            }
+        }));
+
+        area.connect_render(
+            clone!(@strong self as canvas, @strong state => move |area, _| {
+                if let Some(frame) = canvas.pending.take() {
+                    let uploaded = match canvas.gl.borrow_mut().as_mut() {
+                        Some(renderer) => renderer.upload(&frame),
+                        None => Ok(()),
+                    };
+                    if let Err(e) = uploaded {
+                        canvas.pending.replace(Some(frame));
+                        canvas.downgrade(&e);
+                        return Inhibit(true);
+                    }
+                }
+                if let Some(renderer) = canvas.gl.borrow().as_ref() {
+                    let factor = area.scale_factor();
+                    let size = (area.allocated_width() * factor, area.allocated_height() * factor);
+                    let start = Instant::now();
+                    renderer.draw(size, factor, &state);
+                    canvas.stats.frame(start.elapsed());
+                }
+                Inhibit(true)
+            }),
+        );
+
+        fallback.connect_draw(
+            clone!(@strong self as canvas, @strong state => move |area, ctx| {
+                let start = Instant::now();
+                let pyramid = canvas.pyramid.borrow();
+                render::draw(ctx, pyramid.as_ref(), screen_size(area), &state, &canvas.view);
+                canvas.stats.frame(start.elapsed());
+                Inhibit(true)
+            }),
+        );
+    }
+}
@@ -0,0 +1,756 @@
+use {
+    crate::{
+        config::{CAPTURE_ATTEMPTS, CAPTURE_BACKOFF_MS},
+        outputs,
+        tree::Rect,
+        Error,
+    },
+    gtk::{
+        gdk_pixbuf::{Colorspace, Pixbuf},
+        glib::Bytes,
+    },
+    memmap2::Mmap,
+    std::{
+        env,
+        fs::File,
+        io::{self, Cursor},
+        os::fd::{FromRawFd, OwnedFd},
+        path::Path,
+        process::{Command, Output as ProcessOutput, Stdio},
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+/// External program used for capturing, and the package that ships it.
+#[derive(Debug, Clone, Copy)]
+pub struct Tool {
+    pub name: &'static str,
+    pub package: &'static str,
+}
+
+pub const GRIM: Tool = Tool {
+    name: "grim",
+    package: "grim",
+};
+
+pub const WAYSHOT: Tool = Tool {
+    name: "wayshot",
+    package: "wayshot",
+};
+
+pub const SLURP: Tool = Tool {
+    name: "slurp",
+    package: "slurp",
+};
+
+impl Tool {
+    /// Whether the program can be found in `PATH`.
+    pub fn is_installed(&self) -> bool {
+        env::var_os("PATH")
+            .map(|paths| env::split_paths(&paths).any(|dir| dir.join(self.name).is_file()))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn missing(&self) -> Error {
+        Error::MissingTool {
+            tool: self.name,
+            package: self.package,
+        }
+    }
+
+    /// Run the program with `args`, failing with what it printed on stderr if it exits with an
+    /// error.
+    pub(crate) fn run(&self, args: &[&str]) -> Result<ProcessOutput, Error> {
+        self.check(Command::new(self.name).args(args))
+    }
+
+    fn check(&self, command: &mut Command) -> Result<ProcessOutput, Error> {
+        let output = command.output().map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => self.missing(),
+            _ => e.into(),
+        })?;
+        match output.status.success() {
+            true => Ok(output),
+            false => Err(self.failed(&output)),
+        }
+    }
+
+    pub(crate) fn failed(&self, output: &ProcessOutput) -> Error {
+        Error::ToolFailed {
+            tool: self.name,
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+    }
+}
+
+/// Whether we're running under sway, which exports its IPC socket path in `SWAYSOCK`.
+pub fn is_sway() -> bool {
+    env::var_os("SWAYSOCK").is_some()
+}
+
+/// Fail early when there's no way to capture, naming the tool to install.
+pub fn check_tools() -> Result<(), Error> {
+    // With screencopy the tools are only needed for capturing everything, which is what happens
+    // when the focused output isn't known, and the portal can't be checked for without asking it
+    // for a screenshot
+    if (outputs::knows_focus() && cfg!(feature = "screencopy")) || cfg!(feature = "portal") {
+        return Ok(());
+    }
+    match GRIM.is_installed() || WAYSHOT.is_installed() {
+        true => Ok(()),
+        false => Err(GRIM.missing()),
+    }
+}
+
+/// Name of the currently focused output.
+#[tracing::instrument]
+pub fn focused_output() -> Result<String, Error> {
+    outputs::list()?
+        .into_iter()
+        .filter_map(|o| match o.focused {
+            true => Some(o.name),
+            false => None,
+        })
+        .next()
+        .ok_or(Error::NoOutput)
+        .inspect(|name| tracing::info!(output = %name, "focused output"))
+}
+
+/// Names of the enabled outputs.
+pub fn active_outputs() -> Result<Vec<String>, Error> {
+    Ok(outputs::list()?
+        .into_iter()
+        .filter(|o| o.active)
+        .map(|o| o.name)
+        .collect())
+}
+
+/// Where the output called `name` is in the layout, in logical pixels.
+pub fn output_rect(name: &str) -> Result<Rect, Error> {
+    outputs::list()?
+        .into_iter()
+        .find(|o| o.name == name)
+        .map(|o| o.rect)
+        .ok_or_else(|| Error::UnknownOutput(name.to_string()))
+}
+
+/// Physical pixels per logical pixel of the output called `name`, which its captures have.
+pub fn output_scale(name: &str) -> Result<f64, Error> {
+    outputs::list()?
+        .into_iter()
+        .find(|o| o.name == name)
+        .map(|o| o.scale)
+        .ok_or_else(|| Error::UnknownOutput(name.to_string()))
+}
+
+/// The output `region` starts on, and where that output is in the layout.
+pub fn output_containing(region: &Rect) -> Result<(String, Rect), Error> {
+    let corner = (region.x as f64, region.y as f64);
+    outputs::list()?
+        .into_iter()
+        .find(|o| o.active && o.rect.contains(corner))
+        .map(|o| (o.name, o.rect))
+        .ok_or(Error::NoOutput)
+}
+
+/// Parse a region the way slurp prints and grim takes it: `X,Y WxH` in layout coordinates.
+pub fn parse_region(s: &str) -> Option<Rect> {
+    let (pos, size) = s.trim().split_once(' ')?;
+    let (x, y) = pos.split_once(',')?;
+    let (width, height) = size.split_once('x')?;
+    let rect = Rect {
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+        width: width.parse().ok()?,
+        height: height.parse().ok()?,
+    };
+    (rect.width > 0 && rect.height > 0).then_some(rect)
+}
+
+/// Have the user drag out a region with slurp.
+pub fn select_region() -> Result<Rect, Error> {
+    // slurp fails saying so when the selection is cancelled
+    let output = SLURP.run(&[])?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    parse_region(&text).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("slurp printed `{}`", text.trim()),
+        )
+        .into()
+    })
+}
+
+/// Output to capture by default: the focused one, or `None` (everything) when the compositor
+/// doesn't say which one that is.
+pub fn default_output() -> Result<Option<String>, Error> {
+    match outputs::knows_focus() {
+        true => focused_output().map(Some),
+        false => {
+            tracing::warn!(
+                "the compositor doesn't say which output has focus, capturing all outputs"
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Image as it comes out of capturing or loading, before the main loop sees it.
+#[derive(Debug, Clone)]
+pub enum Capture {
+    /// Encoded image data, decoded by [`Capture::into_frame`]
+    Encoded(Vec<u8>),
+    /// Pixels that are ready to use as they are
+    Raw(Frame),
+}
+
+impl Capture {
+    /// Decode if necessary. This can take a while for large captures, so call it off the main
+    /// thread.
+    pub fn into_frame(self) -> Result<Frame, Error> {
+        match self {
+            Self::Encoded(img) => Frame::decode(img),
+            Self::Raw(frame) => Ok(frame),
+        }
+    }
+}
+
+/// What to capture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target<'a> {
+    /// An output, or all of them.
+    Output(Option<&'a str>),
+    Region(Rect),
+}
+
+/// A way of capturing the screen. [`BACKENDS`] are tried in order until one of them works.
+trait Backend: Sync {
+    fn name(&self) -> &'static str;
+
+    /// Capture `target`, with the pointer in it if `cursor`, or `None` if this backend can't
+    /// capture that.
+    fn capture(&self, target: Target<'_>, cursor: bool) -> Option<Result<Frame, Error>>;
+}
+
+/// Straight from the compositor over wlr-screencopy, for a single output.
+#[cfg(feature = "screencopy")]
+struct Screencopy;
+
+#[cfg(feature = "screencopy")]
+impl Backend for Screencopy {
+    fn name(&self) -> &'static str {
+        "screencopy"
+    }
+
+    fn capture(&self, target: Target<'_>, cursor: bool) -> Option<Result<Frame, Error>> {
+        match target {
+            Target::Output(Some(output)) => Some(crate::screencopy::capture(output, cursor)),
+            _ => None,
+        }
+    }
+}
+
+struct Grim;
+
+impl Backend for Grim {
+    fn name(&self) -> &'static str {
+        GRIM.name
+    }
+
+    fn capture(&self, target: Target<'_>, cursor: bool) -> Option<Result<Frame, Error>> {
+        Some(grim(target, cursor))
+    }
+}
+
+struct Wayshot;
+
+impl Backend for Wayshot {
+    fn name(&self) -> &'static str {
+        WAYSHOT.name
+    }
+
+    fn capture(&self, target: Target<'_>, cursor: bool) -> Option<Result<Frame, Error>> {
+        let mut args = vec![];
+        if cursor {
+            args.push("--cursor".to_string());
+        }
+        match target {
+            Target::Output(Some(output)) => args.extend(["-o".to_string(), output.to_string()]),
+            Target::Output(None) => {}
+            Target::Region(r) => args.extend([
+                "-s".to_string(),
+                format!("{} {} {} {}", r.x, r.y, r.width, r.height),
+            ]),
+        }
+        args.push("--stdout".into());
+        let args: Vec<_> = args.iter().map(String::as_str).collect();
+        Some(
+            WAYSHOT
+                .run(&args)
+                .and_then(|output| Frame::decode(output.stdout)),
+        )
+    }
+}
+
+/// Through xdg-desktop-portal, which works wherever a portal backend is running but only
+/// captures everything.
+#[cfg(feature = "portal")]
+struct Portal;
+
+#[cfg(feature = "portal")]
+impl Backend for Portal {
+    fn name(&self) -> &'static str {
+        "portal"
+    }
+
+    /// The portal decides on the pointer itself.
+    fn capture(&self, target: Target<'_>, _cursor: bool) -> Option<Result<Frame, Error>> {
+        match target {
+            Target::Output(None) => Some(crate::portal::screenshot().and_then(Frame::decode)),
+            _ => None,
+        }
+    }
+}
+
+const BACKENDS: &[&dyn Backend] = &[
+    #[cfg(feature = "screencopy")]
+    &Screencopy,
+    &Grim,
+    &Wayshot,
+    #[cfg(feature = "portal")]
+    &Portal,
+];
+
+/// Capture `target` with the first backend that manages to. If none does, the error of the
+/// first one that tried is the most useful.
+fn capture_once(target: Target<'_>, cursor: bool) -> Result<Frame, Error> {
+    let mut first = None;
+    for backend in BACKENDS {
+        match backend.capture(target, cursor) {
+            None => continue,
+            Some(Ok(frame)) => {
+                tracing::debug!(backend = backend.name(), "captured");
+                return Ok(frame);
+            }
+            Some(Err(e)) => {
+                tracing::warn!(error = %e, backend = backend.name(), "capture failed");
+                first.get_or_insert(e);
+            }
+        }
+    }
+    // Grim takes any target, so this is never reached
+    Err(first.unwrap_or_else(|| GRIM.missing()))
+}
+
+fn grim_args(target: Target<'_>, cursor: bool, format: &str) -> Vec<String> {
+    let mut args = vec!["-t".to_string(), format.to_string()];
+    if cursor {
+        args.push("-c".into());
+    }
+    match target {
+        Target::Output(Some(output)) => args.extend(["-o".to_string(), output.to_string()]),
+        Target::Output(None) => {}
+        Target::Region(r) => args.extend([
+            "-g".to_string(),
+            format!("{},{} {}x{}", r.x, r.y, r.width, r.height),
+        ]),
+    }
+    args.push("-".into());
+    args
+}
+
+/// Have grim write uncompressed pixels into a memfd and map it as the frame's pixel data, so
+/// there's no PNG to encode or decode and nothing is copied out of a pipe.
+fn screenshot_shm(target: Target<'_>, cursor: bool) -> Result<Frame, Error> {
+    let fd = unsafe { libc::memfd_create(c"sway-boomer-capture".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+
+    GRIM.check(
+        Command::new(GRIM.name)
+            .args(grim_args(target, cursor, "ppm"))
+            .stdout(Stdio::from(file.try_clone()?)),
+    )?;
+    if file.metadata()?.len() == 0 {
+        return Err(Error::Decode);
+    }
+
+    Frame::from_ppm(unsafe { Mmap::map(&file)? })
+}
+
+fn grim(target: Target<'_>, cursor: bool) -> Result<Frame, Error> {
+    match screenshot_shm(target, cursor) {
+        Ok(frame) => Ok(frame),
+        Err(e @ Error::MissingTool { .. }) => Err(e),
+        Err(e) => {
+            tracing::warn!(error = %e, "shared memory capture failed, reading PNG from grim");
+            let args = grim_args(target, cursor, "png");
+            let args: Vec<_> = args.iter().map(String::as_str).collect();
+            // Decode right away, a broken capture has to be noticed while it can still be retried
+            Frame::decode(GRIM.run(&args)?.stdout)
+        }
+    }
+}
+
+/// Call `f` up to `attempts` times, doubling the pause between attempts from `backoff`.
+/// Missing tools aren't going to appear by waiting, so those fail right away.
+fn retry<T>(
+    attempts: u32,
+    backoff: Duration,
+    mut f: impl FnMut() -> Result<T, Error>,
+) -> Result<T, Error> {
+    let mut delay = backoff;
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(e) if attempt < attempts && !matches!(e, Error::MissingTool { .. }) => {
+                tracing::warn!(error = %e, attempt, "capture failed, retrying");
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            ret => return ret,
+        }
+    }
+}
+
+/// Capture `output`, or all outputs, with the first backend that works: screencopy when built
+/// with it, grim, wayshot, and last the desktop portal when built with it. The pointer is left in
+/// if `cursor` is set.
+///
+/// Right after an output change grim can return an empty or broken image, so failed captures are
+/// retried a few times before giving up.
+#[tracing::instrument]
+pub fn screenshot(output: Option<&str>, cursor: bool) -> Result<Capture, Error> {
+    let start = Instant::now();
+    let frame = retry(
+        CAPTURE_ATTEMPTS,
+        Duration::from_millis(CAPTURE_BACKOFF_MS),
+        || capture_once(Target::Output(output), cursor),
+    )?;
+    tracing::info!(
+        output = output.unwrap_or("all"),
+        duration_ms = start.elapsed().as_millis() as u64,
+        "captured"
+    );
+    Ok(Capture::Raw(frame))
+}
+
+/// Capture `region` of the layout, retrying like [`screenshot`].
+#[tracing::instrument]
+pub fn screenshot_region(region: Rect, cursor: bool) -> Result<Capture, Error> {
+    let frame = retry(
+        CAPTURE_ATTEMPTS,
+        Duration::from_millis(CAPTURE_BACKOFF_MS),
+        || capture_once(Target::Region(region), cursor),
+    )?;
+    Ok(Capture::Raw(frame))
+}
+
+/// Read an image from disk to show instead of a screenshot.
+#[tracing::instrument]
+pub fn load_file(path: &Path) -> Result<Capture, Error> {
+    Ok(Capture::Encoded(std::fs::read(path)?))
+}
+
+/// Width, height and the offset of the pixel data of a binary PPM with 8-bit channels.
+fn parse_ppm_header(data: &[u8]) -> Option<(i32, i32, usize)> {
+    let mut pos = 0;
+    let mut field = || {
+        while data.get(pos)?.is_ascii_whitespace() {
+            pos += 1;
+        }
+        let start = pos;
+        while !data.get(pos)?.is_ascii_whitespace() {
+            pos += 1;
+        }
+        std::str::from_utf8(&data[start..pos]).ok()
+    };
+    if field()? != "P6" {
+        return None;
+    }
+    let width = field()?.parse().ok()?;
+    let height = field()?.parse().ok()?;
+    if field()? != "255" {
+        return None;
+    }
+    // Exactly one whitespace character separates the header from the pixels
+    Some((width, height, pos + 1))
+}
+
+/// Owned buffer with a header in front of the part that's exposed.
+struct Skip<T> {
+    data: T,
+    offset: usize,
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Skip<T> {
+    fn as_ref(&self) -> &[u8] {
+        &self.data.as_ref()[self.offset..]
+    }
+}
+
+/// Decoded capture that, unlike [`Pixbuf`], can be sent between threads.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    data: Bytes,
+    has_alpha: bool,
+    width: i32,
+    height: i32,
+    rowstride: i32,
+}
+
+impl Frame {
+    /// Decode an encoded image such as grim's PNG output.
+    ///
+    /// Takes the buffer by value so a capture of several megabytes isn't copied once more just to
+    /// be read; call this from a worker thread to keep the main loop responsive.
+    #[tracing::instrument(skip(img), fields(bytes = img.len()))]
+    pub fn decode(img: Vec<u8>) -> Result<Self, Error> {
+        Self::from_pixbuf(&Pixbuf::from_read(Cursor::new(img))?)
+    }
+
+    /// Use the pixels of a binary PPM in place.
+    pub fn from_ppm<T: AsRef<[u8]> + Send + 'static>(data: T) -> Result<Self, Error> {
+        let (width, height, offset) = parse_ppm_header(data.as_ref()).ok_or(Error::Decode)?;
+        let rowstride = width.checked_mul(3).ok_or(Error::Decode)?;
+        let len = (rowstride as usize).checked_mul(height as usize);
+        if width <= 0 || height <= 0 || len != Some(data.as_ref().len() - offset) {
+            return Err(Error::Decode);
+        }
+        Ok(Self {
+            data: Bytes::from_owned(Skip { data, offset }),
+            has_alpha: false,
+            width,
+            height,
+            rowstride,
+        })
+    }
+
+    /// Use unpadded rows of 8-bit RGB pixels.
+    pub fn from_rgb(data: Vec<u8>, width: i32, height: i32) -> Result<Self, Error> {
+        let rowstride = width.checked_mul(3).ok_or(Error::Decode)?;
+        if width <= 0
+            || height <= 0
+            || Some(data.len()) != (rowstride as usize).checked_mul(height as usize)
+        {
+            return Err(Error::Decode);
+        }
+        Ok(Self {
+            data: Bytes::from_owned(data),
+            has_alpha: false,
+            width,
+            height,
+            rowstride,
+        })
+    }
+
+    pub fn from_pixbuf(pixbuf: &Pixbuf) -> Result<Self, Error> {
+        Ok(Self {
+            data: pixbuf.read_pixel_bytes().ok_or(Error::Decode)?,
+            has_alpha: pixbuf.has_alpha(),
+            width: pixbuf.width(),
+            height: pixbuf.height(),
+            rowstride: pixbuf.rowstride(),
+        })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.has_alpha
+    }
+
+    /// Raw pixel rows, each [`Self::rowstride`] bytes long.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Bytes per row. Decoded images pad rows to 4 bytes, raw captures aren't padded at all.
+    pub fn rowstride(&self) -> i32 {
+        self.rowstride
+    }
+
+    /// Whether every pixel has the same color, which is what captures of outputs grim can't see
+    /// into (DRM leases, some fullscreen games) look like.
+    pub fn is_uniform(&self) -> bool {
+        let channels = if self.has_alpha { 4 } else { 3 };
+        let row_len = (self.width * channels) as usize;
+        let first = match self.data.get(..channels as usize) {
+            Some(first) => first,
+            None => return true,
+        };
+        self.data.chunks(self.rowstride as usize).all(|row| {
+            row[..row_len]
+                .chunks(channels as usize)
+                .all(|px| px == first)
+        })
+    }
+
+    /// Wrap the pixel data in a [`Pixbuf`] without copying it.
+    pub fn to_pixbuf(&self) -> Pixbuf {
+        Pixbuf::from_bytes(
+            &self.data,
+            Colorspace::Rgb,
+            self.has_alpha,
+            8,
+            self.width,
+            self.height,
+            self.rowstride,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_tool_is_reported_by_name() {
+        let tool = Tool {
+            name: "sway-boomer-no-such-tool",
+            package: "nothing",
+        };
+        assert!(!tool.is_installed());
+        assert!(matches!(
+            tool.run(&[]),
+            Err(Error::MissingTool {
+                tool: "sway-boomer-no-such-tool",
+                ..
+            })
+        ));
+        assert!(Tool {
+            name: "sh",
+            package: ""
+        }
+        .is_installed());
+    }
+
+    #[test]
+    fn failing_tool_reports_its_stderr() {
+        let sh = Tool {
+            name: "sh",
+            package: "",
+        };
+        let e = sh.run(&["-c", "echo no outputs >&2; exit 3"]).unwrap_err();
+        assert!(matches!(e, Error::ToolFailed { tool: "sh", .. }));
+        assert!(e.to_string().ends_with("no outputs"));
+        assert!(sh.run(&["-c", "true"]).is_ok());
+    }
+
+    #[test]
+    fn failed_captures_are_retried() {
+        let mut calls = 0;
+        let ret = retry(3, Duration::ZERO, || {
+            calls += 1;
+            match calls {
+                3 => Ok(calls),
+                _ => Err(Error::Decode),
+            }
+        });
+        assert_eq!(ret.unwrap(), 3);
+
+        calls = 0;
+        assert!(retry(2, Duration::ZERO, || -> Result<(), _> {
+            calls += 1;
+            Err(Error::Decode)
+        })
+        .is_err());
+        assert_eq!(calls, 2);
+
+        calls = 0;
+        assert!(retry(3, Duration::ZERO, || -> Result<(), _> {
+            calls += 1;
+            Err(GRIM.missing())
+        })
+        .is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn decode_round_trips_png() {
+        let pixbuf = Pixbuf::new(Colorspace::Rgb, false, 8, 3, 2).unwrap();
+        pixbuf.fill(0x336699ff);
+        let png = pixbuf.save_to_bufferv("png", &[]).unwrap();
+
+        let decoded = Frame::decode(png).unwrap().to_pixbuf();
+        assert_eq!((decoded.width(), decoded.height()), (3, 2));
+        assert_eq!(
+            &decoded.read_pixel_bytes().unwrap()[..3],
+            &[0x33, 0x66, 0x99]
+        );
+    }
+
+    #[test]
+    fn ppm_is_used_in_place() {
+        let mut ppm = b"P6\n2 1\n255\n".to_vec();
+        ppm.extend([1, 2, 3, 4, 5, 6]);
+        let frame = Frame::from_ppm(ppm).unwrap();
+        assert_eq!(
+            (frame.width(), frame.height(), frame.rowstride()),
+            (2, 1, 6)
+        );
+        assert_eq!(frame.data(), &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            &frame.to_pixbuf().read_pixel_bytes().unwrap()[..],
+            &[1, 2, 3, 4, 5, 6]
+        );
+    }
+
+    #[test]
+    fn ppm_rejects_truncated_and_unsupported() {
+        assert!(Frame::from_ppm(b"P6\n2 1\n255\n\x01\x02".to_vec()).is_err());
+        assert!(Frame::from_ppm(b"P6\n1 1\n65535\n\x01\x02\x03\x04\x05\x06".to_vec()).is_err());
+        assert!(Frame::from_ppm(b"P5\n1 1\n255\n\x01".to_vec()).is_err());
+        assert!(Frame::from_ppm(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn uniform_frames_are_detected() {
+        let pixbuf = Pixbuf::new(Colorspace::Rgb, false, 8, 5, 3).unwrap();
+        pixbuf.fill(0x000000ff);
+        assert!(Frame::from_pixbuf(&pixbuf).unwrap().is_uniform());
+
+        pixbuf.new_subpixbuf(4, 2, 1, 1).unwrap().fill(0x010000ff);
+        assert!(!Frame::from_pixbuf(&pixbuf).unwrap().is_uniform());
+    }
+
+    #[test]
+    fn regions_are_read_and_passed_on_like_slurp_and_grim() {
+        let region = parse_region("-1920,40 300x200\n").unwrap();
+        assert_eq!(
+            region,
+            Rect {
+                x: -1920,
+                y: 40,
+                width: 300,
+                height: 200
+            }
+        );
+        assert_eq!(
+            grim_args(Target::Region(region), false, "ppm"),
+            ["-t", "ppm", "-g", "-1920,40 300x200", "-"]
+        );
+        assert_eq!(
+            grim_args(Target::Output(Some("DP-1")), true, "png"),
+            ["-t", "png", "-c", "-o", "DP-1", "-"]
+        );
+        assert_eq!(parse_region("0,0 0x10"), None);
+        assert_eq!(parse_region("0,0,10x10"), None);
+    }
+
+    #[test]
+    fn decode_rejects_garbage() {
+        assert!(Frame::decode(b"not an image".to_vec()).is_err());
+    }
+}
@@ -0,0 +1,45 @@
+//! Copying to the Wayland clipboard with `wl-copy`.
+//!
+//! A Wayland clipboard is served by the client that set it, so whatever GTK puts there is gone as
+//! soon as the overlay quits, usually right after copying. `wl-copy` forks and keeps serving it.
+
+use {
+    crate::{capture::Tool, Error},
+    std::{
+        io::{self, Read, Write},
+        process::{Command, Output, Stdio},
+    },
+};
+
+pub const WL_COPY: Tool = Tool {
+    name: "wl-copy",
+    package: "wl-clipboard",
+};
+
+/// Put `data` of the MIME type `mime` on the clipboard.
+#[tracing::instrument(skip(data), fields(bytes = data.len()))]
+pub fn copy(data: &[u8], mime: &str) -> Result<(), Error> {
+    let mut child = Command::new(WL_COPY.name)
+        .args(["--type", mime])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => WL_COPY.missing(),
+            _ => e.into(),
+        })?;
+    // Dropped at the end of the statement, so wl-copy sees the end of its input
+    child.stdin.take().unwrap().write_all(data)?;
+    let status = child.wait()?;
+    if status.success() {
+        return Ok(());
+    }
+    // Only read once it failed, the fork that serves the clipboard keeps stderr open
+    let mut stderr = Vec::new();
+    child.stderr.take().unwrap().read_to_end(&mut stderr)?;
+    Err(WL_COPY.failed(&Output {
+        status,
+        stdout: Vec::new(),
+        stderr,
+    }))
+}
@@ -0,0 +1,182 @@
+//! Text commands for controlling the overlay from outside, one per line:
+//!
+//! ```text
+//! zoom-in | zoom-out | zoom <delta> | scale <scale> | set-scale <scale>
+//! pan <dx> <dy>
+//! reset | reset-logical
+//! highlight on|off
+//! high-contrast
+//! interpolation
+//! lens
+//! filter invert|grayscale|adjust|protanopia|deuteranopia|tritanopia
+//! lock
+//! frame-window
+//! show <x> <y> <width> <height> [scale]
+//! preset <name>
+//! quit
+//! toggle
+//! ```
+//!
+//! `toggle` shows or hides the overlay of a `--daemon`, which is what `--toggle` sends.
+
+use crate::{
+    config::Preset,
+    filter::{ColorFilter, Deficiency},
+    input::Action,
+};
+
+/// What a command line asks for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Do(Action),
+    /// Jump to the preset with this name.
+    Preset(String),
+    /// Show or hide the daemon's overlay.
+    Toggle,
+}
+
+impl Command {
+    /// The action to carry out, looking presets up in `presets`.
+    pub fn action<'a>(
+        &self,
+        mut presets: impl Iterator<Item = (&'a String, &'a Preset)>,
+    ) -> Result<Action, String> {
+        match self {
+            Self::Do(action) => Ok(*action),
+            Self::Preset(name) => presets
+                .find(|(n, _)| *n == name)
+                .map(|(_, p)| Action::ShowRegion(p.region(), p.scale))
+                .ok_or_else(|| format!("no preset called `{}`", name)),
+            Self::Toggle => Err("only a --daemon shows and hides its overlay".into()),
+        }
+    }
+}
+
+/// Parse a single command line.
+pub fn parse(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let name = words.next().ok_or("empty command")?;
+    let args: Vec<&str> = words.collect();
+    let numbers = |min: usize, max: usize| -> Result<Vec<f64>, String> {
+        if !(min..=max).contains(&args.len()) {
+            return Err(format!("wrong number of arguments for `{}`", name));
+        }
+        // NaN or infinity would stick in the view until the overlay restarts
+        args.iter()
+            .map(|a| match a.parse::<f64>() {
+                Ok(n) if n.is_finite() => Ok(n),
+                _ => Err(format!("`{}` is not a number", a)),
+            })
+            .collect()
+    };
+
+    let action = match name {
+        "quit" => Action::Quit,
+        "toggle" if args.is_empty() => return Ok(Command::Toggle),
+        "zoom-in" => Action::ZoomIn,
+        "zoom-out" => Action::ZoomOut,
+        "zoom" => Action::Zoom(numbers(1, 1)?[0]),
+        "scale" | "set-scale" => Action::SetScale(numbers(1, 1)?[0]),
+        "reset" => Action::Reset,
+        "reset-logical" => Action::ResetLogical,
+        "pan" => {
+            let d = numbers(2, 2)?;
+            Action::Pan(d[0], d[1])
+        }
+        "highlight" => match args.as_slice() {
+            ["on"] => Action::Highlight(true),
+            ["off"] => Action::Highlight(false),
+            _ => return Err("`highlight` takes `on` or `off`".into()),
+        },
+        "high-contrast" => Action::ToggleHighContrast,
+        "interpolation" => Action::CycleInterpolation,
+        "lens" => Action::CycleLens,
+        "filter" => match args.as_slice() {
+            ["invert"] => Action::ToggleFilter(ColorFilter::Invert),
+            ["grayscale"] => Action::ToggleFilter(ColorFilter::Grayscale),
+            ["adjust"] => Action::ToggleFilter(ColorFilter::Adjust),
+            ["protanopia"] => Action::ToggleFilter(ColorFilter::Simulate(Deficiency::Protanopia)),
+            ["deuteranopia"] => {
+                Action::ToggleFilter(ColorFilter::Simulate(Deficiency::Deuteranopia))
+            }
+            ["tritanopia"] => Action::ToggleFilter(ColorFilter::Simulate(Deficiency::Tritanopia)),
+            _ => return Err("no such filter".into()),
+        },
+        "lock" => Action::ToggleLock,
+        "frame-window" => Action::FrameWindow,
+        "show" => {
+            let r = numbers(4, 5)?;
+            if r[2] <= 0.0 || r[3] <= 0.0 {
+                return Err("`show` needs a positive width and height".into());
+            }
+            Action::ShowRegion((r[0], r[1], r[2], r[3]), r.get(4).copied())
+        }
+        "preset" => match args.as_slice() {
+            [preset] => return Ok(Command::Preset(preset.to_string())),
+            _ => return Err("`preset` takes a name".into()),
+        },
+        _ => return Err(format!("unknown command `{}`", name)),
+    };
+    Ok(Command::Do(action))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!(parse("zoom-in"), Ok(Command::Do(Action::ZoomIn)));
+        assert_eq!(parse("reset"), Ok(Command::Do(Action::Reset)));
+        assert_eq!(
+            parse("reset-logical"),
+            Ok(Command::Do(Action::ResetLogical))
+        );
+        assert_eq!(parse("set-scale 2"), Ok(Command::Do(Action::SetScale(2.0))));
+        assert_eq!(
+            parse(" pan 10 -5 "),
+            Ok(Command::Do(Action::Pan(10.0, -5.0)))
+        );
+        assert_eq!(
+            parse("show 0 0 3840 40 3"),
+            Ok(Command::Do(Action::ShowRegion(
+                (0.0, 0.0, 3840.0, 40.0),
+                Some(3.0)
+            )))
+        );
+        assert_eq!(
+            parse("filter grayscale"),
+            Ok(Command::Do(Action::ToggleFilter(ColorFilter::Grayscale)))
+        );
+        assert_eq!(
+            parse("preset statusbar"),
+            Ok(Command::Preset("statusbar".into()))
+        );
+        assert_eq!(parse("toggle"), Ok(Command::Toggle));
+    }
+
+    #[test]
+    fn bad_commands_are_rejected() {
+        assert!(parse("").is_err());
+        assert!(parse("zoom").is_err());
+        assert!(parse("pan 1 x").is_err());
+        assert!(parse("highlight maybe").is_err());
+        assert!(parse("rm -rf").is_err());
+    }
+
+    #[test]
+    fn non_finite_numbers_are_rejected() {
+        assert!(parse("scale NaN").is_err());
+        assert!(parse("set-scale inf").is_err());
+        assert!(parse("zoom -inf").is_err());
+        assert!(parse("pan inf 0").is_err());
+        assert!(parse("show 0 0 100 NaN").is_err());
+    }
+
+    #[test]
+    fn show_needs_a_positive_size() {
+        assert!(parse("show 0 0 0 40").is_err());
+        assert!(parse("show 0 0 100 -40").is_err());
+        assert!(parse("show -10 -10 100 40").is_ok());
+    }
+}
@@ -0,0 +1,634 @@
+use {
+    crate::{
+        filter::{HighContrast, Polarity},
+        view::Interpolation,
+        Error,
+    },
+    serde::Deserialize,
+    std::{
+        collections::BTreeMap,
+        env, fs, io,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Escape. Key bindings are X keysyms as listed in `xkbcommon-keysyms.h`.
+pub const QUIT_KEY: u32 = 0xff1b;
+/// Left shift.
+pub const HIGHLIGHT_KEY: u32 = 0xffe1;
+pub const HIGH_CONTRAST_KEY: u32 = 'h' as u32;
+/// Zoom step, relative to the scale it's taken from.
+pub const SCALE_DELTA: f64 = 0.1;
+/// Lowest scale, for seeing the whole capture small.
+pub const SCALE_MIN: f64 = 0.1;
+/// Highest scale, enough to tell subpixels apart.
+pub const SCALE_MAX: f64 = 16.0;
+/// Zoom step while control is held.
+pub const FINE_SCALE_DELTA: f64 = 0.02;
+pub const BACKGROUND: (f64, f64, f64) = (0.1, 0.1, 0.1);
+pub const HIGHLIGHT_RADIUS: f64 = 70.0;
+/// Range scrolling with the highlight held keeps its radius in.
+pub const HIGHLIGHT_RADIUS_MIN: f64 = 10.0;
+pub const HIGHLIGHT_RADIUS_MAX: f64 = 1000.0;
+/// Pixel density assumed for lengths in millimeters when the output doesn't report its size.
+pub const FALLBACK_PX_PER_MM: f64 = 96.0 / 25.4;
+pub const HIGHLIGHT_STYLE: (f64, f64, f64, f64) = (1.0, 1.0, 1.0, 0.4);
+pub const MEMORY_BUDGET_MIB: usize = 512;
+pub const CAPTURE_ATTEMPTS: u32 = 3;
+pub const CAPTURE_BACKOFF_MS: u64 = 100;
+pub const OCR_KEY: u32 = 't' as u32;
+/// Page up and down, which presentation clickers send.
+pub const ZOOM_IN_KEY: u32 = 0xff55;
+pub const ZOOM_OUT_KEY: u32 = 0xff56;
+/// Screen pixels one step of horizontal scrolling pans by.
+pub const SCROLL_PAN_STEP: f64 = 60.0;
+/// Screen pixels an arrow key pans by, per press or key repeat.
+pub const KEY_PAN_STEP: f64 = 40.0;
+/// Arrow keys.
+pub const PAN_LEFT_KEY: u32 = 0xff51;
+pub const PAN_UP_KEY: u32 = 0xff52;
+pub const PAN_RIGHT_KEY: u32 = 0xff53;
+pub const PAN_DOWN_KEY: u32 = 0xff54;
+/// Back to scale 1 without any offset, the desktop pixel for pixel.
+pub const RESET_KEY: u32 = '0' as u32;
+/// Back to a capture pixel per logical pixel, which on a HiDPI output is zoomed in by its scale.
+pub const RESET_LOGICAL_KEY: u32 = '9' as u32;
+/// Switches between nearest, bilinear and automatic interpolation.
+pub const INTERPOLATION_KEY: u32 = 'i' as u32;
+/// With automatic interpolation, the scale from which pixels are shown as sharp squares.
+pub const NEAREST_FROM_SCALE: f64 = 2.0;
+/// Toggle inverting colors, grayscale, and the brightness and contrast adjustment.
+pub const INVERT_KEY: u32 = 'n' as u32;
+pub const GRAYSCALE_KEY: u32 = 'g' as u32;
+pub const ADJUST_KEY: u32 = 'b' as u32;
+/// Goes through simulating protanopia, deuteranopia, tritanopia and none of them.
+pub const SIMULATE_KEY: u32 = 'v' as u32;
+/// What the adjustment adds to every channel, and how much further from mid gray it takes them.
+pub const BRIGHTNESS: f64 = 0.1;
+pub const CONTRAST: f64 = 1.3;
+pub const HIGH_CONTRAST: HighContrast = HighContrast {
+    threshold: 0.5,
+    softness: 0.08,
+    polarity: Polarity::Normal,
+};
+pub const CURSOR_FILL: (f64, f64, f64) = (1.0, 1.0, 1.0);
+pub const CURSOR_OUTLINE: (f64, f64, f64) = (0.0, 0.0, 0.0);
+/// Share of the screen height taken by the magnified band with `--dock`.
+pub const DOCK_HEIGHT: f64 = 0.25;
+/// Line between the magnified band or lens and the rest of the screen.
+pub const DOCK_BORDER: (f64, f64, f64) = (0.9, 0.9, 0.9);
+pub const DOCK_BORDER_WIDTH: f64 = 2.0;
+/// Goes through a round lens at the pointer, a rectangular one and none.
+pub const LENS_KEY: u32 = 'o' as u32;
+/// How much the lens magnifies on top of the view, and how far it reaches from the pointer.
+pub const LENS_SCALE: f64 = 2.0;
+pub const LENS_RADIUS: f64 = 120.0;
+/// Width of the rectangular lens per height, enough for a few words of a line of text.
+pub const LENS_ASPECT: f64 = 2.5;
+pub const LOCK_KEY: u32 = 'l' as u32;
+/// Zooms in on the window under the pointer.
+pub const FRAME_KEY: u32 = 'f' as u32;
+/// Held down, dragging with any mouse button pans.
+pub const PAN_KEY: u32 = ' ' as u32;
+/// Toggles the frame rate and timings overlay (F12).
+pub const STATS_KEY: u32 = 0xffc9;
+/// Captures the output again, keeping the view (F5).
+pub const RECAPTURE_KEY: u32 = 0xffc2;
+/// Shows the color under the pointer and copies it.
+pub const PICK_KEY: u32 = 'p' as u32;
+/// How long the picked color stays on screen.
+pub const PICK_SHOWN_MS: u64 = 2000;
+/// Toggles drawing annotations with the left button.
+pub const DRAW_KEY: u32 = 'd' as u32;
+/// Takes back the last annotation.
+pub const UNDO_KEY: u32 = 'u' as u32;
+pub const DRAW_COLOR: (f64, f64, f64, f64) = (1.0, 0.2, 0.2, 1.0);
+/// Width of annotation lines in screen pixels, at the scale they're drawn at.
+pub const DRAW_WIDTH: f64 = 4.0;
+/// Saves what's on screen as a PNG.
+pub const SAVE_KEY: u32 = 's' as u32;
+/// Where views are saved, with `~` for the home directory and the date and time as in `date(1)`.
+pub const SAVE_PATH: &str = "~/Pictures/sway-boomer-%Y%m%d-%H%M%S.png";
+/// With Ctrl, copies the selection or the whole view as an image.
+pub const COPY_KEY: u32 = 'c' as u32;
+/// Outline of the region selected by dragging with Ctrl held.
+pub const SELECTION_COLOR: (f64, f64, f64, f64) = (1.0, 1.0, 1.0, 0.9);
+/// Toggles the thumbnail of the whole capture shown while zoomed in.
+pub const MINIMAP_KEY: u32 = 'm' as u32;
+/// Width of the minimap in screen pixels.
+pub const MINIMAP_WIDTH: f64 = 240.0;
+/// How long the minimap stays after the view last moved.
+pub const MINIMAP_IDLE_MS: u64 = 1000;
+/// How long the zoom level stays on screen after the scale last changed.
+pub const ZOOM_LEVEL_SHOWN_MS: u64 = 1000;
+/// How long things shown for a moment take to fade out.
+pub const FADE_MS: f64 = 300.0;
+/// How long the overlay stays hidden before capturing again, so the output is repainted without it.
+pub const RECAPTURE_DELAY_MS: u64 = 150;
+/// Screen pixels per second the view pans at with a gamepad stick pushed all the way.
+pub const GAMEPAD_PAN_SPEED: f64 = 1200.0;
+pub const GAMEPAD_DEADZONE: f64 = 0.15;
+/// Screen pixels within which panning snaps the view onto window edges.
+pub const SNAP_DISTANCE: f64 = 12.0;
+/// Image pixels left around a window when zooming in on it.
+pub const FRAME_MARGIN: f64 = 16.0;
+/// How long gliding the view over to a newly focused window or another scale takes.
+pub const PAN_DURATION_MS: f64 = 250.0;
+/// How quickly a flung view slows down, the share of its speed it loses per second is
+/// `1 - e^-FLING_FRICTION`.
+pub const FLING_FRICTION: f64 = 4.0;
+/// Screen pixels per second below which a flung view stops.
+pub const FLING_MIN_SPEED: f64 = 30.0;
+/// A drag released after holding the pointer still this long doesn't fling.
+pub const FLING_IDLE_MS: u32 = 50;
+/// Size of the `--magnifier` window in logical pixels, how much it magnifies and how far it stays
+/// from the edges of the output.
+pub const MAGNIFIER_SIZE: (i32, i32) = (400, 240);
+pub const MAGNIFIER_SCALE: f64 = 3.0;
+pub const MAGNIFIER_MARGIN: i32 = 16;
+/// How often the magnifier captures what's around the pointer again.
+pub const MAGNIFIER_INTERVAL_MS: u64 = 100;
+
+/// Settings from `config.toml`, see [`load`].
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Regions to jump to, by name.
+    pub preset: BTreeMap<String, Preset>,
+    pub midi: MidiConfig,
+    /// How strongly scrolling zooms and pans, by kind of device.
+    pub sensitivity: Sensitivities,
+    pub view: ViewConfig,
+    pub draw: DrawConfig,
+    pub save: SaveConfig,
+    pub magnifier: MagnifierConfig,
+    pub remote: RemoteConfig,
+    pub keys: KeyNames,
+}
+
+/// Zoom steps and colors, defaulting to the constants above.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ViewConfig {
+    /// How much one zoom step changes the scale by, relative to the scale it's at.
+    pub zoom_step: f64,
+    pub min_scale: f64,
+    pub max_scale: f64,
+    pub background: (f64, f64, f64),
+    pub highlight_radius: f64,
+    pub highlight_color: (f64, f64, f64, f64),
+    /// Darken everything outside the highlight by this much (0 to 1) instead of tinting inside it.
+    pub dim_outside: Option<f64>,
+    pub interpolation: Interpolation,
+    /// Brightness and contrast the adjustment key switches to.
+    pub brightness: f64,
+    pub contrast: f64,
+    /// Magnification of the lens relative to the view, and its radius or half height.
+    pub lens_scale: f64,
+    pub lens_radius: f64,
+}
+
+impl Default for ViewConfig {
+    fn default() -> Self {
+        Self {
+            zoom_step: SCALE_DELTA,
+            min_scale: SCALE_MIN,
+            max_scale: SCALE_MAX,
+            background: BACKGROUND,
+            highlight_radius: HIGHLIGHT_RADIUS,
+            highlight_color: HIGHLIGHT_STYLE,
+            dim_outside: None,
+            interpolation: Interpolation::Auto,
+            brightness: BRIGHTNESS,
+            contrast: CONTRAST,
+            lens_scale: LENS_SCALE,
+            lens_radius: LENS_RADIUS,
+        }
+    }
+}
+
+impl ViewConfig {
+    /// Make sure `min_scale` and `max_scale` are positive and in order, once the command line
+    /// has had its say.
+    pub fn check_scales(&self) -> Result<(), Error> {
+        let (min, max) = (self.min_scale, self.max_scale);
+        match min > 0.0 && max.is_finite() && min <= max {
+            true => Ok(()),
+            false => Err(Error::ScaleRange { min, max }),
+        }
+    }
+}
+
+/// Keysyms of the key bindings, lowercase for letters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keys {
+    pub quit: u32,
+    pub highlight: u32,
+    pub high_contrast: u32,
+    pub ocr: u32,
+    pub zoom_in: u32,
+    pub zoom_out: u32,
+    pub lock: u32,
+    pub frame: u32,
+    pub pan: u32,
+    pub stats: u32,
+    pub recapture: u32,
+    pub pan_left: u32,
+    pub pan_right: u32,
+    pub pan_up: u32,
+    pub pan_down: u32,
+    pub reset: u32,
+    pub reset_logical: u32,
+    pub pick: u32,
+    pub draw: u32,
+    pub undo: u32,
+    pub save: u32,
+    pub copy: u32,
+    pub minimap: u32,
+    pub interpolation: u32,
+    pub invert: u32,
+    pub grayscale: u32,
+    pub adjust: u32,
+    pub simulate: u32,
+    pub lens: u32,
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Self {
+            quit: QUIT_KEY,
+            highlight: HIGHLIGHT_KEY,
+            high_contrast: HIGH_CONTRAST_KEY,
+            ocr: OCR_KEY,
+            zoom_in: ZOOM_IN_KEY,
+            zoom_out: ZOOM_OUT_KEY,
+            lock: LOCK_KEY,
+            frame: FRAME_KEY,
+            pan: PAN_KEY,
+            stats: STATS_KEY,
+            recapture: RECAPTURE_KEY,
+            pan_left: PAN_LEFT_KEY,
+            pan_right: PAN_RIGHT_KEY,
+            pan_up: PAN_UP_KEY,
+            pan_down: PAN_DOWN_KEY,
+            reset: RESET_KEY,
+            reset_logical: RESET_LOGICAL_KEY,
+            pick: PICK_KEY,
+            draw: DRAW_KEY,
+            undo: UNDO_KEY,
+            save: SAVE_KEY,
+            copy: COPY_KEY,
+            minimap: MINIMAP_KEY,
+            interpolation: INTERPOLATION_KEY,
+            invert: INVERT_KEY,
+            grayscale: GRAYSCALE_KEY,
+            adjust: ADJUST_KEY,
+            simulate: SIMULATE_KEY,
+            lens: LENS_KEY,
+        }
+    }
+}
+
+/// Key bindings to change, as GDK key names such as `Escape` or `h`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyNames {
+    pub quit: Option<String>,
+    pub highlight: Option<String>,
+    pub high_contrast: Option<String>,
+    pub ocr: Option<String>,
+    pub zoom_in: Option<String>,
+    pub zoom_out: Option<String>,
+    pub lock: Option<String>,
+    pub frame: Option<String>,
+    pub pan: Option<String>,
+    pub stats: Option<String>,
+    pub recapture: Option<String>,
+    pub pan_left: Option<String>,
+    pub pan_right: Option<String>,
+    pub pan_up: Option<String>,
+    pub pan_down: Option<String>,
+    pub reset: Option<String>,
+    pub reset_logical: Option<String>,
+    pub pick: Option<String>,
+    pub draw: Option<String>,
+    pub undo: Option<String>,
+    pub save: Option<String>,
+    pub copy: Option<String>,
+    pub minimap: Option<String>,
+    pub interpolation: Option<String>,
+    pub invert: Option<String>,
+    pub grayscale: Option<String>,
+    pub adjust: Option<String>,
+    pub simulate: Option<String>,
+    pub lens: Option<String>,
+}
+
+impl KeyNames {
+    /// The default bindings with the ones named here replaced by the keysym `keysym` finds for
+    /// the name. Names it doesn't find keep their default.
+    pub fn resolve(&self, keysym: impl Fn(&str) -> Option<u32>) -> Keys {
+        let mut keys = Keys::default();
+        let bindings = [
+            (&self.quit, &mut keys.quit),
+            (&self.highlight, &mut keys.highlight),
+            (&self.high_contrast, &mut keys.high_contrast),
+            (&self.ocr, &mut keys.ocr),
+            (&self.zoom_in, &mut keys.zoom_in),
+            (&self.zoom_out, &mut keys.zoom_out),
+            (&self.lock, &mut keys.lock),
+            (&self.frame, &mut keys.frame),
+            (&self.pan, &mut keys.pan),
+            (&self.stats, &mut keys.stats),
+            (&self.recapture, &mut keys.recapture),
+            (&self.pan_left, &mut keys.pan_left),
+            (&self.pan_right, &mut keys.pan_right),
+            (&self.pan_up, &mut keys.pan_up),
+            (&self.pan_down, &mut keys.pan_down),
+            (&self.reset, &mut keys.reset),
+            (&self.reset_logical, &mut keys.reset_logical),
+            (&self.pick, &mut keys.pick),
+            (&self.draw, &mut keys.draw),
+            (&self.undo, &mut keys.undo),
+            (&self.save, &mut keys.save),
+            (&self.copy, &mut keys.copy),
+            (&self.minimap, &mut keys.minimap),
+            (&self.interpolation, &mut keys.interpolation),
+            (&self.invert, &mut keys.invert),
+            (&self.grayscale, &mut keys.grayscale),
+            (&self.adjust, &mut keys.adjust),
+            (&self.simulate, &mut keys.simulate),
+            (&self.lens, &mut keys.lens),
+        ];
+        for (name, sym) in bindings {
+            if let Some(name) = name {
+                match keysym(name) {
+                    Some(s) => *sym = s,
+                    None => {
+                        tracing::warn!(key = %name, "no key with this name, keeping the default")
+                    }
+                }
+            }
+        }
+        keys
+    }
+}
+
+/// Look of annotations, defaulting to the constants above.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DrawConfig {
+    pub color: (f64, f64, f64, f64),
+    pub width: f64,
+}
+
+impl Default for DrawConfig {
+    fn default() -> Self {
+        Self {
+            color: DRAW_COLOR,
+            width: DRAW_WIDTH,
+        }
+    }
+}
+
+/// Where saved views go.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SaveConfig {
+    /// File name template, see [`SAVE_PATH`].
+    pub path: String,
+}
+
+impl Default for SaveConfig {
+    fn default() -> Self {
+        Self {
+            path: SAVE_PATH.into(),
+        }
+    }
+}
+
+/// Remote control over `--remote`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemoteConfig {
+    /// Token clients have to send first, unless `--remote-token` gives one.
+    pub token: Option<String>,
+}
+
+/// Size and magnification of the `--magnifier` window.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MagnifierConfig {
+    pub width: i32,
+    pub height: i32,
+    pub scale: f64,
+    pub interval_ms: u64,
+}
+
+impl Default for MagnifierConfig {
+    fn default() -> Self {
+        Self {
+            width: MAGNIFIER_SIZE.0,
+            height: MAGNIFIER_SIZE.1,
+            scale: MAGNIFIER_SCALE,
+            interval_ms: MAGNIFIER_INTERVAL_MS,
+        }
+    }
+}
+
+/// Multipliers for how far a scroll step zooms and pans.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sensitivity {
+    pub zoom: f64,
+    pub pan: f64,
+}
+
+impl Default for Sensitivity {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            pan: 1.0,
+        }
+    }
+}
+
+/// [`Sensitivity`] for each kind of scrolling device, as one step of a notched wheel and a
+/// high resolution touchpad have little in common.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sensitivities {
+    /// Mouse wheels, and any device not listed here.
+    pub mouse: Sensitivity,
+    pub touchpad: Sensitivity,
+    pub trackpoint: Sensitivity,
+}
+
+/// What the controls of a MIDI controller do, by controller and note number.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MidiConfig {
+    /// Control change that sets the scale, from the lowest at 0 to the highest at 127.
+    pub zoom: Option<u8>,
+    /// Note that shows the highlight while held.
+    pub spotlight: Option<u8>,
+    /// Presets to jump to, by note number.
+    pub pads: BTreeMap<String, String>,
+}
+
+/// Region of the capture to jump to, in image pixels, shown at `scale` or as large as it fits.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+    pub scale: Option<f64>,
+    /// GDK key name, such as `1` or `F2`, that jumps here.
+    pub key: Option<String>,
+}
+
+impl Preset {
+    pub fn region(&self) -> (f64, f64, f64, f64) {
+        (self.x, self.y, self.w, self.h)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/sway-boomer/config.toml`, or under `~/.config` if that isn't set.
+pub fn default_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(dir.join(env!("CARGO_PKG_NAME")).join("config.toml"))
+}
+
+/// Read the config file at `path`, or at [`default_path`] if `None`. A missing default file
+/// just means the defaults.
+pub fn load(path: Option<&Path>) -> Result<ConfigFile, Error> {
+    let (path, required) = match path {
+        Some(path) => (path.to_owned(), true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(ConfigFile::default()),
+        },
+    };
+    let text = match fs::read_to_string(&path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+            return Ok(ConfigFile::default())
+        }
+        text => text.map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?,
+    };
+    let config: ConfigFile = toml::from_str(&text)?;
+    tracing::info!(path = %path.display(), presets = config.preset.len(), "loaded config");
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_read() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [preset.statusbar]
+            x = 0
+            y = 0
+            w = 3840
+            h = 40
+            scale = 3
+            key = "1"
+            "#,
+        )
+        .unwrap();
+        let preset = &config.preset["statusbar"];
+        assert_eq!(preset.region(), (0.0, 0.0, 3840.0, 40.0));
+        assert_eq!(preset.scale, Some(3.0));
+        assert_eq!(preset.key.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn sensitivity_defaults_per_device() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [sensitivity.touchpad]
+            zoom = 0.25
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.sensitivity.touchpad,
+            Sensitivity {
+                zoom: 0.25,
+                pan: 1.0
+            }
+        );
+        assert_eq!(config.sensitivity.mouse, Sensitivity::default());
+    }
+
+    #[test]
+    fn view_and_keys_are_read() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [view]
+            min_scale = 0.5
+            max_scale = 8
+            background = [0, 0, 0.2]
+            interpolation = "nearest"
+
+            [remote]
+            token = "secret"
+
+            [keys]
+            quit = "q"
+            lock = "nope"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.view.max_scale, 8.0);
+        assert_eq!(config.view.min_scale, 0.5);
+        assert_eq!(config.view.background, (0.0, 0.0, 0.2));
+        assert_eq!(config.view.zoom_step, SCALE_DELTA);
+        assert_eq!(config.view.interpolation, Interpolation::Nearest);
+        assert_eq!(config.remote.token.as_deref(), Some("secret"));
+        assert!(config.view.check_scales().is_ok());
+        for (min, max) in [(4.0, 2.0), (0.0, 2.0), (-1.0, 2.0), (1.0, f64::INFINITY)] {
+            let view = ViewConfig {
+                min_scale: min,
+                max_scale: max,
+                ..ViewConfig::default()
+            };
+            assert!(
+                view.check_scales().is_err(),
+                "{} to {} was accepted",
+                min,
+                max
+            );
+        }
+
+        let keys = config
+            .keys
+            .resolve(|name| (name == "q").then_some('q' as u32));
+        assert_eq!(keys.quit, 'q' as u32);
+        assert_eq!(keys.lock, LOCK_KEY);
+        assert_eq!(keys.pan, PAN_KEY);
+    }
+
+    #[test]
+    fn midi_mapping_is_read() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [midi]
+            zoom = 7
+            spotlight = 36
+            pads = { 37 = "statusbar" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.midi.zoom, Some(7));
+        assert_eq!(config.midi.spotlight, Some(36));
+        assert_eq!(config.midi.pads["37"], "statusbar");
+    }
+}
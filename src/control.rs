@@ -0,0 +1,204 @@
+//! Control socket for driving the overlay from sway key bindings and scripts, which keep working
+//! while the overlay has the keyboard.
+//!
+//! Listens on `$XDG_RUNTIME_DIR/sway-boomer.sock`, or in [`daemon::runtime_dir`], and takes the same
+//! [`command`](crate::command) lines as remote control, answering each with `ok` or
+//! `error: <reason>`. There is no token, only the user can connect. `--send` sends a single
+//! command, and `--toggle` sends `toggle` to a [`daemon`].
+
+use {
+    crate::{
+        command::Command,
+        daemon,
+        remote::{self, Reply},
+        Error,
+    },
+    gtk::{
+        glib::{self, Continue},
+        prelude::*,
+    },
+    std::{
+        cell::{Cell, RefCell},
+        env,
+        fs::{self, Permissions},
+        io::{self, BufRead, BufReader, Write},
+        net::Shutdown,
+        os::unix::{
+            fs::PermissionsExt,
+            net::{UnixListener, UnixStream},
+        },
+        path::PathBuf,
+        rc::Rc,
+        thread,
+    },
+};
+
+type Handler = Rc<dyn Fn(Command) -> Result<(), String>>;
+type Toggle = Rc<dyn Fn()>;
+
+thread_local! {
+    // Only the GTK main thread ever fills this
+    static OVERLAYS: RefCell<Vec<(glib::WeakRef<gtk::ApplicationWindow>, Handler)>> =
+        const { RefCell::new(Vec::new()) };
+    static TOGGLE: RefCell<Option<Toggle>> = const { RefCell::new(None) };
+    /// Whether the socket is ours to remove.
+    static LISTENING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn socket_path() -> Result<PathBuf, Error> {
+    Ok(daemon::runtime_dir()?.join(concat!(env!("CARGO_PKG_NAME"), ".sock")))
+}
+
+/// Carry out commands from the socket on the overlay in `window` with `on_command`, for as long
+/// as the window is around. Must be called on the GTK main thread.
+pub fn add<F: Fn(Command) -> Result<(), String> + 'static>(
+    window: &gtk::ApplicationWindow,
+    on_command: F,
+) {
+    OVERLAYS.with(|overlays| {
+        overlays
+            .borrow_mut()
+            .push((window.downgrade(), Rc::new(on_command)))
+    });
+}
+
+/// Call `toggle` for every `toggle` command, which is otherwise refused. Must be called on the
+/// GTK main thread.
+pub fn on_toggle(toggle: impl Fn() + 'static) {
+    TOGGLE.with(|t| *t.borrow_mut() = Some(Rc::new(toggle)));
+}
+
+/// Whether [`listen`] is taking commands.
+pub fn listening() -> bool {
+    LISTENING.with(Cell::get)
+}
+
+/// Pass `cmd` to every overlay that's still open, or `toggle` to the daemon.
+fn dispatch(cmd: Command) -> Result<(), String> {
+    if cmd == Command::Toggle {
+        let toggle = TOGGLE.with(|t| t.borrow().clone());
+        return toggle
+            .map(|toggle| toggle())
+            .ok_or_else(|| "the overlay isn't running as --daemon".into());
+    }
+    // Handlers may close their window, so none of them can run while the list is borrowed
+    let handlers: Vec<Handler> = OVERLAYS.with(|overlays| {
+        let mut overlays = overlays.borrow_mut();
+        overlays.retain(|(window, _)| window.upgrade().is_some());
+        overlays
+            .iter()
+            .map(|(_, handler)| handler.clone())
+            .collect()
+    });
+    if handlers.is_empty() {
+        return Err("no overlay is shown".into());
+    }
+    handlers.iter().try_for_each(|handler| handler(cmd.clone()))
+}
+
+/// Start taking commands. Must be called on the GTK main thread.
+pub fn listen() -> Result<(), Error> {
+    let path = socket_path()?;
+    if UnixStream::connect(&path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is used by another instance", path.display()),
+        )
+        .into());
+    }
+    // Left behind by an instance that didn't get to clean up
+    let _ = fs::remove_file(&path);
+    // Nobody else can get into the directory, this is for a runtime directory more open than it
+    // should be
+    let listener = UnixListener::bind(&path)?;
+    fs::set_permissions(&path, Permissions::from_mode(0o600))?;
+
+    let (tx, rx) = glib::MainContext::channel::<(Command, Reply)>(glib::PRIORITY_DEFAULT);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    tracing::debug!(error = %e, "control connection failed");
+                    continue;
+                }
+            };
+            let tx = tx.clone();
+            thread::spawn(move || {
+                if let Err(e) = client(stream, |cmd, reply| tx.send((cmd, reply)).is_ok()) {
+                    tracing::debug!(error = %e, "control client disconnected");
+                }
+            });
+        }
+    });
+    rx.attach(None, |(cmd, reply): (Command, Reply)| {
+        let _ = reply.send(dispatch(cmd));
+        Continue(true)
+    });
+    LISTENING.with(|listening| listening.set(true));
+    tracing::info!(path = %path.display(), "taking commands");
+    Ok(())
+}
+
+/// Answer the commands of a single client, passing them to `send` until it returns `false`.
+fn client(mut stream: UnixStream, send: impl Fn(Command, Reply) -> bool) -> io::Result<()> {
+    let reader = stream.try_clone()?;
+    remote::commands(&mut BufReader::new(reader), &mut stream, send)
+}
+
+/// Stop others from connecting once the overlay is done.
+pub fn stop() {
+    if listening() {
+        if let Ok(path) = socket_path() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Send `command` to the running overlay and wait for it to be carried out.
+pub fn send(command: &str) -> Result<(), Error> {
+    request(command, "no overlay is running to send to")
+}
+
+/// Have the running daemon show or hide its overlay.
+pub fn toggle() -> Result<(), Error> {
+    request("toggle", "no daemon is running, start one with --daemon")
+}
+
+/// Send `command` and wait for the answer, failing with `missing` when nothing listens.
+fn request(command: &str, missing: &str) -> Result<(), Error> {
+    let mut stream = UnixStream::connect(socket_path()?)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", missing, e)))?;
+    writeln!(stream, "{}", command.trim())?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    match reply.trim() {
+        "ok" => Ok(()),
+        "" => Err(io::Error::other("the overlay closed without answering").into()),
+        reply => Err(io::Error::other(reply.trim_start_matches("error: ").to_string()).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::input::Action, std::io::Read};
+
+    #[test]
+    fn bad_commands_are_not_passed_on() {
+        let (mut ours, theirs) = UnixStream::pair().unwrap();
+        ours.write_all(b"scale NaN\npan inf 0\nzoom-in\n").unwrap();
+        ours.shutdown(Shutdown::Write).unwrap();
+        client(theirs, |cmd, reply| {
+            assert_eq!(cmd, Command::Do(Action::ZoomIn));
+            reply.send(Ok(())).is_ok()
+        })
+        .unwrap();
+        let mut output = String::new();
+        ours.read_to_string(&mut output).unwrap();
+        assert_eq!(
+            output,
+            "error: `NaN` is not a number\nerror: `inf` is not a number\nok\n"
+        );
+    }
+}
@@ -0,0 +1,41 @@
+//! Enlarged pointer drawn on top of the view.
+//!
+//! The real cursor stays at its normal size while everything under it is magnified, which makes
+//! it easy to lose. With `--cursor-size` it's hidden and this arrow is drawn instead.
+
+use {
+    crate::config::{CURSOR_FILL, CURSOR_OUTLINE},
+    gtk::cairo::Context,
+};
+
+/// Outline of the arrow with its tip at the origin, for a cursor 1 unit tall.
+pub const SHAPE: [(f64, f64); 7] = [
+    (0.0, 0.0),
+    (0.0, 0.85),
+    (0.22, 0.66),
+    (0.36, 0.98),
+    (0.48, 0.93),
+    (0.34, 0.61),
+    (0.6, 0.61),
+];
+
+/// Width of the outline relative to the cursor size.
+pub const OUTLINE_WIDTH: f64 = 1.0 / 24.0;
+
+/// Draw the cursor with its tip at `pos`, `size` logical pixels tall.
+pub fn draw(ctx: &Context, pos: (f64, f64), size: f64) {
+    ctx.save().ok();
+    ctx.translate(pos.0, pos.1);
+    ctx.scale(size, size);
+    for (x, y) in SHAPE {
+        ctx.line_to(x, y);
+    }
+    ctx.close_path();
+    ctx.set_source_rgb(CURSOR_FILL.0, CURSOR_FILL.1, CURSOR_FILL.2);
+    let _ = ctx.fill_preserve();
+    ctx.set_source_rgb(CURSOR_OUTLINE.0, CURSOR_OUTLINE.1, CURSOR_OUTLINE.2);
+    ctx.set_line_width(OUTLINE_WIDTH * 2.0);
+    ctx.set_line_join(gtk::cairo::LineJoin::Round);
+    let _ = ctx.stroke();
+    ctx.restore().ok();
+}
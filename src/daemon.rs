@@ -0,0 +1,164 @@
+//! Stay resident with `--daemon` and show the overlay whenever `--toggle` asks over the
+//! [`control`] socket, so GTK is already up by the time the key is pressed.
+//!
+//! The daemon's pid is kept in `$XDG_RUNTIME_DIR/sway-boomer.pid`, locked for as long as it runs
+//! so a second daemon refuses to start, see [`runtime_dir`] for when there is no runtime
+//! directory. Quitting the overlay only closes its windows while resident.
+
+use {
+    crate::{control, Error},
+    gtk::{
+        glib::{self, clone, Continue},
+        prelude::*,
+    },
+    std::{
+        cell::{Cell, RefCell},
+        env,
+        fs::{self, DirBuilder, File, OpenOptions},
+        io::{self, Write},
+        os::unix::{
+            fs::{DirBuilderExt, MetadataExt, OpenOptionsExt},
+            io::AsRawFd,
+        },
+        path::PathBuf,
+        process,
+    },
+};
+
+thread_local! {
+    static RESIDENT: Cell<bool> = const { Cell::new(false) };
+    /// Whether a capture for the overlay is under way, so a second toggle doesn't start another.
+    static CAPTURING: Cell<bool> = const { Cell::new(false) };
+    /// The locked pid file, which the lock goes away with when the daemon exits however it does.
+    static PID_FILE: RefCell<Option<File>> = const { RefCell::new(None) };
+}
+
+/// Where the pid file and control socket go: `$XDG_RUNTIME_DIR`, or without one a directory of
+/// the user's own in the temporary directory that only they can enter. One that's there already
+/// but belongs to someone else or is open to others is refused rather than used.
+pub fn runtime_dir() -> Result<PathBuf, Error> {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(dir.into());
+    }
+    let uid = unsafe { libc::getuid() };
+    let dir = env::temp_dir().join(format!("{}-{}", env!("CARGO_PKG_NAME"), uid));
+    tracing::warn!(dir = %dir.display(), "XDG_RUNTIME_DIR is not set");
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e.into()),
+        _ => {}
+    }
+    let meta = fs::symlink_metadata(&dir)?;
+    match meta.is_dir() && meta.uid() == uid && meta.mode() & 0o077 == 0 {
+        true => Ok(dir),
+        false => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not a private directory of this user", dir.display()),
+        )
+        .into()),
+    }
+}
+
+fn pid_path() -> Result<PathBuf, Error> {
+    Ok(runtime_dir()?.join(concat!(env!("CARGO_PKG_NAME"), ".pid")))
+}
+
+/// Write our pid to the pid file, unless another daemon holds the lock on it.
+fn lock_pid_file() -> Result<File, Error> {
+    let path = pid_path()?;
+    // Not truncated before it's locked, that would wipe the pid of the daemon holding it
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(&path)?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let e = io::Error::last_os_error();
+        return Err(match e.kind() {
+            io::ErrorKind::WouldBlock => io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("another daemon holds {}", path.display()),
+            ),
+            _ => e,
+        }
+        .into());
+    }
+    file.set_len(0)?;
+    write!(file, "{}", process::id())?;
+    Ok(file)
+}
+
+/// Keep `app` running without windows and call `toggle` whenever the control socket, which must
+/// be listening already, is sent `toggle`. Must be called on the GTK main thread.
+pub fn start(app: &gtk::Application, toggle: impl Fn() + 'static) -> Result<(), Error> {
+    let file = lock_pid_file()?;
+    if !control::listening() {
+        return Err(
+            io::Error::other("the daemon can't be toggled without the control socket").into(),
+        );
+    }
+    PID_FILE.with(|pid_file| *pid_file.borrow_mut() = Some(file));
+    RESIDENT.with(|resident| resident.set(true));
+    app.hold();
+    control::on_toggle(toggle);
+    // Quit properly, so the pid file goes away
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        let app = app.clone();
+        glib::unix_signal_add_local(signal, move || {
+            app.quit();
+            Continue(false)
+        });
+    }
+    tracing::info!(pid = process::id(), "waiting for --toggle");
+    Ok(())
+}
+
+/// Forget about the daemon once it's done.
+pub fn stop() {
+    // Removed while still locked, so no other daemon's pid file goes
+    if PID_FILE.with(|pid_file| pid_file.borrow().is_some()) {
+        if let Ok(path) = pid_path() {
+            let _ = fs::remove_file(path);
+        }
+    }
+    PID_FILE.with(|pid_file| pid_file.take());
+}
+
+/// Quit the overlay: close its windows when resident, otherwise quit `app`.
+pub fn quit(app: &gtk::Application) {
+    match RESIDENT.with(Cell::get) {
+        true => app.windows().iter().for_each(|w| w.close()),
+        false => app.quit(),
+    }
+}
+
+/// Hide the overlay if `app` shows one, otherwise run `capture` on another thread and hand what
+/// it captured to `show` back on the GTK main thread.
+pub fn show_or_hide<T: Send + 'static>(
+    app: &gtk::Application,
+    capture: impl FnOnce() -> Result<T, Error> + Send + 'static,
+    show: impl Fn(&gtk::Application, T) + 'static,
+) {
+    if !app.windows().is_empty() {
+        quit(app);
+        return;
+    }
+    if CAPTURING.with(|capturing| capturing.replace(true)) {
+        return;
+    }
+    let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    std::thread::spawn(move || {
+        let _ = tx.send(capture());
+    });
+    rx.attach(
+        None,
+        clone!(@weak app => @default-return Continue(false), move |captured: Result<T, Error>| {
+            match captured {
+                Ok(captured) => show(&app, captured),
+                Err(e) => tracing::error!(error = %e, "could not capture"),
+            }
+            CAPTURING.with(|capturing| capturing.set(false));
+            Continue(false)
+        }),
+    );
+}
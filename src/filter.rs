@@ -0,0 +1,342 @@
+//! Color transforms applied to the capture while it's shown.
+//!
+//! The cairo path runs these per pixel on the CPU, the GL path has the same math in its fragment
+//! shader. Keep the two in sync.
+
+use gtk::gdk_pixbuf::Pixbuf;
+
+/// Weights of red, green and blue in luminance, as in Rec. 709.
+const LUMA: [f64; 3] = [0.2126, 0.7152, 0.0722];
+
+/// Affine transform of colors with channels from 0 to 1: each output channel is a row of weights
+/// for red, green and blue plus an offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorMatrix(pub [[f64; 4]; 3]);
+
+impl ColorMatrix {
+    pub const IDENTITY: Self = Self([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+    ]);
+
+    pub const INVERT: Self = Self([
+        [-1.0, 0.0, 0.0, 1.0],
+        [0.0, -1.0, 0.0, 1.0],
+        [0.0, 0.0, -1.0, 1.0],
+    ]);
+
+    pub const GRAYSCALE: Self = Self([
+        [LUMA[0], LUMA[1], LUMA[2], 0.0],
+        [LUMA[0], LUMA[1], LUMA[2], 0.0],
+        [LUMA[0], LUMA[1], LUMA[2], 0.0],
+    ]);
+
+    /// Adds `brightness` to every channel after scaling its distance from mid gray by `contrast`.
+    pub fn adjust(brightness: f64, contrast: f64) -> Self {
+        let offset = 0.5 - 0.5 * contrast + brightness;
+        Self([
+            [contrast, 0.0, 0.0, offset],
+            [0.0, contrast, 0.0, offset],
+            [0.0, 0.0, contrast, offset],
+        ])
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(&self, next: &Self) -> Self {
+        let (a, b) = (&self.0, &next.0);
+        let mut out = [[0.0; 4]; 3];
+        for (i, row) in out.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..3).map(|k| b[i][k] * a[k][j]).sum::<f64>();
+            }
+            row[3] += b[i][3];
+        }
+        Self(out)
+    }
+
+    /// An 8 bit RGB pixel transformed, clamped to what fits.
+    pub fn apply(&self, rgb: [u8; 3]) -> [u8; 3] {
+        let [r, g, b] = rgb.map(|c| c as f64 / 255.0);
+        self.0.map(|row| {
+            let v = row[0] * r + row[1] * g + row[2] * b + row[3];
+            (v.clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+    }
+
+    /// Transformed copy of `pb`.
+    pub fn apply_to_pixbuf(&self, pb: &Pixbuf) -> Option<Pixbuf> {
+        let out = pb.copy()?;
+        let channels = out.n_channels() as usize;
+        let (width, rowstride) = (out.width() as usize, out.rowstride() as usize);
+        // The copy isn't shared with anything yet
+        let pixels = unsafe { out.pixels() };
+        for row in pixels.chunks_mut(rowstride) {
+            for px in row[..width * channels].chunks_mut(channels) {
+                let rgb = self.apply([px[0], px[1], px[2]]);
+                px[..3].copy_from_slice(&rgb);
+            }
+        }
+        Some(out)
+    }
+}
+
+/// Color vision deficiency to simulate, with the matrices of Machado et al. (2009) at full
+/// severity. They're meant for linear RGB but are applied to the pixels as they are, like most
+/// simulators do, which is close enough to judge a palette by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deficiency {
+    /// No long wavelength (red) cones.
+    Protanopia,
+    /// No medium wavelength (green) cones.
+    Deuteranopia,
+    /// No short wavelength (blue) cones.
+    Tritanopia,
+}
+
+impl Deficiency {
+    pub fn matrix(self) -> ColorMatrix {
+        let [r, g, b] = match self {
+            Self::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Self::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Self::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        };
+        let row = |[x, y, z]: [f64; 3]| [x, y, z, 0.0];
+        ColorMatrix([row(r), row(g), row(b)])
+    }
+
+    /// The one after `current`, going through all of them and back to none.
+    pub fn next(current: Option<Self>) -> Option<Self> {
+        match current {
+            None => Some(Self::Protanopia),
+            Some(Self::Protanopia) => Some(Self::Deuteranopia),
+            Some(Self::Deuteranopia) => Some(Self::Tritanopia),
+            Some(Self::Tritanopia) => None,
+        }
+    }
+}
+
+/// Filter that can be switched on and off while the overlay is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorFilter {
+    Invert,
+    Grayscale,
+    /// The brightness and contrast from the config.
+    Adjust,
+    /// Replaces any other deficiency being simulated.
+    Simulate(Deficiency),
+}
+
+/// The filters that are on, applied as the simulated deficiency, grayscale, brightness and
+/// contrast, then inverting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filters {
+    pub simulate: Option<Deficiency>,
+    pub invert: bool,
+    pub grayscale: bool,
+    pub adjust: bool,
+    /// Added to every channel, from -1 to 1.
+    pub brightness: f64,
+    /// How much further from mid gray channels get, 1 leaves them.
+    pub contrast: f64,
+}
+
+impl Filters {
+    pub fn toggle(&mut self, filter: ColorFilter) {
+        let on = match filter {
+            ColorFilter::Invert => &mut self.invert,
+            ColorFilter::Grayscale => &mut self.grayscale,
+            ColorFilter::Adjust => &mut self.adjust,
+            ColorFilter::Simulate(deficiency) => {
+                self.simulate = match self.simulate {
+                    Some(current) if current == deficiency => None,
+                    _ => Some(deficiency),
+                };
+                return;
+            }
+        };
+        *on = !*on;
+    }
+
+    /// All filters that are on as one transform, `None` if none is.
+    pub fn matrix(&self) -> Option<ColorMatrix> {
+        let simulated = self.simulate.map(Deficiency::matrix);
+        let stages = [
+            (
+                simulated.is_some(),
+                simulated.unwrap_or(ColorMatrix::IDENTITY),
+            ),
+            (self.grayscale, ColorMatrix::GRAYSCALE),
+            (
+                self.adjust,
+                ColorMatrix::adjust(self.brightness, self.contrast),
+            ),
+            (self.invert, ColorMatrix::INVERT),
+        ];
+        stages
+            .iter()
+            .filter(|(on, _)| *on)
+            .map(|(_, m)| *m)
+            .reduce(|a, b| a.then(&b))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarity {
+    /// Light stays light and dark stays dark
+    Normal,
+    /// Light text on a dark background becomes dark on light, and the other way around
+    Inverted,
+}
+
+/// Turns the image into (nearly) black and white for legibility. Luminance above `threshold`
+/// becomes white and below it black, with a ramp `softness` wide in between so text edges stay
+/// anti-aliased instead of turning jagged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HighContrast {
+    pub threshold: f64,
+    pub softness: f64,
+    pub polarity: Polarity,
+}
+
+impl HighContrast {
+    /// Gray level in `0..=255` for an 8 bit RGB pixel.
+    pub fn apply(&self, rgb: [u8; 3]) -> u8 {
+        let [r, g, b] = rgb.map(|c| c as f64 / 255.0);
+        let luminance = LUMA[0] * r + LUMA[1] * g + LUMA[2] * b;
+        let v = ((luminance - self.threshold) / self.softness + 0.5).clamp(0.0, 1.0);
+        let v = match self.polarity {
+            Polarity::Normal => v,
+            Polarity::Inverted => 1.0 - v,
+        };
+        (v * 255.0).round() as u8
+    }
+
+    /// Filtered copy of `pb`.
+    pub fn apply_to_pixbuf(&self, pb: &Pixbuf) -> Option<Pixbuf> {
+        let out = pb.copy()?;
+        let channels = out.n_channels() as usize;
+        let (width, rowstride) = (out.width() as usize, out.rowstride() as usize);
+        // The copy isn't shared with anything yet
+        let pixels = unsafe { out.pixels() };
+        for row in pixels.chunks_mut(rowstride) {
+            for px in row[..width * channels].chunks_mut(channels) {
+                let v = self.apply([px[0], px[1], px[2]]);
+                px[..3].fill(v);
+            }
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, gtk::gdk_pixbuf::Colorspace};
+
+    const HIGH_CONTRAST: HighContrast = HighContrast {
+        threshold: 0.5,
+        softness: 0.1,
+        polarity: Polarity::Normal,
+    };
+
+    #[test]
+    fn high_contrast_pushes_to_extremes() {
+        assert_eq!(HIGH_CONTRAST.apply([40, 40, 40]), 0);
+        assert_eq!(HIGH_CONTRAST.apply([200, 200, 200]), 255);
+        // Saturated blue is dark, saturated green is light
+        assert_eq!(HIGH_CONTRAST.apply([0, 0, 255]), 0);
+        assert_eq!(HIGH_CONTRAST.apply([0, 255, 0]), 255);
+        // Only values right at the threshold stay gray
+        assert!((100..160).contains(&HIGH_CONTRAST.apply([128, 128, 128])));
+    }
+
+    #[test]
+    fn inverted_polarity_swaps_light_and_dark() {
+        let inverted = HighContrast {
+            polarity: Polarity::Inverted,
+            ..HIGH_CONTRAST
+        };
+        assert_eq!(inverted.apply([40, 40, 40]), 255);
+        assert_eq!(inverted.apply([200, 200, 200]), 0);
+    }
+
+    #[test]
+    fn filters_combine_in_order() {
+        let mut filters = Filters {
+            simulate: None,
+            invert: false,
+            grayscale: false,
+            adjust: false,
+            brightness: 0.1,
+            contrast: 2.0,
+        };
+        assert_eq!(filters.matrix(), None);
+        filters.toggle(ColorFilter::Invert);
+        assert_eq!(filters.matrix().unwrap().apply([255, 0, 51]), [0, 255, 204]);
+        // Gray first, so inverting pure red gives the inverse of its luminance
+        filters.toggle(ColorFilter::Grayscale);
+        assert_eq!(
+            filters.matrix().unwrap().apply([255, 0, 0]),
+            [201, 201, 201]
+        );
+        filters.toggle(ColorFilter::Invert);
+        filters.toggle(ColorFilter::Grayscale);
+        filters.toggle(ColorFilter::Adjust);
+        // Twice as far from 0.5, then 0.1 brighter
+        assert_eq!(
+            filters.matrix().unwrap().apply([153, 0, 102]),
+            [204, 0, 102]
+        );
+    }
+
+    #[test]
+    fn simulations_confuse_red_and_green() {
+        let mut filters = Filters {
+            simulate: None,
+            invert: false,
+            grayscale: false,
+            adjust: false,
+            brightness: 0.0,
+            contrast: 1.0,
+        };
+        for deficiency in [Deficiency::Protanopia, Deficiency::Deuteranopia] {
+            filters.toggle(ColorFilter::Simulate(deficiency));
+            let m = filters.matrix().unwrap();
+            let [red, green] = [m.apply([200, 60, 0]), m.apply([60, 140, 0])];
+            // Both come out as shades of the same yellowish hue
+            let hue = |[r, g, _]: [u8; 3]| r as f64 / g as f64;
+            assert!((hue(red) - hue(green)).abs() < 0.3, "{:?}", deficiency);
+        }
+        // Toggling the same one again turns it off
+        filters.toggle(ColorFilter::Simulate(Deficiency::Deuteranopia));
+        assert_eq!(filters.simulate, None);
+        assert_eq!(Deficiency::next(Some(Deficiency::Tritanopia)), None);
+    }
+
+    #[test]
+    fn pixbuf_copy_is_filtered() {
+        let pb = Pixbuf::new(Colorspace::Rgb, true, 8, 3, 2).unwrap();
+        pb.fill(0xf0f0f080);
+        let out = HIGH_CONTRAST.apply_to_pixbuf(&pb).unwrap();
+        assert_eq!(
+            &out.read_pixel_bytes().unwrap()[..4],
+            &[255, 255, 255, 0x80]
+        );
+        assert_eq!(
+            &pb.read_pixel_bytes().unwrap()[..4],
+            &[0xf0, 0xf0, 0xf0, 0x80]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Follow keyboard focus and the text caret through AT-SPI, for using the overlay as a screen
+//! magnifier.
+//!
+//! A worker thread connects to the accessibility bus, listens for focus and caret events and
+//! looks up where on screen they happened. Toolkits that only know window-relative positions
+//! (which includes many on Wayland) report nothing useful and are skipped.
+
+use {
+    gtk::glib::{self, Continue},
+    std::thread,
+    zbus::{
+        blocking::{Connection, MessageIterator},
+        zvariant::{Structure, Value},
+        MatchRule,
+    },
+};
+
+const EVENT_INTERFACE: &str = "org.a11y.atspi.Event.Object";
+
+/// Screen coordinates as understood by AT-SPI's `CoordType`.
+const COORD_SCREEN: u32 = 0;
+
+/// Area on screen that just got focus, in logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Focus {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Focus {
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+/// Call `on_focus` on the main loop whenever focus or the caret moves.
+pub fn follow<F: Fn(Focus) + 'static>(on_focus: F) {
+    let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    thread::spawn(move || {
+        if let Err(e) = listen(|focus| tx.send(focus).is_ok()) {
+            tracing::warn!(error = %e, "not following focus, AT-SPI is unavailable");
+        }
+    });
+    rx.attach(None, move |focus| {
+        on_focus(focus);
+        Continue(true)
+    });
+}
+
+fn a11y_bus() -> zbus::Result<Connection> {
+    let session = Connection::session()?;
+    // Toolkits only expose their accessibility trees while something is listening
+    let _ = session.call_method(
+        Some("org.a11y.Bus"),
+        "/org/a11y/bus",
+        Some("org.freedesktop.DBus.Properties"),
+        "Set",
+        &("org.a11y.Status", "IsEnabled", Value::from(true)),
+    );
+    let address: String = session
+        .call_method(
+            Some("org.a11y.Bus"),
+            "/org/a11y/bus",
+            Some("org.a11y.Bus"),
+            "GetAddress",
+            &(),
+        )?
+        .body()
+        .deserialize()?;
+    zbus::blocking::connection::Builder::address(address.as_str())?.build()
+}
+
+/// Pass every located focus change to `send` until it returns `false`.
+fn listen(send: impl Fn(Focus) -> bool) -> zbus::Result<()> {
+    let bus = a11y_bus()?;
+    for event in ["object:state-changed:focused", "object:text-caret-moved"] {
+        let _ = bus.call_method(
+            Some("org.a11y.atspi.Registry"),
+            "/org/a11y/atspi/registry",
+            Some("org.a11y.atspi.Registry"),
+            "RegisterEvent",
+            &event,
+        );
+    }
+
+    let rule = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface(EVENT_INTERFACE)?
+        .build();
+    for msg in MessageIterator::for_match_rule(rule, &bus, None)? {
+        let msg = msg?;
+        let header = msg.header();
+        let (sender, path, member) = match (header.sender(), header.path(), header.member()) {
+            (Some(sender), Some(path), Some(member)) => (sender, path, member),
+            _ => continue,
+        };
+        let body = msg.body();
+        let args = match body.deserialize::<Structure>() {
+            Ok(args) => args,
+            Err(_) => continue,
+        };
+        let (kind, detail1) = match args.fields() {
+            [Value::Str(kind), Value::I32(detail1), ..] => (kind.as_str(), *detail1),
+            _ => continue,
+        };
+
+        let extents = match (member.as_str(), kind) {
+            ("StateChanged", "focused") if detail1 == 1 => bus
+                .call_method(
+                    Some(sender.as_str()),
+                    path,
+                    Some("org.a11y.atspi.Component"),
+                    "GetExtents",
+                    &COORD_SCREEN,
+                )
+                .and_then(|reply| reply.body().deserialize::<(i32, i32, i32, i32)>()),
+            ("TextCaretMoved", _) => bus
+                .call_method(
+                    Some(sender.as_str()),
+                    path,
+                    Some("org.a11y.atspi.Text"),
+                    "GetCharacterExtents",
+                    &(detail1, COORD_SCREEN),
+                )
+                .and_then(|reply| reply.body().deserialize::<(i32, i32, i32, i32)>()),
+            _ => continue,
+        };
+
+        match extents {
+            // Objects without a known screen position report nothing or a negative origin
+            Ok((x, y, width, height)) if x >= 0 && y >= 0 && (width > 0 || height > 0) => {
+                let focus = Focus {
+                    x: x as f64,
+                    y: y as f64,
+                    width: width as f64,
+                    height: height as f64,
+                };
+                tracing::debug!(?focus, event = %member, "focus moved");
+                if !send(focus) {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => tracing::debug!(error = %e, "could not locate focus"),
+        }
+    }
+    Ok(())
+}
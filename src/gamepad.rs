@@ -0,0 +1,137 @@
+//! Drive the overlay from a gamepad or a presentation remote that shows up as one.
+//!
+//! The left stick pans, the triggers zoom, holding the south button (A on most pads) shows the
+//! highlight, west toggles the lock onto the current view, north toggles high contrast and start
+//! quits. A worker thread waits for controller events and only wakes up regularly while a stick
+//! is pushed, so an idle controller costs nothing.
+
+use {
+    crate::{
+        config::{GAMEPAD_DEADZONE, GAMEPAD_PAN_SPEED},
+        input::{self, Action},
+        schedule::Scheduler,
+        view::ImageState,
+    },
+    gilrs::{Axis, Button, EventType, Gilrs},
+    gtk::glib::{self, clone, Continue},
+    std::{
+        rc::Rc,
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+/// How often the view moves while a stick is pushed.
+const PAN_INTERVAL: Duration = Duration::from_millis(16);
+
+/// What pressing or releasing `button` does, if anything.
+fn button_action(button: Button, pressed: bool) -> Option<Action> {
+    match (button, pressed) {
+        (Button::South, _) => Some(Action::Highlight(pressed)),
+        (Button::RightTrigger | Button::RightTrigger2, true) => Some(Action::ZoomIn),
+        (Button::LeftTrigger | Button::LeftTrigger2, true) => Some(Action::ZoomOut),
+        (Button::West, true) => Some(Action::ToggleLock),
+        (Button::North, true) => Some(Action::ToggleHighContrast),
+        (Button::Start, true) => Some(Action::Quit),
+        _ => None,
+    }
+}
+
+/// Screen pixels to pan by after `elapsed` with a stick at `(x, y)`, where up is positive.
+fn pan(stick: (f64, f64), elapsed: Duration) -> Option<(f64, f64)> {
+    let (x, y) = stick;
+    if x.hypot(y) < GAMEPAD_DEADZONE {
+        return None;
+    }
+    let distance = GAMEPAD_PAN_SPEED * elapsed.as_secs_f64();
+    Some((x * distance, -y * distance))
+}
+
+/// Carry out what connected gamepads ask for on the overlay in `window`.
+pub fn connect(
+    app: &gtk::Application,
+    window: &gtk::ApplicationWindow,
+    scheduler: &Rc<Scheduler>,
+    state: &Rc<ImageState>,
+) {
+    let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    thread::spawn(move || {
+        let mut gilrs = match Gilrs::new() {
+            Ok(gilrs) => gilrs,
+            Err(e) => {
+                tracing::warn!(error = %e, "gamepad input unavailable");
+                return;
+            }
+        };
+
+        let mut last = Instant::now();
+        let mut panning = false;
+        loop {
+            let timeout = panning.then_some(PAN_INTERVAL);
+            if let Some(event) = gilrs.next_event_blocking(timeout) {
+                let action = match event.event {
+                    EventType::ButtonPressed(button, _) => button_action(button, true),
+                    EventType::ButtonReleased(button, _) => button_action(button, false),
+                    EventType::Connected => {
+                        tracing::info!(gamepad = %gilrs.gamepad(event.id).name(), "gamepad connected");
+                        None
+                    }
+                    _ => None,
+                };
+                if let Some(action) = action {
+                    if tx.send(action).is_err() {
+                        return;
+                    }
+                }
+            }
+
+            let now = Instant::now();
+            let stick = gilrs
+                .gamepads()
+                .map(|(_, pad)| {
+                    (
+                        pad.value(Axis::LeftStickX) as f64,
+                        pad.value(Axis::LeftStickY) as f64,
+                    )
+                })
+                .find(|&(x, y)| x.hypot(y) >= GAMEPAD_DEADZONE);
+            // Start counting from now when a stick was just pushed, the time before was idle
+            let elapsed = match panning {
+                true => now - last,
+                false => Duration::ZERO,
+            };
+            last = now;
+            panning = stick.is_some();
+            if let Some((dx, dy)) = stick.and_then(|stick| pan(stick, elapsed)) {
+                if tx.send(Action::Pan(dx, dy)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    rx.attach(
+        None,
+        clone!(@strong app, @weak window, @strong scheduler, @strong state => @default-return Continue(false), move |action| {
+            input::perform(action, &app, &window, &scheduler, &state);
+            Continue(true)
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_deflection_is_ignored() {
+        assert_eq!(pan((0.1, 0.05), Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn stick_up_pans_up() {
+        let (dx, dy) = pan((0.0, 1.0), Duration::from_millis(500)).unwrap();
+        assert_eq!(dx, 0.0);
+        assert_eq!(dy, -GAMEPAD_PAN_SPEED / 2.0);
+    }
+}
@@ -0,0 +1,483 @@
+//! OpenGL renderer for the `GLArea`.
+//!
+//! The capture is uploaded to a texture once. Every frame is then a single full-screen quad whose
+//! fragment shader looks up the source pixel through the view transform and blends the highlight,
+//! so redraws don't touch pixel data on the CPU at all.
+
+use {
+    crate::{
+        capture::Frame,
+        config::{CURSOR_FILL, CURSOR_OUTLINE, DOCK_BORDER, DOCK_BORDER_WIDTH, HIGH_CONTRAST},
+        cursor,
+        filter::{ColorMatrix, Polarity},
+        view::{ImageState, Lens},
+        Error,
+    },
+    gl::types::{GLchar, GLenum, GLint, GLuint},
+    std::{
+        ffi::{CStr, CString},
+        os::raw::c_void,
+        ptr,
+        sync::OnceLock,
+    },
+};
+
+const VERTEX_SHADER: &str = r#"
+in vec2 position;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+uniform sampler2D image;
+uniform vec2 image_size;
+// Framebuffer size in device pixels and device pixels per logical pixel
+uniform vec2 viewport;
+uniform float device_scale;
+// View transform in logical pixels, see `view::Viewport`
+uniform vec2 origin;
+uniform float scale;
+// Docked band: top and bottom edge, its own transform, and the border color and width
+uniform vec2 dock;
+uniform vec2 dock_origin;
+uniform float dock_scale;
+uniform vec3 dock_border;
+uniform float dock_border_width;
+// Lens at the mouse, 0 without one, 1 round and 2 rectangular: its half size and own transform
+uniform int lens;
+uniform vec2 lens_size;
+uniform vec2 lens_origin;
+uniform float lens_scale;
+uniform vec3 background;
+uniform vec4 highlight;
+uniform vec2 mouse;
+uniform vec2 spotlight;
+uniform float radius;
+// How much to darken outside the highlight, 0 to tint inside it instead
+uniform float dim;
+// See `filter::ColorMatrix`, the identity without filters
+uniform mat3 color_matrix;
+uniform vec3 color_offset;
+// See `filter::HighContrast`: x threshold, y softness, z 1 for inverted polarity, w 1 if enabled
+uniform vec4 high_contrast;
+// Outline of the enlarged cursor in logical pixels, see `cursor::SHAPE`; cursor_size 0 if off
+uniform vec2 cursor_shape[CURSOR_POINTS];
+uniform float cursor_size;
+uniform vec3 cursor_fill;
+uniform vec3 cursor_outline;
+
+out vec4 color;
+
+void main() {
+    vec2 screen = vec2(gl_FragCoord.x, viewport.y - gl_FragCoord.y) / device_scale;
+    bool docked = screen.y >= dock.x && screen.y < dock.y;
+    // Signed distance from the edge of the lens, negative inside
+    float lens_edge = lens == 1
+        ? length(screen - mouse) - lens_size.x
+        : max(abs(screen.x - mouse.x) - lens_size.x, abs(screen.y - mouse.y) - lens_size.y);
+    bool in_lens = lens > 0 && lens_edge < 0.0;
+    vec2 pixel = in_lens ? (screen - lens_origin) / lens_scale
+        : docked ? (screen - dock_origin) / dock_scale : (screen - origin) / scale;
+
+    if (any(lessThan(pixel, vec2(0.0))) || any(greaterThanEqual(pixel, image_size))) {
+        color = vec4(background, 1.0);
+    } else {
+        color = vec4(texture(image, pixel / image_size).rgb, 1.0);
+        color.rgb = clamp(color_matrix * color.rgb + color_offset, 0.0, 1.0);
+        if (high_contrast.w > 0.5) {
+            float luminance = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+            float v = clamp((luminance - high_contrast.x) / high_contrast.y + 0.5, 0.0, 1.0);
+            color.rgb = vec3(mix(v, 1.0 - v, high_contrast.z));
+        }
+    }
+
+    // The border runs along the edge of the band that faces the middle of the screen
+    float dock_edge = dock.x > 0.0 ? dock.x : dock.y;
+    if (dock.y > dock.x && abs(screen.y - dock_edge) < dock_border_width / 2.0) {
+        color.rgb = dock_border;
+    }
+    if (lens > 0 && abs(lens_edge) < dock_border_width / 2.0) {
+        color.rgb = dock_border;
+    }
+
+    if (distance(screen, spotlight) < radius) {
+        color.rgb = mix(color.rgb, highlight.rgb, highlight.a);
+    } else {
+        color.rgb *= 1.0 - dim;
+    }
+
+    if (cursor_size > 0.0) {
+        bool inside = false;
+        float edge = 1e9;
+        for (int i = 0, j = CURSOR_POINTS - 1; i < CURSOR_POINTS; j = i++) {
+            vec2 a = mouse + cursor_shape[i] * cursor_size;
+            vec2 b = mouse + cursor_shape[j] * cursor_size;
+            if ((a.y > screen.y) != (b.y > screen.y)
+                && screen.x < (b.x - a.x) * (screen.y - a.y) / (b.y - a.y) + a.x) {
+                inside = !inside;
+            }
+            vec2 ab = b - a;
+            float t = clamp(dot(screen - a, ab) / dot(ab, ab), 0.0, 1.0);
+            edge = min(edge, distance(screen, a + t * ab));
+        }
+        if (edge < cursor_size * CURSOR_OUTLINE_WIDTH) {
+            color.rgb = cursor_outline;
+        } else if (inside) {
+            color.rgb = cursor_fill;
+        }
+    }
+}
+"#;
+
+/// Resolve GL entry points through libepoxy, which GTK already uses for its GL contexts.
+fn load() -> Result<(), Error> {
+    static LOADED: OnceLock<Result<(), String>> = OnceLock::new();
+    LOADED
+        .get_or_init(|| {
+            let epoxy = unsafe { libloading::Library::new("libepoxy.so.0") }
+                .map_err(|e| format!("could not load libepoxy: {}", e))?;
+            gl::load_with(|name| unsafe {
+                // epoxy exports each function as a pointer variable named `epoxy_<name>`
+                epoxy
+                    .get::<*const *const c_void>(format!("epoxy_{}", name).as_bytes())
+                    .map(|sym| **sym)
+                    .unwrap_or(ptr::null())
+            });
+            // The function pointers stay valid for the rest of the process
+            std::mem::forget(epoxy);
+            Ok(())
+        })
+        .clone()
+        .map_err(Error::Gl)
+}
+
+unsafe fn compile(kind: GLenum, header: &str, source: &str) -> Result<GLuint, Error> {
+    let shader = gl::CreateShader(kind);
+    let source = CString::new(format!("{}{}", header, source)).unwrap();
+    gl::ShaderSource(shader, 1, &source.as_ptr(), ptr::null());
+    gl::CompileShader(shader);
+
+    let mut ok = 0;
+    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut ok);
+    if ok == 0 {
+        let mut log = vec![0u8; 1024];
+        let mut len = 0;
+        gl::GetShaderInfoLog(
+            shader,
+            log.len() as i32,
+            &mut len,
+            log.as_mut_ptr() as *mut GLchar,
+        );
+        gl::DeleteShader(shader);
+        log.truncate(len as usize);
+        return Err(Error::Gl(String::from_utf8_lossy(&log).into_owned()));
+    }
+    Ok(shader)
+}
+
+#[derive(Debug)]
+pub struct Renderer {
+    program: GLuint,
+    vao: GLuint,
+    vbo: GLuint,
+    texture: GLuint,
+    /// Of the capture in the texture, in pixels.
+    image_size: Option<(i32, i32)>,
+}
+
+impl Renderer {
+    /// Set up shaders and geometry. The GL context to render with must be current.
+    pub fn new(uses_es: bool) -> Result<Self, Error> {
+        load()?;
+        let header = match uses_es {
+            true => "#version 300 es\nprecision highp float;\n",
+            false => "#version 150\n",
+        };
+
+        unsafe {
+            let vertex = compile(gl::VERTEX_SHADER, header, VERTEX_SHADER)?;
+            let fragment_header = format!(
+                "{}#define CURSOR_POINTS {}\n#define CURSOR_OUTLINE_WIDTH {:.6}\n",
+                header,
+                cursor::SHAPE.len(),
+                cursor::OUTLINE_WIDTH
+            );
+            let fragment = match compile(gl::FRAGMENT_SHADER, &fragment_header, FRAGMENT_SHADER) {
+                Ok(f) => f,
+                Err(e) => {
+                    gl::DeleteShader(vertex);
+                    return Err(e);
+                }
+            };
+            let program = gl::CreateProgram();
+            gl::AttachShader(program, vertex);
+            gl::AttachShader(program, fragment);
+            gl::LinkProgram(program);
+            gl::DeleteShader(vertex);
+            gl::DeleteShader(fragment);
+
+            let mut ok = 0;
+            gl::GetProgramiv(program, gl::LINK_STATUS, &mut ok);
+            if ok == 0 {
+                gl::DeleteProgram(program);
+                return Err(Error::Gl("could not link shader program".into()));
+            }
+
+            // Two triangles covering the whole viewport
+            let quad: [f32; 12] = [
+                -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0,
+            ];
+            let (mut vao, mut vbo) = (0, 0);
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindVertexArray(vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                std::mem::size_of_val(&quad) as isize,
+                quad.as_ptr() as *const c_void,
+                gl::STATIC_DRAW,
+            );
+            let position = gl::GetAttribLocation(program, c"position".as_ptr());
+            gl::EnableVertexAttribArray(position as GLuint);
+            gl::VertexAttribPointer(position as GLuint, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
+            gl::BindVertexArray(0);
+
+            let mut texture = 0;
+            gl::GenTextures(1, &mut texture);
+
+            Ok(Self {
+                program,
+                vao,
+                vbo,
+                texture,
+                image_size: None,
+            })
+        }
+    }
+
+    /// Copy `frame` to the GPU. This is the only time pixel data is transferred.
+    pub fn upload(&mut self, frame: &Frame) -> Result<(), Error> {
+        let _span = tracing::debug_span!("upload", width = frame.width(), height = frame.height())
+            .entered();
+        unsafe {
+            let mut max = 0;
+            gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max);
+            if frame.width() > max || frame.height() > max {
+                return Err(Error::Gl(format!(
+                    "capture of {}x{} exceeds the maximum texture size of {}",
+                    frame.width(),
+                    frame.height(),
+                    max
+                )));
+            }
+
+            let (internal, format) = match frame.has_alpha() {
+                true => (gl::RGBA8, gl::RGBA),
+                false => (gl::RGB8, gl::RGB),
+            };
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            // Decoded pixbufs pad rows to 4 bytes, raw captures are tightly packed
+            let channels = if frame.has_alpha() { 4 } else { 3 };
+            let alignment = match frame.rowstride() == frame.width() * channels {
+                true => 1,
+                false => 4,
+            };
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                internal as GLint,
+                frame.width(),
+                frame.height(),
+                0,
+                format,
+                gl::UNSIGNED_BYTE,
+                frame.data().as_ptr() as *const c_void,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+        }
+        self.image_size = Some((frame.width(), frame.height()));
+        Ok(())
+    }
+
+    /// Draw into a framebuffer of `viewport` device pixels at `device_scale` per logical pixel.
+    pub fn draw(&self, viewport: (i32, i32), device_scale: i32, state: &ImageState) {
+        let _span = tracing::trace_span!("render", scale = state.scale.get()).entered();
+        unsafe {
+            gl::Viewport(0, 0, viewport.0, viewport.1);
+            let background = state.background.get();
+            gl::ClearColor(
+                background.0 as f32,
+                background.1 as f32,
+                background.2 as f32,
+                1.0,
+            );
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+
+            let image_size = match self.image_size {
+                Some(pixels) => state.logical_size(pixels),
+                None => return,
+            };
+            let d = device_scale as f64;
+            let screen = (viewport.0 as f64 / d, viewport.1 as f64 / d);
+            // Docked, the band is drawn through its own transform and the rest as it is
+            let (t, dock) = match state.dock.get() {
+                None => (state.viewport(screen), ((0.0, 0.0), state.unscaled(screen))),
+                Some(dock) => {
+                    let (top, height) = dock.band(screen.1);
+                    (
+                        state.unscaled(screen),
+                        (
+                            (top, top + height),
+                            state.dock_viewport(screen, (top, height)),
+                        ),
+                    )
+                }
+            };
+            let (ox, oy) = t.origin();
+            let (mx, my) = state.pointer(screen);
+            let highlight = state.highlight_color.get();
+            let (alpha, dim) = match (state.highlight.get(), state.dim_outside.get()) {
+                (true, Some(dim)) => (0.0, dim),
+                (true, None) => (highlight.3, 0.0),
+                (false, _) => (0.0, 0.0),
+            };
+
+            gl::UseProgram(self.program);
+            let loc = |name: &CStr| gl::GetUniformLocation(self.program, name.as_ptr());
+            gl::Uniform1i(loc(c"image"), 0);
+            gl::Uniform2f(loc(c"image_size"), image_size.0 as f32, image_size.1 as f32);
+            gl::Uniform2f(loc(c"viewport"), viewport.0 as f32, viewport.1 as f32);
+            gl::Uniform1f(loc(c"device_scale"), device_scale as f32);
+            gl::Uniform2f(loc(c"origin"), ox as f32, oy as f32);
+            gl::Uniform1f(loc(c"scale"), t.scale as f32);
+            let ((dock_top, dock_bottom), dock_t) = dock;
+            gl::Uniform2f(loc(c"dock"), dock_top as f32, dock_bottom as f32);
+            let (dx, dy) = dock_t.origin();
+            gl::Uniform2f(loc(c"dock_origin"), dx as f32, dy as f32);
+            gl::Uniform1f(loc(c"dock_scale"), dock_t.scale as f32);
+            gl::Uniform3f(
+                loc(c"dock_border"),
+                DOCK_BORDER.0 as f32,
+                DOCK_BORDER.1 as f32,
+                DOCK_BORDER.2 as f32,
+            );
+            gl::Uniform1f(loc(c"dock_border_width"), DOCK_BORDER_WIDTH as f32);
+            let lens = state.lens.get();
+            let (lw, lh) = lens.map_or((0.0, 0.0), |lens| lens.half_size(state.lens_radius.get()));
+            gl::Uniform1i(
+                loc(c"lens"),
+                match lens {
+                    None => 0,
+                    Some(Lens::Circle) => 1,
+                    Some(Lens::Rectangle) => 2,
+                },
+            );
+            gl::Uniform2f(loc(c"lens_size"), lw as f32, lh as f32);
+            let lens_t = state.lens_viewport(screen);
+            let (lx, ly) = lens_t.origin();
+            gl::Uniform2f(loc(c"lens_origin"), lx as f32, ly as f32);
+            gl::Uniform1f(loc(c"lens_scale"), lens_t.scale as f32);
+            gl::Uniform3f(
+                loc(c"background"),
+                background.0 as f32,
+                background.1 as f32,
+                background.2 as f32,
+            );
+            gl::Uniform4f(
+                loc(c"highlight"),
+                highlight.0 as f32,
+                highlight.1 as f32,
+                highlight.2 as f32,
+                alpha as f32,
+            );
+            let m = state
+                .filters
+                .get()
+                .matrix()
+                .unwrap_or(ColorMatrix::IDENTITY)
+                .0;
+            // Column by column
+            let columns: [f32; 9] = std::array::from_fn(|i| m[i % 3][i / 3] as f32);
+            gl::UniformMatrix3fv(loc(c"color_matrix"), 1, gl::FALSE, columns.as_ptr());
+            gl::Uniform3f(
+                loc(c"color_offset"),
+                m[0][3] as f32,
+                m[1][3] as f32,
+                m[2][3] as f32,
+            );
+            gl::Uniform4f(
+                loc(c"high_contrast"),
+                HIGH_CONTRAST.threshold as f32,
+                HIGH_CONTRAST.softness as f32,
+                (HIGH_CONTRAST.polarity == Polarity::Inverted) as u8 as f32,
+                state.high_contrast.get() as u8 as f32,
+            );
+            gl::Uniform2f(loc(c"mouse"), mx as f32, my as f32);
+            let (hx, hy) = state.highlight_pos(screen);
+            gl::Uniform2f(loc(c"spotlight"), hx as f32, hy as f32);
+            let shape = cursor::SHAPE.map(|(x, y)| [x as f32, y as f32]);
+            gl::Uniform2fv(
+                loc(c"cursor_shape[0]"),
+                shape.len() as i32,
+                shape.as_ptr() as *const f32,
+            );
+            gl::Uniform1f(
+                loc(c"cursor_size"),
+                state.cursor_size.get().unwrap_or(0.0) as f32,
+            );
+            gl::Uniform3f(
+                loc(c"cursor_fill"),
+                CURSOR_FILL.0 as f32,
+                CURSOR_FILL.1 as f32,
+                CURSOR_FILL.2 as f32,
+            );
+            gl::Uniform3f(
+                loc(c"cursor_outline"),
+                CURSOR_OUTLINE.0 as f32,
+                CURSOR_OUTLINE.1 as f32,
+                CURSOR_OUTLINE.2 as f32,
+            );
+            gl::Uniform1f(loc(c"radius"), state.highlight_radius.get() as f32);
+            gl::Uniform1f(loc(c"dim"), dim as f32);
+
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            // The magnified band decides when docked
+            let filter = match state.interpolation.get().smooth(state.scale.get()) {
+                true => gl::LINEAR,
+                false => gl::NEAREST,
+            };
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as GLint);
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            gl::BindVertexArray(0);
+            gl::UseProgram(0);
+        }
+    }
+
+    /// Free the GPU resources. The GL context they were created in must be current.
+    pub fn destroy(self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.texture);
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteProgram(self.program);
+        }
+    }
+}
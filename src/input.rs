@@ -0,0 +1,800 @@
+use {
+    crate::{
+        config::{
+            ConfigFile, KeyNames, Keys, Sensitivity, FINE_SCALE_DELTA, FLING_IDLE_MS,
+            FLING_MIN_SPEED, KEY_PAN_STEP, PAN_DURATION_MS, SCROLL_PAN_STEP,
+        },
+        daemon,
+        filter::{ColorFilter, Deficiency},
+        schedule::Scheduler,
+        view::{screen_size, ImageState, Lens},
+    },
+    clap::ValueEnum,
+    gtk::{
+        gdk::{
+            keys::{self, Key},
+            EventKey, InputSource, ModifierType, ScrollDirection,
+        },
+        glib::{self, clone},
+        prelude::*,
+    },
+    std::{cell::Cell, rc::Rc},
+};
+
+/// Mouse button that pans the view when dragging. Middle-dragging always pans too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PanButton {
+    Left,
+    Middle,
+    Right,
+}
+
+impl PanButton {
+    fn mask(self) -> ModifierType {
+        match self {
+            Self::Left => ModifierType::BUTTON1_MASK,
+            Self::Middle => ModifierType::BUTTON2_MASK,
+            Self::Right => ModifierType::BUTTON3_MASK,
+        }
+    }
+}
+
+/// Pointer and keyboard state of one overlay window: the drag in progress, how fast it's going,
+/// and whether the pan key is held. Every window has its own, so they drag separately.
+#[derive(Debug, Default)]
+pub struct Controller {
+    panning: Cell<bool>,
+    /// Where the pointer was at the last motion of the drag.
+    last_pos: Cell<Option<(f64, f64)>>,
+    /// Offset the drag would have reached without snapping, so the view can be dragged off an edge
+    unsnapped: Cell<Option<(f64, f64)>>,
+    /// Time in milliseconds and position of the last motion of the drag, and the pointer's
+    /// velocity in screen pixels per second, for flinging the view on release.
+    motion: Cell<Option<(u32, (f64, f64))>>,
+    velocity: Cell<(f64, f64)>,
+    /// Whether the view is still moving after a fling.
+    gliding: Rc<Cell<bool>>,
+    /// Whether a lagging highlight is still catching up with the pointer.
+    easing: Rc<Cell<bool>>,
+}
+
+impl Controller {
+    /// Mouse buttons that pan when held while the pointer moves: `pan_button` and the middle one,
+    /// all of them while the pan key is held. The left one draws or selects while `drawing`, see
+    /// `annotate` and `selection`.
+    fn pan_buttons(&self, pan_button: PanButton, drawing: bool) -> ModifierType {
+        let buttons = pan_button.mask() | ModifierType::BUTTON2_MASK;
+        match (self.panning.get(), drawing) {
+            (true, _) => {
+                ModifierType::BUTTON1_MASK | ModifierType::BUTTON2_MASK | ModifierType::BUTTON3_MASK
+            }
+            (false, true) => buttons & !ModifierType::BUTTON1_MASK,
+            (false, false) => buttons,
+        }
+    }
+
+    /// Drag the view of `state` on a screen of `widget_size` along with the pointer that just moved
+    /// to `pos` at `time`. Returns whether the view moved.
+    pub fn drag_to(
+        &self,
+        state: &ImageState,
+        pos: (f64, f64),
+        time: u32,
+        widget_size: (f64, f64),
+    ) -> bool {
+        let moved = match self.last_pos.replace(Some(pos)) {
+            Some(last) => {
+                if let Some(offset) = self.unsnapped.get() {
+                    state.offset.set(offset);
+                }
+                state.drag(last, pos);
+                self.unsnapped.set(Some(state.offset.get()));
+                state.snap(widget_size);
+                true
+            }
+            None => false,
+        };
+
+        self.gliding.set(false);
+        if let Some((t, last)) = self.motion.replace(Some((time, pos))) {
+            if time > t {
+                let dt = (time - t) as f64 / 1000.0;
+                let now = ((pos.0 - last.0) / dt, (pos.1 - last.1) / dt);
+                // Smooth out the jitter of single events
+                let (vx, vy) = self.velocity.get();
+                self.velocity
+                    .set((vx * 0.3 + now.0 * 0.7, vy * 0.3 + now.1 * 0.7));
+            }
+        }
+        moved
+    }
+
+    /// End the drag with the buttons released at `time`. Returns the velocity to fling the view
+    /// at, unless the pointer was held still before or hardly moving.
+    pub fn release(&self, time: u32) -> Option<(f64, f64)> {
+        self.last_pos.set(None);
+        self.unsnapped.set(None);
+        let v = self.velocity.replace((0.0, 0.0));
+        let still = self
+            .motion
+            .take()
+            .is_none_or(|(t, _)| time.saturating_sub(t) > FLING_IDLE_MS);
+        (!still && v.0.hypot(v.1) > FLING_MIN_SPEED).then_some(v)
+    }
+}
+
+/// Something a key, button or other control asks for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Quit,
+    ZoomIn,
+    ZoomOut,
+    /// Change the scale by this much.
+    Zoom(f64),
+    /// Change the scale by this much, keeping what shows at this screen position in place.
+    ZoomAt(f64, (f64, f64)),
+    /// Set the scale to this.
+    SetScale(f64),
+    /// Glide to this scale keeping what's under the pointer in place, or to the whole capture
+    /// fitting the window.
+    ZoomTo(Option<f64>),
+    /// Move the view by this many screen pixels.
+    Pan(f64, f64),
+    /// Back to scale 1 without any offset, which shows the desktop pixel for pixel.
+    Reset,
+    /// Back to the scale at which a capture pixel takes up a logical pixel, without any offset.
+    ResetLogical,
+    Highlight(bool),
+    /// Grow the highlight by this share of its radius.
+    ResizeHighlight(f64),
+    ToggleHighContrast,
+    ToggleFilter(ColorFilter),
+    /// Simulate the next color vision deficiency, or none after the last.
+    CycleSimulation,
+    /// Go on to the next [`Interpolation`](crate::view::Interpolation) mode.
+    CycleInterpolation,
+    /// Magnify through the next [`Lens`] shape, or without one after the last.
+    CycleLens,
+    ToggleLock,
+    /// Zoom in on the window under the pointer.
+    FrameWindow,
+    /// Center a region (x, y, width, height) of the capture at a scale, or as large as it fits.
+    ShowRegion((f64, f64, f64, f64), Option<f64>),
+}
+
+/// Carry out `action` on the overlay in `window`.
+pub fn perform(
+    action: Action,
+    app: &gtk::Application,
+    window: &gtk::ApplicationWindow,
+    scheduler: &Scheduler,
+    state: &Rc<ImageState>,
+) {
+    let size = screen_size(window);
+    match action {
+        Action::Quit => return daemon::quit(app),
+        Action::ZoomTo(Some(scale)) => zoom_to(scheduler, state, scale, state.pointer(size), size),
+        Action::ZoomTo(None) => zoom_to_region(scheduler, state, state.whole_capture(size), size),
+        action => {
+            if !apply(action, state, size) {
+                return;
+            }
+        }
+    }
+    scheduler.redraw();
+}
+
+/// Carry out `action` on `state`, for a screen of `size`, right away rather than animated.
+/// Returns whether the view changed, which it doesn't for quitting, since that's up to whatever
+/// shows it.
+pub fn apply(action: Action, state: &ImageState, size: (f64, f64)) -> bool {
+    // The lens has a scale of its own, which zooming changes while it's shown
+    if let (Some(_), Some(delta)) = (state.lens.get(), zoom_delta(action, state.zoom_step.get())) {
+        state.set_lens_scale(state.lens_scale.get() + delta);
+        return true;
+    }
+    match action {
+        Action::Quit => return false,
+        Action::ZoomIn => {
+            state.zoom_in();
+            state.follow(size);
+        }
+        Action::ZoomOut => {
+            state.zoom_out();
+            state.follow(size);
+        }
+        Action::Zoom(delta) => {
+            state.set_scale(state.scale.get() + delta);
+            state.follow(size);
+        }
+        Action::ZoomAt(delta, screen) => {
+            state.zoom_at(delta, screen, size);
+            state.follow(size);
+        }
+        Action::SetScale(scale) => {
+            state.set_scale(scale);
+            state.follow(size);
+        }
+        Action::ZoomTo(scale) => {
+            // The lock would override where this is going
+            state.lock.set(None);
+            match scale {
+                Some(scale) => state.zoom_at(scale - state.scale.get(), state.pointer(size), size),
+                None => state.show_region(state.whole_capture(size), None, size),
+            }
+        }
+        Action::Pan(dx, dy) => state.drag((dx, dy), (0.0, 0.0)),
+        Action::Reset => {
+            state.set_scale(1.0);
+            state.offset.set((0.0, 0.0));
+        }
+        Action::ResetLogical => {
+            state.set_scale(state.density.get());
+            state.offset.set((0.0, 0.0));
+        }
+        Action::Highlight(on) => {
+            state.highlight.set(on);
+            // A lagging highlight starts out on the pointer the next time
+            state.spotlight.set(None);
+        }
+        Action::ResizeHighlight(delta) => state.resize_highlight(delta),
+        Action::ToggleHighContrast => state.high_contrast.set(!state.high_contrast.get()),
+        Action::ToggleFilter(filter) => {
+            let mut filters = state.filters.get();
+            filters.toggle(filter);
+            state.filters.set(filters);
+        }
+        Action::CycleSimulation => {
+            let mut filters = state.filters.get();
+            filters.simulate = Deficiency::next(filters.simulate);
+            tracing::info!(deficiency = ?filters.simulate, "simulating");
+            state.filters.set(filters);
+        }
+        Action::CycleInterpolation => {
+            let mode = state.interpolation.get().next();
+            tracing::info!(?mode, "interpolation");
+            state.interpolation.set(mode);
+        }
+        Action::CycleLens => {
+            let lens = Lens::next(state.lens.get());
+            tracing::info!(?lens, "lens");
+            state.lens.set(lens);
+        }
+        Action::ToggleLock => state.toggle_lock(size),
+        Action::ShowRegion(region, scale) => state.show_region(region, scale, size),
+        Action::FrameWindow => return state.frame_window_at_pointer(size),
+    }
+    true
+}
+
+/// How much `action` changes the scale by, if it zooms relative to the current scale.
+fn zoom_delta(action: Action, step: f64) -> Option<f64> {
+    match action {
+        Action::ZoomIn => Some(step),
+        Action::ZoomOut => Some(-step),
+        Action::Zoom(delta) | Action::ZoomAt(delta, _) => Some(delta),
+        _ => None,
+    }
+}
+
+/// Keysyms for the keys `names` binds, parsed like GTK accelerators, the defaults for the rest.
+pub fn keys(names: &KeyNames) -> Keys {
+    names.resolve(|name| match gtk::accelerator_parse(name) {
+        (0, _) => None,
+        (key, modifiers) => {
+            if !modifiers.is_empty() {
+                tracing::warn!(key = %name, "modifiers in key bindings are ignored");
+            }
+            Some(key)
+        }
+    })
+}
+
+/// Keysym of the key `evt` is about, lowercase for letters like the bindings.
+pub fn keysym(evt: &EventKey) -> u32 {
+    *evt.keyval().to_lower()
+}
+
+/// What pressing or releasing the key with keysym `key` does with `keys` bound, if anything.
+/// Keysyms are the X ones xkbcommon also uses, so this doesn't depend on GDK.
+pub fn key_action(keys: &Keys, key: u32, pressed: bool) -> Option<Action> {
+    match key {
+        _ if key == keys.highlight => Some(Action::Highlight(pressed)),
+        _ if !pressed => None,
+        _ if key == keys.quit => Some(Action::Quit),
+        _ if key == keys.lock => Some(Action::ToggleLock),
+        _ if key == keys.frame => Some(Action::FrameWindow),
+        _ if key == keys.high_contrast => Some(Action::ToggleHighContrast),
+        _ if key == keys.interpolation => Some(Action::CycleInterpolation),
+        _ if key == keys.invert => Some(Action::ToggleFilter(ColorFilter::Invert)),
+        _ if key == keys.grayscale => Some(Action::ToggleFilter(ColorFilter::Grayscale)),
+        _ if key == keys.adjust => Some(Action::ToggleFilter(ColorFilter::Adjust)),
+        _ if key == keys.simulate => Some(Action::CycleSimulation),
+        _ if key == keys.lens => Some(Action::CycleLens),
+        _ if key == keys.zoom_in => Some(Action::ZoomIn),
+        _ if key == keys.zoom_out => Some(Action::ZoomOut),
+        _ if key == keys.pan_left => Some(Action::Pan(-KEY_PAN_STEP, 0.0)),
+        _ if key == keys.pan_right => Some(Action::Pan(KEY_PAN_STEP, 0.0)),
+        _ if key == keys.pan_up => Some(Action::Pan(0.0, -KEY_PAN_STEP)),
+        _ if key == keys.pan_down => Some(Action::Pan(0.0, KEY_PAN_STEP)),
+        _ if key == keys.reset => Some(Action::Reset),
+        _ if key == keys.reset_logical => Some(Action::ResetLogical),
+        _ => None,
+    }
+}
+
+/// What the zoom key `key` does besides the bindings: `+` and `-` zoom and keypad `0` resets,
+/// which repeat while held. `1` to `4` glide to those scales and `5` fits the capture.
+pub fn zoom_keys(key: u32) -> Option<Action> {
+    use keys::constants as k;
+    let table = [
+        (k::plus, Action::ZoomIn),
+        // `+` is shifted on most layouts
+        (k::equal, Action::ZoomIn),
+        (k::KP_Add, Action::ZoomIn),
+        (k::minus, Action::ZoomOut),
+        (k::KP_Subtract, Action::ZoomOut),
+        (k::KP_0, Action::Reset),
+        (k::_1, Action::ZoomTo(Some(1.0))),
+        (k::_2, Action::ZoomTo(Some(2.0))),
+        (k::_3, Action::ZoomTo(Some(3.0))),
+        (k::_4, Action::ZoomTo(Some(4.0))),
+        (k::_5, Action::ZoomTo(None)),
+    ];
+    table
+        .into_iter()
+        .find(|(k, _)| **k == key)
+        .map(|(_, action)| action)
+}
+
+/// `action` from a scroll step, made stronger or weaker by `sensitivity`, where a zoom step is
+/// `step`.
+pub fn adjust(action: Action, sensitivity: Sensitivity, step: f64) -> Action {
+    match action {
+        Action::ZoomIn => Action::Zoom(step * sensitivity.zoom),
+        Action::ZoomOut => Action::Zoom(-step * sensitivity.zoom),
+        Action::Zoom(delta) => Action::Zoom(delta * sensitivity.zoom),
+        Action::Pan(dx, dy) => Action::Pan(dx * sensitivity.pan, dy * sensitivity.pan),
+        action => action,
+    }
+}
+
+/// What one step of the wheel in `direction` does with `modifiers` held, `None` for scrolling
+/// that doesn't come in steps. Like image editors, shift pans sideways and control zooms in finer
+/// steps. Shift is the highlight key by default, so it's left alone while the `highlight` is on.
+pub fn wheel_step(
+    direction: ScrollDirection,
+    modifiers: ModifierType,
+    highlight: bool,
+) -> Option<Action> {
+    let shift = modifiers.contains(ModifierType::SHIFT_MASK) && !highlight;
+    let control = modifiers.contains(ModifierType::CONTROL_MASK);
+    Some(match direction {
+        ScrollDirection::Up if shift => Action::Pan(-SCROLL_PAN_STEP, 0.0),
+        ScrollDirection::Down if shift => Action::Pan(SCROLL_PAN_STEP, 0.0),
+        ScrollDirection::Up if control => Action::Zoom(FINE_SCALE_DELTA),
+        ScrollDirection::Down if control => Action::Zoom(-FINE_SCALE_DELTA),
+        ScrollDirection::Up => Action::ZoomIn,
+        ScrollDirection::Down => Action::ZoomOut,
+        ScrollDirection::Left => Action::Pan(-SCROLL_PAN_STEP, 0.0),
+        ScrollDirection::Right => Action::Pan(SCROLL_PAN_STEP, 0.0),
+        _ => return None,
+    })
+}
+
+/// What scrolling for `action` at `pos` does, as strong as `sensitivity` says. What is under the
+/// pointer stays there, like in image viewers. With the highlight held scrolling resizes it
+/// instead, like in boomer.
+pub fn scrolled(
+    action: Action,
+    sensitivity: Sensitivity,
+    state: &ImageState,
+    pos: (f64, f64),
+) -> Action {
+    match adjust(action, sensitivity, state.zoom_step.get()) {
+        Action::Zoom(delta) if state.highlight.get() => Action::ResizeHighlight(delta),
+        Action::Zoom(delta) => Action::ZoomAt(state.relative_step(delta), pos),
+        action => action,
+    }
+}
+
+/// Connect keyboard and pointer handlers on `window` that update `state` and schedule redraws,
+/// dragging with `pan_button` held pans. While the pan key is held any button pans. Presets that
+/// name a key in `config` are jumped to with it, other bindings are `keys`, and scrolling is as
+/// strong as `config` says for the device.
+pub fn connect(
+    app: &gtk::Application,
+    window: &gtk::ApplicationWindow,
+    scheduler: &Rc<Scheduler>,
+    state: &Rc<ImageState>,
+    pan_button: PanButton,
+    keys: Keys,
+    config: &ConfigFile,
+) {
+    let sensitivity = config.sensitivity;
+    let preset_keys: Vec<_> = config
+        .preset
+        .iter()
+        .filter_map(|(name, preset)| {
+            let key = Key::from_name(preset.key.as_deref()?);
+            match *key == *keys::constants::VoidSymbol {
+                true => {
+                    tracing::warn!(preset = %name, key = ?preset.key, "unknown key name");
+                    None
+                }
+                false => Some((key, Action::ShowRegion(preset.region(), preset.scale))),
+            }
+        })
+        .collect();
+
+    let controller = Rc::new(Controller::default());
+    window.connect_key_press_event(
+        clone!(@strong scheduler, @strong app, @strong state, @strong controller => move |window, evt| {
+            let preset = preset_keys.iter().find(|(key, _)| *key == evt.keyval());
+            if let Some(&(_, action)) = preset {
+                perform(action, &app, window, &scheduler, &state);
+                return Inhibit(false);
+            }
+            if keysym(evt) == keys.pan {
+                controller.panning.set(true);
+                return Inhibit(false);
+            }
+            let action = key_action(&keys, keysym(evt), true).or_else(|| zoom_keys(*evt.keyval()));
+            if let Some(action) = action {
+                perform(action, &app, window, &scheduler, &state);
+            }
+            Inhibit(false)
+        }),
+    );
+
+    window.connect_key_release_event(
+        clone!(@strong scheduler, @strong app, @strong state, @strong controller => move |window, evt| {
+            if keysym(evt) == keys.pan {
+                controller.panning.set(false);
+            }
+            if let Some(action) = key_action(&keys, keysym(evt), false) {
+                perform(action, &app, window, &scheduler, &state);
+            }
+            Inhibit(false)
+        }),
+    );
+
+    connect_gestures(app, window, scheduler, state);
+
+    window.connect_scroll_event(
+        clone!(@strong state, @strong app, @strong scheduler => move |window, evt| {
+            let source = evt.source_device().map(|d| d.source());
+            let action = match wheel_step(evt.direction(), evt.state(), state.highlight.get()) {
+                Some(action) => action,
+                // Wheels also send discrete events alongside these, only touchpads need them. Two
+                // fingers pan, pinching zooms, see `connect_gestures`
+                None if source == Some(InputSource::Touchpad) => {
+                    let (dx, dy) = evt.delta();
+                    Action::Pan(dx * SCROLL_PAN_STEP, dy * SCROLL_PAN_STEP)
+                }
+                None => return Inhibit(false),
+            };
+            let sensitivity = match source {
+                Some(InputSource::Touchpad) => sensitivity.touchpad,
+                Some(InputSource::Trackpoint) => sensitivity.trackpoint,
+                _ => sensitivity.mouse,
+            };
+            let action = scrolled(action, sensitivity, &state, evt.position());
+            perform(action, &app, window, &scheduler, &state);
+            Inhibit(false)
+        }),
+    );
+
+    window.connect_motion_notify_event(
+        clone!(@strong state, @strong scheduler, @strong controller => move |window, evt| {
+            let pos = evt.position();
+            let selecting = evt.state().contains(ModifierType::CONTROL_MASK);
+            let buttons = controller.pan_buttons(pan_button, state.drawing.get() || selecting);
+            let size = screen_size(window);
+            state.mouse_pos.set(pos);
+            if state.follow_mouse.get() {
+                state.follow(size);
+                scheduler.redraw();
+            } else if evt.state().intersects(buttons)
+                && controller.drag_to(&state, pos, evt.time(), size)
+            {
+                scheduler.redraw();
+            }
+
+            // Whatever is drawn at the pointer has to move along with it
+            if state.highlight.get()
+                || state.cursor_size.get().is_some()
+                || state.dock.get().is_some()
+                || state.lens.get().is_some()
+            {
+                scheduler.redraw();
+            }
+            if state.highlight.get() {
+                ease_highlight(window, &scheduler, &state, &controller.easing);
+            }
+
+            Inhibit(false)
+        }),
+    );
+
+    window.connect_button_press_event(clone!(@strong controller => move |_, _| {
+        controller.gliding.set(false);
+        Inhibit(false)
+    }));
+
+    window.connect_button_release_event(clone!(@strong state, @strong scheduler => move |_, evt| {
+        if let Some(v) = controller.release(evt.time()) {
+            fling(&scheduler, &state, v, &controller.gliding);
+        }
+        Inhibit(false)
+    }));
+}
+
+/// Zoom continuously by pinching a touchpad or touchscreen, keeping what is between the fingers
+/// in place. On touchscreens one finger also pans and a long press toggles the highlight there.
+fn connect_gestures(
+    app: &gtk::Application,
+    window: &gtk::ApplicationWindow,
+    scheduler: &Rc<Scheduler>,
+    state: &Rc<ImageState>,
+) {
+    let pinch = gtk::GestureZoom::new(window);
+    pinch.set_propagation_phase(gtk::PropagationPhase::Bubble);
+    // The gesture reports scales relative to where it began
+    let start = Rc::new(Cell::new(1.0));
+    pinch.connect_begin(clone!(@strong state, @strong start => move |_, _| {
+        start.set(state.scale.get());
+    }));
+    pinch.connect_scale_changed(
+        clone!(@strong app, @weak window, @strong scheduler, @strong state => move |pinch, scale| {
+            let center = pinch.bounding_box_center().unwrap_or_else(|| state.mouse_pos.get());
+            let delta = start.get() * scale - state.scale.get();
+            perform(Action::ZoomAt(delta, center), &app, &window, &scheduler, &state);
+        }),
+    );
+
+    let drag = gtk::GestureDrag::new(window);
+    drag.set_touch_only(true);
+    // Drags report how far they got from where they began
+    let dragged = Rc::new(Cell::new((0.0, 0.0)));
+    drag.connect_drag_begin(clone!(@strong dragged => move |_, _, _| dragged.set((0.0, 0.0))));
+    drag.connect_drag_update(
+        clone!(@strong app, @weak window, @strong scheduler, @strong state => move |_, x, y| {
+            let (lx, ly) = dragged.replace((x, y));
+            perform(Action::Pan(lx - x, ly - y), &app, &window, &scheduler, &state);
+        }),
+    );
+
+    let press = gtk::GestureLongPress::new(window);
+    press.set_touch_only(true);
+    press.connect_pressed(
+        clone!(@strong app, @weak window, @strong scheduler, @strong state => move |_, x, y| {
+            state.mouse_pos.set((x, y));
+            let on = !state.highlight.get();
+            perform(Action::Highlight(on), &app, &window, &scheduler, &state);
+        }),
+    );
+
+    // Gestures only live as long as something holds on to them
+    window.connect_destroy(move |_| {
+        let _ = (&pinch, &drag, &press);
+    });
+}
+
+/// Keep the view moving at `velocity` after a drag until friction stops it or `gliding` is
+/// cleared by grabbing the view again.
+fn fling(
+    scheduler: &Scheduler,
+    state: &Rc<ImageState>,
+    mut velocity: (f64, f64),
+    gliding: &Rc<Cell<bool>>,
+) {
+    gliding.set(true);
+    let mut last = None;
+    scheduler.animate(clone!(@strong state, @strong gliding => move |now| {
+        // Without animations there is nothing to glide through
+        if now == i64::MAX || !gliding.get() {
+            gliding.set(false);
+            return false;
+        }
+        if let Some(last) = last.replace(now) {
+            velocity = state.glide(velocity, (now - last) as f64 / 1e6);
+        }
+        let moving = velocity.0.hypot(velocity.1) > FLING_MIN_SPEED;
+        gliding.set(moving);
+        moving
+    }));
+}
+
+/// Keep a lagging highlight moving toward the pointer until it has caught up.
+fn ease_highlight(
+    window: &gtk::ApplicationWindow,
+    scheduler: &Scheduler,
+    state: &Rc<ImageState>,
+    easing: &Rc<Cell<bool>>,
+) {
+    if state.highlight_lag.get().is_none() || easing.replace(true) {
+        return;
+    }
+    let mut last = None;
+    scheduler.animate(
+        clone!(@weak window, @strong state, @strong easing => @default-return false, move |now| {
+            let dt = match last.replace(now) {
+                _ if now == i64::MAX => f64::INFINITY,
+                Some(last) => (now - last) as f64 / 1e6,
+                None => 0.0,
+            };
+            let size = screen_size(&window);
+            let moving = state.highlight.get() && state.ease_spotlight(dt, size);
+            easing.set(moving);
+            moving
+        }),
+    );
+}
+
+/// Glide and zoom over to showing `region` (x, y, width, height) of the image as large as it fits
+/// on a screen of `widget_size`, changing scale and offset together.
+pub fn zoom_to_region(
+    scheduler: &Scheduler,
+    state: &Rc<ImageState>,
+    region: (f64, f64, f64, f64),
+    widget_size: (f64, f64),
+) {
+    // The lock would override where this is going
+    state.lock.set(None);
+    let from = (state.scale.get(), state.offset.get());
+    state.show_region(region, None, widget_size);
+    let to = (state.scale.get(), state.offset.get());
+    state.scale.set(from.0);
+    state.offset.set(from.1);
+
+    let mut start = None;
+    scheduler.animate(clone!(@strong state => move |now| {
+        let start = *start.get_or_insert(now);
+        let t = match now {
+            i64::MAX => 1.0,
+            _ => ((now - start) as f64 / (PAN_DURATION_MS * 1000.0)).min(1.0),
+        };
+        let k = 1.0 - (1.0 - t).powi(3);
+        let lerp = |a: f64, b: f64| a + (b - a) * k;
+        state.scale.set(lerp(from.0, to.0));
+        state.offset.set((lerp(from.1 .0, to.1 .0), lerp(from.1 .1, to.1 .1)));
+        t < 1.0
+    }));
+}
+
+/// Glide the view of `state` over to `scale`, keeping what shows at `screen` in place on a screen
+/// of `widget_size`.
+pub fn zoom_to(
+    scheduler: &Scheduler,
+    state: &Rc<ImageState>,
+    scale: f64,
+    screen: (f64, f64),
+    widget_size: (f64, f64),
+) {
+    state.lock.set(None);
+    let from = state.viewport(widget_size);
+    let mut start = None;
+    scheduler.animate(clone!(@strong state => move |now| {
+        let start = *start.get_or_insert(now);
+        let t = match now {
+            i64::MAX => 1.0,
+            _ => ((now - start) as f64 / (PAN_DURATION_MS * 1000.0)).min(1.0),
+        };
+        let k = 1.0 - (1.0 - t).powi(3);
+        state.set_scale(from.scale + (scale - from.scale) * k);
+        state.offset.set(from.zoom_about_point(state.scale.get(), screen).offset);
+        t < 1.0
+    }));
+}
+
+/// Glide the view of `state` over to `offset` instead of jumping there.
+pub fn pan_to(scheduler: &Scheduler, state: &Rc<ImageState>, offset: (f64, f64)) {
+    let from = state.offset.get();
+    let mut start = None;
+    scheduler.animate(clone!(@strong state => move |now| {
+        let start = *start.get_or_insert(now);
+        let t = match now {
+            i64::MAX => 1.0,
+            _ => ((now - start) as f64 / (PAN_DURATION_MS * 1000.0)).min(1.0),
+        };
+        // Ease out, slowing down toward the end
+        let k = 1.0 - (1.0 - t).powi(3);
+        state.offset.set((from.0 + (offset.0 - from.0) * k, from.1 + (offset.1 - from.1) * k));
+        t < 1.0
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: (f64, f64) = (800.0, 600.0);
+
+    #[test]
+    fn dragging_pans_with_the_pointer_and_flings_on_release() {
+        let state = ImageState::default();
+        let controller = Controller::default();
+        // The first motion only notes where the drag starts
+        assert!(!controller.drag_to(&state, (100.0, 100.0), 1000, SIZE));
+        assert!(controller.drag_to(&state, (90.0, 100.0), 1010, SIZE));
+        assert!(controller.drag_to(&state, (80.0, 100.0), 1020, SIZE));
+        assert_eq!(state.offset.get(), (20.0, 0.0));
+
+        let (vx, vy) = controller.release(1030).unwrap();
+        assert!(vx < -900.0 && vy == 0.0);
+        // The next drag starts over
+        assert!(!controller.drag_to(&state, (0.0, 0.0), 2000, SIZE));
+    }
+
+    #[test]
+    fn drag_held_still_before_release_stays() {
+        let state = ImageState::default();
+        let controller = Controller::default();
+        controller.drag_to(&state, (100.0, 100.0), 1000, SIZE);
+        controller.drag_to(&state, (50.0, 100.0), 1010, SIZE);
+        assert_eq!(controller.release(1010 + FLING_IDLE_MS + 1), None);
+    }
+
+    #[test]
+    fn pan_key_pans_with_any_button() {
+        let controller = Controller::default();
+        let left = ModifierType::BUTTON1_MASK;
+        assert!(controller
+            .pan_buttons(PanButton::Left, false)
+            .contains(left));
+        assert!(!controller.pan_buttons(PanButton::Left, true).contains(left));
+        assert!(!controller
+            .pan_buttons(PanButton::Right, false)
+            .contains(left));
+
+        controller.panning.set(true);
+        assert!(controller
+            .pan_buttons(PanButton::Right, true)
+            .contains(left));
+    }
+
+    #[test]
+    fn scroll_steps_follow_sensitivity() {
+        let touchpad = Sensitivity {
+            zoom: 0.5,
+            pan: 2.0,
+        };
+        assert_eq!(
+            adjust(Action::ZoomIn, touchpad, 0.1),
+            Action::Zoom(0.1 * 0.5)
+        );
+        assert_eq!(
+            adjust(Action::Pan(10.0, -5.0), touchpad, 0.1),
+            Action::Pan(20.0, -10.0)
+        );
+        assert_eq!(adjust(Action::Reset, touchpad, 0.1), Action::Reset);
+
+        assert_eq!(zoom_delta(Action::ZoomOut, 0.1), Some(-0.1));
+        assert_eq!(zoom_delta(Action::ZoomAt(0.3, (1.0, 2.0)), 0.1), Some(0.3));
+        assert_eq!(zoom_delta(Action::SetScale(2.0), 0.1), None);
+    }
+
+    #[test]
+    fn shift_scroll_pans_unless_the_highlight_is_held() {
+        let state = ImageState::default();
+        let shift = ModifierType::SHIFT_MASK;
+        let step = |highlight| {
+            state.highlight.set(highlight);
+            let action = wheel_step(ScrollDirection::Up, shift, highlight).unwrap();
+            scrolled(action, Sensitivity::default(), &state, (10.0, 20.0))
+        };
+        assert_eq!(step(false), Action::Pan(-SCROLL_PAN_STEP, 0.0));
+        assert_eq!(step(true), Action::ResizeHighlight(state.zoom_step.get()));
+
+        let control = ModifierType::CONTROL_MASK | shift;
+        assert_eq!(
+            wheel_step(ScrollDirection::Down, control, true),
+            Some(Action::Zoom(-FINE_SCALE_DELTA))
+        );
+        assert_eq!(
+            wheel_step(ScrollDirection::Smooth, ModifierType::empty(), false),
+            None
+        );
+    }
+}
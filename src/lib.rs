@@ -0,0 +1,159 @@
+//! Core of sway-boomer: screen capture, view math, rendering and input handling.
+//!
+//! The binary in `main.rs` only builds the GTK window and wires these modules together.
+
+pub mod annotate;
+pub mod args;
+pub mod autoclose;
+pub mod bench;
+pub mod canvas;
+pub mod capture;
+pub mod clipboard;
+pub mod command;
+pub mod config;
+pub mod control;
+pub mod cursor;
+pub mod daemon;
+pub mod filter;
+#[cfg(feature = "atspi")]
+pub mod focus;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+#[cfg(feature = "gl")]
+pub mod gpu;
+pub mod input;
+pub mod logging;
+pub mod magnifier;
+pub mod midi;
+pub mod minimap;
+#[cfg(feature = "ocr")]
+pub mod ocr;
+pub mod outputs;
+pub mod overlay;
+pub mod picker;
+#[cfg(feature = "portal")]
+pub mod portal;
+pub mod power;
+pub mod pyramid;
+pub mod recapture;
+pub mod remote;
+pub mod render;
+pub mod save;
+pub mod schedule;
+#[cfg(feature = "screencopy")]
+pub mod screencopy;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod selection;
+pub mod stats;
+pub mod sway;
+pub mod teardown;
+pub mod tree;
+pub mod view;
+#[cfg(feature = "wayland")]
+pub mod wayland;
+#[cfg(feature = "winit")]
+pub mod windowed;
+#[cfg(feature = "screencopy")]
+pub mod xdg_output;
+pub mod zoom;
+
+#[derive(Debug, giftwrap::Wrap)]
+pub enum Error {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    Glib(gtk::glib::Error),
+    GlibBool(gtk::glib::BoolError),
+    Cairo(gtk::cairo::Error),
+    CairoIo(gtk::cairo::IoError),
+    #[noWrap]
+    Decode,
+    #[noWrap]
+    Gl(String),
+    #[noWrap]
+    NoOutput,
+    #[noWrap]
+    NoPointer,
+    #[noWrap]
+    UnknownOutput(String),
+    #[noWrap]
+    UnknownContainer(i64),
+    #[noWrap]
+    Script(String),
+    #[noWrap]
+    Screencopy(String),
+    #[noWrap]
+    Portal(String),
+    #[noWrap]
+    Wayland(String),
+    #[noWrap]
+    Winit(String),
+    #[noWrap]
+    Wgpu(String),
+    /// The zoom range is empty or not made of positive scales
+    #[noWrap]
+    ScaleRange {
+        min: f64,
+        max: f64,
+    },
+    /// An external program is not installed
+    #[noWrap]
+    MissingTool {
+        tool: &'static str,
+        package: &'static str,
+    },
+    /// An external program exited with an error
+    #[noWrap]
+    ToolFailed {
+        tool: &'static str,
+        status: std::process::ExitStatus,
+        stderr: String,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Json(e) => write!(f, "invalid JSON from sway: {}", e),
+            Self::Toml(e) => write!(f, "invalid config file: {}", e),
+            Self::Glib(e) => write!(f, "{}", e),
+            Self::GlibBool(e) => write!(f, "{}", e),
+            Self::Cairo(e) => write!(f, "cairo: {}", e),
+            Self::CairoIo(e) => write!(f, "cairo: {}", e),
+            Self::Decode => write!(f, "could not decode the capture"),
+            Self::Gl(e) => write!(f, "OpenGL: {}", e),
+            Self::NoOutput => write!(f, "no focused output found"),
+            Self::NoPointer => write!(f, "the compositor doesn't say where the pointer is"),
+            Self::UnknownOutput(name) => write!(f, "no output called `{}`", name),
+            Self::UnknownContainer(id) => write!(f, "no container with id {}", id),
+            Self::Script(e) => write!(f, "script: {}", e),
+            Self::Screencopy(e) => write!(f, "screencopy: {}", e),
+            Self::Portal(e) => write!(f, "screenshot portal: {}", e),
+            Self::Wayland(e) => write!(f, "wayland: {}", e),
+            Self::Winit(e) => write!(f, "winit: {}", e),
+            Self::Wgpu(e) => write!(f, "wgpu: {}", e),
+            Self::ScaleRange { min, max } => write!(
+                f,
+                "the zoom range {} to {} is empty, both must be positive with the minimum first",
+                min, max
+            ),
+            Self::MissingTool { tool, package } => write!(
+                f,
+                "`{}` was not found in PATH, please install the `{}` package",
+                tool, package
+            ),
+            Self::ToolFailed {
+                tool,
+                status,
+                stderr,
+            } => match stderr.trim() {
+                "" => write!(f, "`{}` failed ({})", tool, status),
+                stderr => write!(f, "`{}` failed ({}): {}", tool, status, stderr),
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,40 @@
+use {
+    clap::ValueEnum,
+    tracing_subscriber::{fmt::format::FmtSpan, EnvFilter},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// One JSON object per event, for journald and log collectors
+    Json,
+}
+
+/// Install the global log subscriber.
+///
+/// `RUST_LOG` takes precedence; otherwise `verbosity` (the number of `-v` flags) picks the level.
+/// From `-vv` upwards span durations are logged when spans close. JSON output always includes
+/// them, since that is what monitoring wants to aggregate.
+pub fn init(verbosity: u8, format: LogFormat) {
+    let level = match verbosity {
+        0 => "warn",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    let span_events = match (format, verbosity) {
+        (LogFormat::Json, _) | (LogFormat::Text, 2..) => FmtSpan::CLOSE,
+        (LogFormat::Text, _) => FmtSpan::NONE,
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(span_events)
+        .with_writer(std::io::stderr);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().with_current_span(false).init(),
+    }
+}
@@ -0,0 +1,323 @@
+//! `--magnifier`: a small window in a corner of the screen that keeps showing what's around the
+//! pointer magnified, for as long as it runs, rather than an overlay that's closed again.
+//!
+//! The window sits on the overlay layer and lets the pointer through to whatever is below it.
+//! Wayland doesn't tell clients where the pointer is, so that's asked from the compositor, which
+//! only Hyprland answers. On sway the magnifier shows what's around the middle of the focused
+//! window instead. The window moves over to the other side when the pointer comes near it.
+//!
+//! With `--follow-windows` or `--follow-focus` it also follows what gets focus, and magnifies
+//! wherever the pointer or focus moved to last.
+
+use {
+    crate::{
+        capture::{self, Frame},
+        config::{MagnifierConfig, MAGNIFIER_MARGIN},
+        outputs,
+        tree::{self, Rect},
+        view::Interpolation,
+        Error,
+    },
+    clap::ValueEnum,
+    gtk::{
+        cairo::{Filter, Region},
+        gdk_pixbuf::Pixbuf,
+        glib::{self, clone, Continue},
+        prelude::*,
+    },
+    std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+        sync::mpsc,
+        thread,
+        time::Duration,
+    },
+};
+
+/// Corner of the output the magnifier sits in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    /// Whether the window is against the left edge, and whether against the top one.
+    fn edges(self) -> (bool, bool) {
+        match self {
+            Self::TopLeft => (true, true),
+            Self::TopRight => (false, true),
+            Self::BottomLeft => (true, false),
+            Self::BottomRight => (false, false),
+        }
+    }
+
+    /// The corner on the other side, at the same edge.
+    fn opposite(self) -> Self {
+        match self {
+            Self::TopLeft => Self::TopRight,
+            Self::TopRight => Self::TopLeft,
+            Self::BottomLeft => Self::BottomRight,
+            Self::BottomRight => Self::BottomLeft,
+        }
+    }
+
+    /// Where a window of `size` is in this corner of `output`, `margin` away from its edges.
+    fn place(self, output: &Rect, size: (i32, i32), margin: i32) -> Rect {
+        let (left, top) = self.edges();
+        Rect {
+            x: match left {
+                true => output.x + margin,
+                false => output.x + output.width - margin - size.0,
+            },
+            y: match top {
+                true => output.y + margin,
+                false => output.y + output.height - margin - size.1,
+            },
+            width: size.0,
+            height: size.1,
+        }
+    }
+}
+
+/// Part of `output` to capture for showing `size` at `scale` centered on `point`, moved back
+/// onto the output where it would stick out.
+fn region_around(point: (f64, f64), size: (i32, i32), scale: f64, output: &Rect) -> Rect {
+    let width = ((size.0 as f64 / scale).round() as i32).clamp(1, output.width);
+    let height = ((size.1 as f64 / scale).round() as i32).clamp(1, output.height);
+    let x = (point.0 - width as f64 / 2.0).round() as i32;
+    let y = (point.1 - height as f64 / 2.0).round() as i32;
+    Rect {
+        x: x.clamp(output.x, output.x + output.width - width),
+        y: y.clamp(output.y, output.y + output.height - height),
+        width,
+        height,
+    }
+}
+
+/// Where to magnify around: wherever the pointer or focus moved to last.
+#[derive(Debug, Default)]
+struct Target {
+    /// Where the pointer was the last time it was known.
+    pointer: Option<(f64, f64)>,
+    point: Option<(f64, f64)>,
+}
+
+impl Target {
+    /// Where to magnify around with the pointer now at `pointer`, if that's known, and `focus`
+    /// where focus went since the last time, if it moved.
+    fn update(
+        &mut self,
+        pointer: Option<(f64, f64)>,
+        focus: Option<(f64, f64)>,
+    ) -> Option<(f64, f64)> {
+        // Typing moves focus while the pointer rests, so focus wins when both moved
+        if let Some(focus) = focus {
+            self.point = Some(focus);
+        } else if pointer.is_some() && pointer != self.pointer {
+            self.point = pointer;
+        }
+        self.pointer = pointer.or(self.pointer);
+        self.point
+    }
+}
+
+/// Where the pointer is, or on sway the middle of the focused window in its place.
+fn pointer() -> Result<(f64, f64), Error> {
+    match outputs::pointer()? {
+        Some(point) => Ok(point),
+        None if capture::is_sway() => Ok(tree::container_rect(None)?.center()),
+        None => Err(Error::NoPointer),
+    }
+}
+
+/// Capture what's around `point`, pointer included, along with the output it's on.
+fn capture(
+    config: &MagnifierConfig,
+    point: (f64, f64),
+) -> Result<(Rect, (f64, f64), Frame), Error> {
+    let (_, output) = capture::output_containing(&Rect {
+        x: point.0 as i32,
+        y: point.1 as i32,
+        width: 1,
+        height: 1,
+    })?;
+    let region = region_around(
+        point,
+        (config.width, config.height),
+        config.scale.max(1.0),
+        &output,
+    );
+    let frame = capture::screenshot_region(region, true)?.into_frame()?;
+    Ok((output, point, frame))
+}
+
+/// GDK's monitor at `output`, matched by where it is in the layout.
+fn monitor(window: &gtk::ApplicationWindow, output: &Rect) -> Option<gtk::gdk::Monitor> {
+    let display = WidgetExt::display(window);
+    (0..display.n_monitors())
+        .filter_map(|i| display.monitor(i))
+        .find(|m| (m.geometry().x, m.geometry().y) == (output.x, output.y))
+}
+
+fn anchor(window: &gtk::ApplicationWindow, corner: Corner) {
+    use gtk_layer_shell::Edge;
+    let (left, top) = corner.edges();
+    [
+        (Edge::Left, left),
+        (Edge::Right, !left),
+        (Edge::Top, top),
+        (Edge::Bottom, !top),
+    ]
+    .into_iter()
+    .for_each(|(edge, anchored)| {
+        gtk_layer_shell::set_anchor(window, edge, anchored);
+        gtk_layer_shell::set_margin(window, edge, MAGNIFIER_MARGIN);
+    });
+}
+
+/// Open the magnifier window of `app` in `corner`, showing captures of what's around the pointer
+/// with `interpolation`, until the application quits. Call what's returned with where focus
+/// moves to, in the layout, to magnify there instead until the pointer moves.
+pub fn open(
+    app: &gtk::Application,
+    corner: Corner,
+    config: MagnifierConfig,
+    interpolation: Interpolation,
+) -> impl Fn((f64, f64)) {
+    let size = (config.width.max(1), config.height.max(1));
+    let window = gtk::ApplicationWindow::new(app);
+    window.set_size_request(size.0, size.1);
+    let area = gtk::DrawingArea::new();
+    window.add(&area);
+
+    let shown: Rc<RefCell<Option<Pixbuf>>> = Rc::new(RefCell::new(None));
+    area.connect_draw(clone!(@strong shown => move |area, ctx| {
+        ctx.set_source_rgb(0.0, 0.0, 0.0);
+        let _ = ctx.paint();
+        if let Some(pb) = &*shown.borrow() {
+            let scale = area.allocated_width() as f64 / pb.width() as f64;
+            ctx.scale(scale, area.allocated_height() as f64 / pb.height() as f64);
+            ctx.set_source_pixbuf(pb, 0.0, 0.0);
+            ctx.source().set_filter(match interpolation.smooth(scale) {
+                true => Filter::Bilinear,
+                false => Filter::Nearest,
+            });
+            let _ = ctx.paint();
+        }
+        Inhibit(false)
+    }));
+
+    gtk_layer_shell::init_for_window(&window);
+    gtk_layer_shell::set_layer(&window, gtk_layer_shell::Layer::Overlay);
+    gtk_layer_shell::set_namespace(&window, concat!(env!("CARGO_PKG_NAME"), "-magnifier"));
+    anchor(&window, corner);
+    // The pointer goes through to whatever is below
+    window.connect_realize(|window| window.input_shape_combine_region(Some(&Region::create())));
+
+    let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    let interval = Duration::from_millis(config.interval_ms.max(1));
+    let (focus_tx, focus_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut target = Target::default();
+        loop {
+            let pointer = pointer();
+            let focus = focus_rx.try_iter().last();
+            let captured = match target.update(pointer.as_ref().ok().copied(), focus) {
+                Some(point) => capture(&config, point),
+                None => Err(pointer.err().unwrap_or(Error::NoPointer)),
+            };
+            if tx.send(captured).is_err() {
+                break;
+            }
+            thread::sleep(interval);
+        }
+    });
+
+    let corner = Cell::new(corner);
+    let on = Cell::new(None::<Rect>);
+    let failing = Cell::new(false);
+    rx.attach(
+        None,
+        clone!(@weak window, @weak area => @default-return Continue(false), move |captured: Result<(Rect, (f64, f64), Frame), Error>| {
+            let (output, point, frame) = match captured {
+                Ok(captured) => captured,
+                Err(e) => {
+                    if !failing.replace(true) {
+                        tracing::warn!(error = %e, "magnifier can't capture around the pointer");
+                    }
+                    return Continue(true);
+                }
+            };
+            failing.set(false);
+            if on.replace(Some(output)) != Some(output) {
+                if let Some(monitor) = monitor(&window, &output) {
+                    gtk_layer_shell::set_monitor(&window, &monitor);
+                }
+            }
+            // Out of the way of what it magnifies
+            if corner.get().place(&output, size, MAGNIFIER_MARGIN).contains(point) {
+                corner.set(corner.get().opposite());
+                anchor(&window, corner.get());
+            }
+            shown.replace(Some(frame.to_pixbuf()));
+            area.queue_draw();
+            Continue(true)
+        }),
+    );
+
+    window.show_all();
+    move |point| {
+        let _ = focus_tx.send(point);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magnified_region_stays_on_the_output() {
+        let output = Rect {
+            x: 1920,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        };
+        assert_eq!(
+            region_around((2500.0, 500.0), (400, 240), 4.0, &output),
+            Rect {
+                x: 2450,
+                y: 470,
+                width: 100,
+                height: 60
+            }
+        );
+        let corner = region_around((1925.0, 1078.0), (400, 240), 4.0, &output);
+        assert_eq!((corner.x, corner.y), (1920, 1020));
+
+        let placed = Corner::BottomRight.place(&output, (400, 240), 16);
+        assert_eq!((placed.x, placed.y), (3424, 824));
+        assert_eq!(Corner::BottomRight.opposite(), Corner::BottomLeft);
+    }
+
+    #[test]
+    fn whatever_moved_last_is_magnified() {
+        let mut target = Target::default();
+        assert_eq!(target.update(None, None), None);
+        assert_eq!(target.update(Some((10.0, 10.0)), None), Some((10.0, 10.0)));
+        // Typing somewhere else with the pointer at rest
+        assert_eq!(
+            target.update(Some((10.0, 10.0)), Some((500.0, 300.0))),
+            Some((500.0, 300.0))
+        );
+        assert_eq!(
+            target.update(Some((10.0, 10.0)), None),
+            Some((500.0, 300.0))
+        );
+        assert_eq!(target.update(None, None), Some((500.0, 300.0)));
+        assert_eq!(target.update(Some((20.0, 10.0)), None), Some((20.0, 10.0)));
+    }
+}
@@ -1,13 +1,28 @@
 use {
+    clap::Parser,
     gtk::{
-        gdk::{EventMask, ScrollDirection},
-        gdk_pixbuf::{InterpType, Pixbuf},
+        gdk::EventMask,
         gio::prelude::*,
-        glib::clone,
+        glib::{self, clone, Continue},
         prelude::*,
     },
-    serde::Deserialize,
-    std::{cell::Cell, io::Cursor, process::Command, rc::Rc},
+    std::{cell::RefCell, net::TcpListener, path::Path, rc::Rc},
+    sway_boomer::{
+        annotate,
+        args::Args,
+        autoclose, bench,
+        canvas::Canvas,
+        capture::{self, Capture, Frame},
+        command::Command,
+        config::{self, ConfigFile, FALLBACK_PX_PER_MM},
+        control, daemon, input,
+        logging::{self, LogFormat},
+        magnifier, midi, minimap, overlay, picker, recapture, remote, render, save,
+        schedule::Scheduler,
+        selection, stats, teardown, tree,
+        view::{screen_size, ImageState, SnapEdges},
+        zoom, Error,
+    },
 };
 
 macro_rules! get_obj {
@@ -24,37 +39,22 @@ macro_rules! get_obj {
 }
 
 const LAYOUT: &str = include_str!("../boomer.glade");
-const QUIT_KEY: u16 = 9;
-const HIGHLIGHT_KEY: u16 = 50;
-const SCALE_DELTA: f64 = 0.1;
-const SCALE_MAX: f64 = 3.0;
-const BACKGROUND: (f64, f64, f64) = (0.1, 0.1, 0.1);
-const HIGHLIGHT_RADIUS: f64 = 70.0;
-const HIGHLIGHT_STYLE: (f64, f64, f64, f64) = (1.0, 1.0, 1.0, 0.4);
-
-#[derive(Debug, Deserialize)]
-struct Output {
-    name: String,
-    focused: bool,
-}
 
-#[derive(Debug, giftwrap::Wrap)]
-enum Error {
-    Io(std::io::Error),
-    Json(serde_json::Error),
-    #[noWrap]
-    NoOutput,
-}
-
-#[derive(Debug, Clone)]
-struct ImageState {
-    scale: Cell<f64>,
-    offset: Cell<(f64, f64)>,
-    mouse_pos: Cell<(f64, f64)>,
-    highlight: Cell<bool>,
-}
-
-fn activate(app: &gtk::Application, img: Vec<u8>) {
+const UNIFORM_WARNING: &str =
+    "<span size=\"large\" background=\"#000000c0\" foreground=\"#ffffff\"> \
+    The capture is a single solid color. </span>\n\
+    <span background=\"#000000c0\" foreground=\"#ffffff\"> \
+    Fullscreen or DRM leased outputs often can't be captured, press Escape and try again. </span>";
+
+/// Show the overlay for `img`, which is a capture of `output` (or of everything) unless it came
+/// from `--file`.
+fn activate(
+    app: &gtk::Application,
+    img: Capture,
+    output: Option<String>,
+    args: &Args,
+    config: &ConfigFile,
+) {
     let builder = gtk::Builder::from_string(LAYOUT);
 
     let window: gtk::ApplicationWindow = get_obj!(builder, "main-window");
@@ -63,168 +63,548 @@ fn activate(app: &gtk::Application, img: Vec<u8>) {
         EventMask::SCROLL_MASK
             | EventMask::SMOOTH_SCROLL_MASK
             | EventMask::BUTTON_MOTION_MASK
-            | EventMask::POINTER_MOTION_MASK,
+            | EventMask::POINTER_MOTION_MASK
+            | EventMask::ENTER_NOTIFY_MASK
+            | EventMask::TOUCHPAD_GESTURE_MASK
+            | EventMask::TOUCH_MASK,
     );
 
-    let source_pixbuf = Pixbuf::from_read(Cursor::new(img.clone())).unwrap();
-    let state = Rc::new(ImageState {
-        scale: Cell::new(1f64),
-        offset: Cell::new((0f64, 0f64)),
-        mouse_pos: Cell::new((0f64, 0f64)),
-        highlight: Cell::new(false),
-    });
-
-    let glarea: gtk::GLArea = get_obj!(builder, "gl-area");
-    glarea.connect_draw(
-        clone!(@strong img, @strong state => move |_, ctx| {
-            let scale = state.scale.get();
-            let (xpos, ypos) = state.offset.get();
-
-            // TODO: Try usin `scale` instead for better performance
-            if let Some(new_pb) = source_pixbuf.scale_simple((source_pixbuf.width() as f64 * scale) as i32, (source_pixbuf.height() as f64 * scale) as i32, InterpType::Nearest) {
-
-                let pb_width = source_pixbuf.width() as f64;
-                let pb_height = source_pixbuf.width() as f64;
-
-                let new_pb_width = new_pb.width() as f64;
-                let new_pb_height = new_pb.width() as f64;
-
-                let x = -(new_pb_width - pb_width) / 2.0;
-                let y = -(new_pb_height - pb_height) / 2.0;
-
-                // Fill background
-                ctx.set_source_rgba(BACKGROUND.0, BACKGROUND.1, BACKGROUND.1, 1f64);
-                let _ = ctx.paint();
-                // Paint pixbuf
-                ctx.set_source_pixbuf(&new_pb, x - xpos, y - ypos);
-                let _= ctx.paint();
-
-                if state.highlight.get() {
-                    let (mx, my) = state.mouse_pos.get();
-                    ctx.set_source_rgba(HIGHLIGHT_STYLE.0, HIGHLIGHT_STYLE.1, HIGHLIGHT_STYLE.2, HIGHLIGHT_STYLE.3);
-                    ctx.arc(mx, my, HIGHLIGHT_RADIUS, 0.0, std::f64::consts::TAU);
-                    let _ = ctx.fill();
+    let state = Rc::new(ImageState::default());
+    state.configure(&config.view);
+    state.set_scale(args.scale);
+    state.cursor_size.set(args.cursor_size);
+    state.follow_mouse.set(args.follow_mouse);
+    state.dock.set(args.dock);
+    state.lens.set(args.lens);
+    state.lock.set(args.lock);
+    state
+        .highlight_lag
+        .set(args.highlight_lag.map(|ms| ms.max(1.0) / 1000.0));
+    // Captures of HiDPI outputs have more pixels than the overlay has logical ones
+    if args.file.is_none() {
+        match overlay::output(args).and_then(|output| capture::output_scale(&output)) {
+            Ok(scale) if scale > 0.0 => state.density.set(scale),
+            Ok(_) => {}
+            Err(e) => tracing::warn!(error = %e, "output scale unknown, assuming 1"),
+        }
+    }
+    // Window positions are relative to the whole output
+    if capture::is_sway() && args.file.is_none() && args.region.is_none() {
+        match overlay::output(args).and_then(|output| tree::windows(&output)) {
+            Ok(windows) => {
+                state.windows.replace(windows);
+            }
+            Err(e) => tracing::warn!(error = %e, "window layout unavailable"),
+        }
+    }
+    if args.snap {
+        let windows = state.windows.borrow();
+        let rects = windows
+            .iter()
+            .map(|w| (w.x as f64, w.y as f64, w.width as f64, w.height as f64));
+        state.snap_edges.replace(Some(SnapEdges::new(rects)));
+    }
+    if let Some(radius) = args.highlight_radius {
+        state.highlight_radius.set(radius.to_px(FALLBACK_PX_PER_MM));
+        window.connect_realize(clone!(@strong state => move |window| {
+            // Millimeters only mean something once we know which output we're on
+            let monitor = window
+                .window()
+                .and_then(|w| WidgetExt::display(window).monitor_at_window(&w));
+            match monitor {
+                Some(m) if m.width_mm() > 0 => {
+                    let px_per_mm = m.geometry().width as f64 / m.width_mm() as f64;
+                    state.highlight_radius.set(radius.to_px(px_per_mm));
                 }
-
+                _ => tracing::debug!("output size unknown, assuming 96 DPI"),
             }
-            Inhibit(true)
-        }),
+        }));
+    }
+    if args.cursor_size.is_some() {
+        window.connect_realize(|window| {
+            let cursor = gtk::gdk::Cursor::for_display(
+                &WidgetExt::display(window),
+                gtk::gdk::CursorType::BlankCursor,
+            );
+            if let Some(gdk_window) = window.window() {
+                gdk_window.set_cursor(Some(&cursor));
+            }
+        });
+    }
+
+    let stack: gtk::Stack = get_obj!(builder, "canvas");
+    let scheduler = Scheduler::new(&stack);
+    let low_power = args.power_mode.is_low();
+    if low_power {
+        tracing::info!("low power mode");
+    }
+    scheduler.set_animated(!low_power);
+    let canvas = Canvas::new(
+        args.memory_budget_bytes(),
+        low_power,
+        state.clone(),
+        clone!(@weak scheduler => move || scheduler.redraw()),
     );
+    canvas.connect(&stack);
+
+    #[cfg(feature = "script")]
+    let on_capture = match &args.script {
+        Some(path) => match sway_boomer::script::Script::load(path) {
+            Ok(script) => Some(sway_boomer::script::connect(
+                script,
+                app,
+                &window,
+                &get_obj!(builder, "decorations"),
+                &scheduler,
+                &state,
+                config.preset.clone(),
+            )),
+            Err(e) => {
+                tracing::error!(error = %e, "could not load script");
+                daemon::quit(app);
+                return;
+            }
+        },
+        None => None,
+    };
 
-    window.connect_key_press_event(
-        clone!(@strong glarea, @strong app, @strong state => move |_, evt| {
-            match evt.keycode() {
-                Some(QUIT_KEY) => app.quit(),
-                Some(HIGHLIGHT_KEY) => {
-                    state.highlight.set(true);
-                    glarea.queue_render();
-                },
-                _ => {}
+    // Decode on a worker so the overlay maps right away
+    let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    std::thread::spawn(move || {
+        let _ = tx.send(img.into_frame().map(|frame| {
+            let uniform = frame.is_uniform();
+            (frame, uniform)
+        }));
+    });
+    let warning: gtk::Label = get_obj!(builder, "warning");
+    rx.attach(
+        None,
+        clone!(@strong app, @strong scheduler, @strong canvas => move |frame: Result<(Frame, bool), Error>| {
+            match frame {
+                Ok((frame, uniform)) => {
+                    if uniform {
+                        tracing::warn!("capture is a single color, the output may not be capturable");
+                        warning.set_markup(UNIFORM_WARNING);
+                        warning.show();
+                    }
+                    #[cfg(feature = "script")]
+                    if let Some(on_capture) = &on_capture {
+                        on_capture((frame.width(), frame.height()));
+                    }
+                    canvas.set_frame(frame);
+                    scheduler.redraw();
+                }
+                Err(e) => {
+                    tracing::error!(error = ?e, "failed to decode capture");
+                    daemon::quit(&app);
+                }
             }
-            Inhibit(false)
+            Continue(false)
         }),
     );
 
-    window.connect_key_release_event(clone!(@strong glarea, @strong state => move |_, evt| {
-        if let Some(HIGHLIGHT_KEY) = evt.keycode() {
-            state.highlight.set(false);
-            glarea.queue_render();
-        }
-        Inhibit(false)
-    }));
-
-    window.connect_scroll_event(clone!(@strong state, @strong glarea => move |_, evt| {
-        match evt.direction() {
-            ScrollDirection::Up => {
-                state.scale.set((state.scale.get() + SCALE_DELTA).min(SCALE_MAX));
-                glarea.queue_render();
-            },
-            ScrollDirection::Down => {
-                state.scale.set((state.scale.get() - SCALE_DELTA).max(SCALE_DELTA));
-                glarea.queue_render();
-            },
-            _ => {}
-        }
-        Inhibit(false)
-    }));
+    let keys = input::keys(&config.keys);
+    input::connect(
+        app,
+        &window,
+        &scheduler,
+        &state,
+        args.pan_button,
+        keys,
+        config,
+    );
 
-    static mut LAST_POS: Option<(f64, f64)> = None;
-    window.connect_motion_notify_event(clone!(@strong state, @strong glarea => move |_, evt| {
-        let pos = evt.position();
-        state.mouse_pos.set(pos);
-        if evt.state().contains(gtk::gdk::ModifierType::BUTTON1_MASK) {
-            if let Some(lp) = unsafe { LAST_POS } {
-                let (xoff, yoff) = state.offset.get();
-                state.offset.set((xoff + lp.0 - pos.0, yoff + lp.1 - pos.1));
-                glarea.queue_render();
-            }
-            unsafe { LAST_POS = Some(pos) };
-        }
+    annotate::connect(
+        &window,
+        &get_obj!(builder, "annotations"),
+        &scheduler,
+        &state,
+        keys,
+        config.draw,
+    );
 
-        if state.highlight.get() {
-            glarea.queue_render();
-        }
+    #[cfg(feature = "gamepad")]
+    sway_boomer::gamepad::connect(app, &window, &scheduler, &state);
+
+    stats::connect(&window, &get_obj!(builder, "stats"), &canvas, keys.stats);
+    minimap::connect(
+        &window,
+        &get_obj!(builder, "minimap"),
+        &canvas,
+        &scheduler,
+        &state,
+        keys.minimap,
+    );
+    zoom::connect(&get_obj!(builder, "zoom-level"), &scheduler, &state);
+    picker::connect(
+        &window,
+        &get_obj!(builder, "picked"),
+        &canvas,
+        &state,
+        keys.pick,
+    );
 
-        Inhibit(false)
-    }));
+    save::connect(
+        &window,
+        &canvas,
+        &state,
+        keys.save,
+        config.save.path.clone(),
+    );
+    selection::connect(
+        &window,
+        &get_obj!(builder, "selection"),
+        &canvas,
+        &scheduler,
+        &state,
+        args.pan_button,
+        keys.copy,
+    );
 
-    window.connect_button_release_event(clone!(@strong glarea => move |_, _| {
-        unsafe { LAST_POS = None };
-        Inhibit(false)
-    }));
+    if args.file.is_none() {
+        recapture::connect(
+            &window,
+            &canvas,
+            &scheduler,
+            output.clone(),
+            args.region,
+            args.cursor,
+            keys.recapture,
+        );
+    }
+
+    #[cfg(feature = "ocr")]
+    sway_boomer::ocr::connect(&window, &canvas, &state, keys.ocr, args.speak);
+
+    #[cfg(feature = "atspi")]
+    if args.follow_focus {
+        sway_boomer::focus::follow(
+            clone!(@strong state, @strong scheduler, @strong stack => move |focus| {
+                let size = screen_size(&stack);
+                state.center_on(focus.center(), (size.0 / 2.0, size.1 / 2.0), size);
+                scheduler.redraw();
+            }),
+        );
+    }
+
+    if let Some(addr) = args.remote {
+        let served = TcpListener::bind(addr).and_then(|listener| {
+            let token = match args.remote_token.as_ref().or(config.remote.token.as_ref()) {
+                Some(token) => token.clone(),
+                None => {
+                    let token = remote::generate_token()?;
+                    tracing::info!(%token, "generated a remote control token");
+                    token
+                }
+            };
+            tracing::info!(%addr, "accepting remote control");
+            remote::serve(
+                listener,
+                token,
+                commander(app, &window, &scheduler, &state, config),
+            );
+            Ok(())
+        });
+        if let Err(e) = served {
+            tracing::error!(error = %e, %addr, "could not start remote control");
+            daemon::quit(app);
+            return;
+        }
+    }
+
+    control::add(&window, commander(app, &window, &scheduler, &state, config));
+
+    if let Some(device) = &args.midi {
+        let presets = config.preset.clone();
+        midi::connect(
+            device.clone(),
+            config.midi.clone(),
+            (config.view.min_scale, config.view.max_scale),
+            clone!(@strong app, @weak window, @strong scheduler, @strong state => move |cmd| {
+                match cmd.action(presets.iter()) {
+                    Ok(action) => input::perform(action, &app, &window, &scheduler, &state),
+                    Err(e) => tracing::warn!(error = %e, "MIDI control ignored"),
+                }
+            }),
+        );
+    }
+
+    // A file doesn't go out of date
+    if let (false, false, Some(output)) = (args.no_auto_close, args.file.is_some(), &output) {
+        if capture::is_sway() {
+            autoclose::connect(&window, output.clone());
+        }
+    }
+
+    if args.follow_windows {
+        match overlay::output(args) {
+            Ok(output) => tree::follow_focus(
+                Some(output),
+                clone!(@strong state, @strong scheduler, @strong stack => move |rect| {
+                    let size = screen_size(&stack);
+                    let from = state.offset.get();
+                    state.center_on(rect.center(), (size.0 / 2.0, size.1 / 2.0), size);
+                    let to = state.offset.replace(from);
+                    input::pan_to(&scheduler, &state, to);
+                }),
+            ),
+            Err(e) => tracing::warn!(error = %e, "not following window focus"),
+        }
+    }
 
     gtk_layer_shell::init_for_window(&window);
     gtk_layer_shell::set_layer(&window, gtk_layer_shell::Layer::Overlay);
     gtk_layer_shell::set_keyboard_interactivity(&window, true);
 
+    // Without a monitor the compositor picks one, which needn't be the output that was captured,
+    // even when it's the focused one: focus may have moved since
+    if let Some(monitor) = output
+        .as_deref()
+        .and_then(|name| overlay::monitor(&window, name))
+    {
+        gtk_layer_shell::set_monitor(&window, &monitor);
+    }
+
+    let geometry = args
+        .geometry
+        .and_then(|g| overlay::output_size(&window, args).map(|size| g.resolve(size)));
+    let full = geometry.is_none();
     [
         (gtk_layer_shell::Edge::Left, true),
         // (gtk_layer_shell::Edge::Left, false),
-        (gtk_layer_shell::Edge::Right, true),
+        (gtk_layer_shell::Edge::Right, full),
         (gtk_layer_shell::Edge::Top, true),
-        (gtk_layer_shell::Edge::Bottom, true),
+        (gtk_layer_shell::Edge::Bottom, full),
     ]
     .iter()
     .for_each(|(anchor, state)| {
         gtk_layer_shell::set_anchor(&window, *anchor, *state);
     });
+    if let Some((x, y, width, height)) = geometry {
+        gtk_layer_shell::set_margin(&window, gtk_layer_shell::Edge::Left, x);
+        gtk_layer_shell::set_margin(&window, gtk_layer_shell::Edge::Top, y);
+        window.set_size_request(width, height);
+    }
 
+    teardown::install(&window);
     window.show_all();
     // window.fullscreen()
 }
 
-fn main() -> Result<(), Error> {
-    let output = serde_json::from_slice::<Vec<Output>>(
-        &Command::new("swaymsg")
-            .args(&["-t", "get_outputs", "-r"])
-            .output()?
-            .stdout,
-    )?
-    .into_iter()
-    .filter_map(|o| match o.focused {
-        true => Some(o.name),
-        false => None,
+/// Carry out commands from outside on the overlay in `window`, looking presets up in `config`.
+fn commander(
+    app: &gtk::Application,
+    window: &gtk::ApplicationWindow,
+    scheduler: &Rc<Scheduler>,
+    state: &Rc<ImageState>,
+    config: &ConfigFile,
+) -> impl Fn(Command) -> Result<(), String> + 'static {
+    let presets = config.preset.clone();
+    clone!(@strong app, @weak window, @strong scheduler, @strong state => @default-return Err("overlay closed".into()), move |cmd: Command| {
+        let action = cmd.action(presets.iter())?;
+        input::perform(action, &app, &window, &scheduler, &state);
+        Ok(())
     })
-    .next()
-    .ok_or(Error::NoOutput)?;
+}
+
+/// The view `args` ask for, outside a GTK window that would know the output's size.
+fn initial_state(args: &Args, config: &ConfigFile) -> ImageState {
+    let state = ImageState::default();
+    state.configure(&config.view);
+    state.set_scale(args.scale);
+    if let Some(offset) = args.offset {
+        state.offset.set(offset);
+    }
+    state.dock.set(args.dock);
+    state.lens.set(args.lens);
+    state.lock.set(args.lock);
+    if let Some(radius) = args.highlight_radius {
+        state.highlight_radius.set(radius.to_px(FALLBACK_PX_PER_MM));
+    }
+    state
+}
+
+/// Render a single frame of `img` to the `--headless` path.
+fn headless(img: Capture, path: &Path, args: &Args, config: &ConfigFile) -> Result<(), Error> {
+    let state = initial_state(args, config);
+    if let Some(pos) = args.highlight_at {
+        state.highlight.set(true);
+        state.mouse_pos.set(pos);
+    }
+    let frame = img.into_frame()?;
+    state.pixels.set(Some((frame.width(), frame.height())));
+    render::offscreen_to_png(&frame, &state, path)
+}
+
+/// State of an overlay shown without a GTK window, which can't tell its output's scale.
+#[cfg(any(feature = "wayland", feature = "winit"))]
+fn windowless_state(args: &Args, config: &ConfigFile) -> ImageState {
+    let state = initial_state(args, config);
+    state.follow_mouse.set(args.follow_mouse);
+    // Captures of HiDPI outputs have more pixels than the overlay has logical ones
+    if args.file.is_none() {
+        match overlay::output(args).and_then(|output| capture::output_scale(&output)) {
+            Ok(scale) if scale > 0.0 => state.density.set(scale),
+            Ok(_) => {}
+            Err(e) => tracing::warn!(error = %e, "output scale unknown, assuming 1"),
+        }
+    }
+    state
+}
 
-    let img = Command::new("grim")
-        .args(&["-o", &output, "-"])
-        .output()?
-        .stdout;
+/// Show `img` of `output` through `--backend wayland` instead of a GTK window.
+#[cfg(feature = "wayland")]
+fn layer_shell(
+    img: Capture,
+    output: Option<String>,
+    args: &Args,
+    config: &ConfigFile,
+) -> Result<(), Error> {
+    sway_boomer::wayland::show(
+        img.into_frame()?,
+        output.as_deref(),
+        windowless_state(args, config),
+        config,
+        args.pan_button,
+    )
+}
 
-    println!("Monitor: {}", output);
+/// Show `img` of `output` through `--backend winit` instead of a GTK window.
+#[cfg(feature = "winit")]
+fn windowed(
+    img: Capture,
+    output: Option<String>,
+    args: &Args,
+    config: &ConfigFile,
+) -> Result<(), Error> {
+    sway_boomer::windowed::show(
+        img.into_frame()?,
+        output.as_deref(),
+        windowless_state(args, config),
+        config,
+        args.pan_button,
+    )
+}
+
+/// Show an overlay for each of `overlays`, which hand their capture over rather than keep a copy.
+fn show(app: &gtk::Application, overlays: Vec<overlay::Overlay>, args: &Args, config: &ConfigFile) {
+    for (img, output, args) in overlays {
+        activate(app, img, output, &args, config);
+    }
+    if args.output_at_cursor {
+        overlay::keep_under_pointer(app);
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    if args.toggle {
+        return control::toggle();
+    }
+    if let Some(command) = &args.send {
+        return control::send(command);
+    }
+    let mut config = config::load(args.config.as_deref())?;
+    config.view.min_scale = args.min_scale.unwrap_or(config.view.min_scale);
+    config.view.max_scale = args.max_scale.unwrap_or(config.view.max_scale);
+    config.view.check_scales()?;
+    let config = Rc::new(config);
+    if args.bench {
+        return bench::run(
+            overlay::chosen_output(&args)?.as_deref(),
+            args.memory_budget_bytes(),
+        );
+    }
+    // The daemon only captures once it's asked to show the overlay, the magnifier all the time
+    let overlays = match args.daemon || args.magnifier.is_some() {
+        true => Vec::new(),
+        false => overlay::capture(&args)?,
+    };
+
+    if let Some(path) = &args.headless {
+        let (img, _, _) = overlays.into_iter().next().unwrap();
+        return headless(img, path, &args, &config);
+    }
+    #[cfg(feature = "wayland")]
+    if args.backend == sway_boomer::args::Backend::Wayland {
+        let (img, output, args) = overlays.into_iter().next().unwrap();
+        return layer_shell(img, output, &args, &config);
+    }
+    #[cfg(feature = "winit")]
+    if args.backend == sway_boomer::args::Backend::Winit {
+        let (img, output, args) = overlays.into_iter().next().unwrap();
+        return windowed(img, output, &args, &config);
+    }
 
     let application = gtk::Application::new(
         Some(concat!("net.olback.", env!("CARGO_PKG_NAME"))),
         Default::default(),
     );
 
+    let overlays = RefCell::new(overlays);
+    let resident = args.daemon;
     application.connect_activate(move |app| {
-        activate(app, img.clone());
+        if let Err(e) = control::listen() {
+            tracing::warn!(error = %e, "not taking commands over the control socket");
+        }
+        if let Some(corner) = args.magnifier {
+            let focus_on = Rc::new(magnifier::open(
+                app,
+                corner,
+                config.magnifier,
+                config.view.interpolation,
+            ));
+            if args.follow_windows {
+                let focus_on = focus_on.clone();
+                tree::follow_focus(None, move |rect| focus_on(rect.center()));
+            }
+            #[cfg(feature = "atspi")]
+            if args.follow_focus {
+                sway_boomer::focus::follow(move |focus| focus_on(focus.center()));
+            }
+            return;
+        }
+        if !args.daemon {
+            // Taken, so the closure doesn't hold on to the captures for as long as the app runs
+            show(app, overlays.take(), &args, &config);
+            return;
+        }
+        let started = daemon::start(
+            app,
+            clone!(@weak app, @strong args, @strong config => move || {
+                let captured = args.clone();
+                daemon::show_or_hide(
+                    &app,
+                    move || overlay::capture(&captured),
+                    clone!(@strong args, @strong config => move |app, overlays| show(app, overlays, &args, &config)),
+                );
+            }),
+        );
+        if let Err(e) = started {
+            tracing::error!(error = %e, "could not start the daemon");
+            app.quit();
+        }
     });
 
-    application.run();
+    // Arguments are handled by clap, don't let GApplication reject them
+    application.run_with_args::<&str>(&[]);
+    control::stop();
+    if resident {
+        daemon::stop();
+    }
 
     Ok(())
 }
+
+fn main() {
+    let args = Args::parse();
+    let format = args.log_format;
+    logging::init(args.verbose, format);
+
+    if let Err(e) = run(args) {
+        match format {
+            LogFormat::Text => eprintln!("sway-boomer: {}", e),
+            LogFormat::Json => tracing::error!(error = %e, "exiting"),
+        }
+        std::process::exit(1);
+    }
+}
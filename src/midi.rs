@@ -0,0 +1,208 @@
+//! Drive the overlay from a MIDI controller, read from a raw ALSA MIDI device such as
+//! `/dev/snd/midiC1D0`.
+//!
+//! Which knob zooms, which pad holds the highlight and which pads jump to presets comes from the
+//! `[midi]` table of the config file.
+
+use {
+    crate::{command::Command, config::MidiConfig, input::Action},
+    gtk::glib::{self, Continue},
+    std::{
+        fs::File,
+        io::{BufReader, Read},
+        path::PathBuf,
+        thread,
+    },
+};
+
+/// Channel message from a controller, whatever the channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    ControlChange { controller: u8, value: u8 },
+    NoteOn { note: u8, velocity: u8 },
+    NoteOff { note: u8 },
+}
+
+/// Splits a byte stream into [`Message`]s, including ones sent with running status.
+#[derive(Debug, Default)]
+pub struct Parser {
+    status: Option<u8>,
+    data: Vec<u8>,
+}
+
+impl Parser {
+    pub fn push(&mut self, byte: u8) -> Option<Message> {
+        match byte {
+            // Real-time messages can show up anywhere without interrupting anything
+            0xf8..=0xff => return None,
+            // System messages cancel running status, their data is skipped
+            0xf0..=0xf7 => {
+                self.status = None;
+                return None;
+            }
+            0x80..=0xef => {
+                self.status = Some(byte);
+                self.data.clear();
+                return None;
+            }
+            _ => {}
+        }
+
+        let status = self.status?;
+        self.data.push(byte);
+        let len = match status & 0xf0 {
+            0xc0 | 0xd0 => 1,
+            _ => 2,
+        };
+        if self.data.len() < len {
+            return None;
+        }
+        let data = std::mem::take(&mut self.data);
+        match (status & 0xf0, &data[..]) {
+            (0xb0, &[controller, value]) => Some(Message::ControlChange { controller, value }),
+            // Note on with no velocity is how many controllers release a note
+            (0x90, &[note, 0]) | (0x80, &[note, _]) => Some(Message::NoteOff { note }),
+            (0x90, &[note, velocity]) => Some(Message::NoteOn { note, velocity }),
+            _ => None,
+        }
+    }
+}
+
+/// What `message` asks for according to `config`. The zoom knob sweeps `(min, max)` scales
+/// geometrically, so each notch zooms by the same factor wherever it is.
+pub fn command(message: Message, config: &MidiConfig, (min, max): (f64, f64)) -> Option<Command> {
+    match message {
+        Message::ControlChange { controller, value } if Some(controller) == config.zoom => {
+            let scale = match value {
+                127 => max,
+                value => min * (max / min).powf(value as f64 / 127.0),
+            };
+            Some(Command::Do(Action::SetScale(scale)))
+        }
+        Message::NoteOn { note, .. } if Some(note) == config.spotlight => {
+            Some(Command::Do(Action::Highlight(true)))
+        }
+        Message::NoteOff { note } if Some(note) == config.spotlight => {
+            Some(Command::Do(Action::Highlight(false)))
+        }
+        Message::NoteOn { note, .. } => config
+            .pads
+            .get(&note.to_string())
+            .map(|preset| Command::Preset(preset.clone())),
+        _ => None,
+    }
+}
+
+/// Read MIDI from the device at `path`, calling `on_command` on the main loop with what its
+/// messages ask for according to `config`, with the zoom knob spanning `range`.
+pub fn connect<F: Fn(Command) + 'static>(
+    path: PathBuf,
+    config: MidiConfig,
+    range: (f64, f64),
+    on_command: F,
+) {
+    let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    thread::spawn(move || {
+        let device = match File::open(&path) {
+            Ok(device) => device,
+            Err(e) => {
+                tracing::warn!(error = %e, path = %path.display(), "MIDI device unavailable");
+                return;
+            }
+        };
+        let mut parser = Parser::default();
+        for byte in BufReader::new(device).bytes() {
+            let byte = match byte {
+                Ok(byte) => byte,
+                Err(e) => {
+                    tracing::warn!(error = %e, "MIDI device went away");
+                    return;
+                }
+            };
+            if let Some(message) = parser.push(byte) {
+                tracing::trace!(?message, "MIDI");
+                if let Some(cmd) = command(message, &config, range) {
+                    if tx.send(cmd).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+    rx.attach(None, move |cmd| {
+        on_command(cmd);
+        Continue(true)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Vec<Message> {
+        let mut parser = Parser::default();
+        bytes.iter().filter_map(|&b| parser.push(b)).collect()
+    }
+
+    #[test]
+    fn running_status_and_real_time_bytes() {
+        assert_eq!(
+            parse(&[0xb3, 1, 64, 0xf8, 1, 127, 0x99, 36, 100, 36, 0]),
+            [
+                Message::ControlChange {
+                    controller: 1,
+                    value: 64
+                },
+                Message::ControlChange {
+                    controller: 1,
+                    value: 127
+                },
+                Message::NoteOn {
+                    note: 36,
+                    velocity: 100
+                },
+                Message::NoteOff { note: 36 },
+            ]
+        );
+        // Data without a status, and sysex contents, are dropped
+        assert!(parse(&[1, 2, 0xf0, 0x7e, 0x01, 0xf7, 3]).is_empty());
+    }
+
+    #[test]
+    fn messages_map_to_commands() {
+        let config = MidiConfig {
+            zoom: Some(7),
+            spotlight: Some(36),
+            pads: [("37".to_string(), "statusbar".to_string())].into(),
+        };
+        let cc = |value| Message::ControlChange {
+            controller: 7,
+            value,
+        };
+        let range = (0.25, 4.0);
+        let scale = |value| match command(cc(value), &config, range) {
+            Some(Command::Do(Action::SetScale(scale))) => scale,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(scale(0), 0.25);
+        assert_eq!(scale(127), 4.0);
+        // Halfway through the knob is halfway through the zoom factors
+        assert!((scale(127) / scale(64) - scale(64) / scale(0)).abs() < 0.1);
+        assert_eq!(
+            command(Message::NoteOff { note: 36 }, &config, range),
+            Some(Command::Do(Action::Highlight(false)))
+        );
+        assert_eq!(
+            command(
+                Message::NoteOn {
+                    note: 37,
+                    velocity: 1
+                },
+                &config,
+                range
+            ),
+            Some(Command::Preset("statusbar".into()))
+        );
+        assert_eq!(command(Message::NoteOff { note: 37 }, &config, range), None);
+    }
+}
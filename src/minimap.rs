@@ -0,0 +1,179 @@
+//! Thumbnail of the whole capture in a corner while zoomed in, with the part in view marked, so
+//! panning around a large capture doesn't get lost. Toggled with a key (M by default), it fades
+//! out once the view has been still for a moment.
+
+use {
+    crate::{
+        canvas::Canvas,
+        config::{FADE_MS, MINIMAP_IDLE_MS, MINIMAP_WIDTH},
+        input,
+        schedule::Scheduler,
+        view::{screen_size, ImageState, Viewport},
+    },
+    gtk::{
+        gdk::prelude::*,
+        gdk_pixbuf::{InterpType, Pixbuf},
+        glib::{self, clone, Continue},
+        prelude::*,
+    },
+    std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+        time::Duration,
+    },
+};
+
+/// Screen pixels between the minimap and the corner of the screen.
+const MARGIN: f64 = 16.0;
+
+/// Where the minimap of an image of `source` goes on a `screen`: (x, y, width, height) in the
+/// bottom right corner.
+fn placement(screen: (f64, f64), source: (f64, f64)) -> (f64, f64, f64, f64) {
+    let width = MINIMAP_WIDTH.min(screen.0 / 3.0);
+    let height = width * source.1 / source.0;
+    (
+        screen.0 - MARGIN - width,
+        screen.1 - MARGIN - height,
+        width,
+        height,
+    )
+}
+
+/// Part of the minimap at `map` (x, y, width, height) showing what's in view through `t` on
+/// a `screen`, or `None` when all of the image is in view.
+fn viewport(
+    t: &Viewport,
+    screen: (f64, f64),
+    map: (f64, f64, f64, f64),
+) -> Option<(f64, f64, f64, f64)> {
+    let ((x1, y1), (x2, y2)) = t.visible_region((0.0, 0.0), screen)?;
+    let (w, h) = t.source_size;
+    if x1 <= 0.0 && y1 <= 0.0 && x2 >= w && y2 >= h {
+        return None;
+    }
+    let k = map.2 / w;
+    Some((map.0 + x1 * k, map.1 + y1 * k, (x2 - x1) * k, (y2 - y1) * k))
+}
+
+/// Show the minimap for `canvas` on `area`, toggled with the key with keysym `key`.
+pub fn connect(
+    window: &gtk::ApplicationWindow,
+    area: &gtk::DrawingArea,
+    canvas: &Rc<Canvas>,
+    scheduler: &Rc<Scheduler>,
+    state: &Rc<ImageState>,
+    key: u32,
+) {
+    // Scaled down copy of the capture, and the pixels it was made from
+    let thumbnail = Rc::new(RefCell::new(None::<(Pixbuf, *const u8)>));
+    let last = Rc::new(Cell::new(None::<Viewport>));
+    // Only the timeout of the latest view change starts fading out
+    let changes = Rc::new(Cell::new(0u32));
+
+    scheduler.redraw_above(area);
+    area.show();
+    area.connect_draw(clone!(@strong canvas, @strong scheduler, @strong state => move |area, ctx| {
+        let frame = match canvas.frame() {
+            Some(frame) => frame,
+            None => return Inhibit(false),
+        };
+        let screen = screen_size(area);
+        let source = state.logical_size((frame.width(), frame.height()));
+        let t = state.viewport(screen);
+        let map = placement(screen, source);
+        let view = match viewport(&t, screen, map) {
+            Some(view) => view,
+            None => return Inhibit(false),
+        };
+
+        if last.replace(Some(t)) != Some(t) {
+            area.set_opacity(1.0);
+            let change = changes.get().wrapping_add(1);
+            changes.set(change);
+            glib::timeout_add_local(
+                Duration::from_millis(MINIMAP_IDLE_MS),
+                clone!(@weak area, @weak scheduler, @strong changes => @default-return Continue(false), move || {
+                    if changes.get() == change {
+                        let changes = changes.clone();
+                        scheduler.fade_out(&area, FADE_MS, move || changes.get() == change);
+                    }
+                    Continue(false)
+                }),
+            );
+        }
+
+        let mut thumbnail = thumbnail.borrow_mut();
+        let stale = thumbnail
+            .as_ref()
+            .is_none_or(|(_, pixels)| *pixels != frame.data().as_ptr());
+        if stale {
+            *thumbnail = frame
+                .to_pixbuf()
+                .scale_simple(map.2.round() as i32, map.3.round() as i32, InterpType::Bilinear)
+                .map(|pb| (pb, frame.data().as_ptr()));
+        }
+        let thumb = match thumbnail.as_ref() {
+            Some((thumb, _)) => thumb,
+            None => return Inhibit(false),
+        };
+
+        ctx.set_source_rgba(0.0, 0.0, 0.0, 0.6);
+        ctx.rectangle(map.0 - 2.0, map.1 - 2.0, map.2 + 4.0, map.3 + 4.0);
+        let _ = ctx.fill();
+        ctx.save().ok();
+        ctx.translate(map.0, map.1);
+        ctx.scale(map.2 / thumb.width() as f64, map.3 / thumb.height() as f64);
+        ctx.set_source_pixbuf(thumb, 0.0, 0.0);
+        let _ = ctx.paint();
+        ctx.restore().ok();
+        ctx.set_source_rgb(1.0, 1.0, 1.0);
+        ctx.set_line_width(1.5);
+        ctx.rectangle(view.0, view.1, view.2, view.3);
+        let _ = ctx.stroke();
+        Inhibit(false)
+    }));
+
+    window.connect_key_press_event(clone!(@strong area, @strong scheduler => move |_, evt| {
+        if input::keysym(evt) != key {
+            return Inhibit(false);
+        }
+        match area.is_visible() {
+            true => area.hide(),
+            false => {
+                area.set_opacity(1.0);
+                area.show();
+            }
+        }
+        scheduler.redraw();
+        Inhibit(true)
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn viewport_marks_what_is_in_view() {
+        let source = (1920.0, 1080.0);
+        let map = placement(source, source);
+        assert_eq!(map, (1664.0, 929.0, 240.0, 135.0));
+
+        let whole = Viewport {
+            scale: 1.0,
+            offset: (0.0, 0.0),
+            source_size: source,
+            widget_size: source,
+        };
+        assert_eq!(viewport(&whole, source, map), None);
+
+        // Zoomed in 4x on the middle, showing a quarter of the width
+        let zoomed = Viewport {
+            scale: 4.0,
+            ..whole
+        };
+        let (x, y, w, h) = viewport(&zoomed, source, map).unwrap();
+        assert_eq!((w, h), (60.0, 33.75));
+        assert_eq!((x, y), (map.0 + 90.0, map.1 + 50.625));
+    }
+}
@@ -0,0 +1,147 @@
+//! Text recognition of what's in view, for reading small print or having it read out.
+//!
+//! The recognized text is printed to stdout, which is what the key is for when piping the overlay
+//! into other tools, so it doesn't go through the logs.
+//!
+//! Both tesseract and speech-dispatcher are used through their command line tools, so this only
+//! adds runtime dependencies, and only for users who press the key.
+
+use {
+    crate::{
+        canvas::Canvas,
+        capture::{Frame, Tool},
+        input,
+        view::{screen_size, ImageState},
+        Error,
+    },
+    gtk::{glib::clone, prelude::*},
+    std::{
+        io::{self, Write},
+        process::{Command, Stdio},
+        rc::Rc,
+        thread,
+    },
+};
+
+pub const TESSERACT: Tool = Tool {
+    name: "tesseract",
+    package: "tesseract",
+};
+
+pub const SPD_SAY: Tool = Tool {
+    name: "spd-say",
+    package: "speech-dispatcher",
+};
+
+fn command(tool: &Tool, args: &[&str]) -> Result<std::process::Child, Error> {
+    Command::new(tool.name)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => tool.missing(),
+            _ => e.into(),
+        })
+}
+
+/// Recognized text of `frame`, with surrounding whitespace trimmed.
+#[tracing::instrument(skip(frame), fields(width = frame.width(), height = frame.height()))]
+pub fn recognize(frame: &Frame) -> Result<String, Error> {
+    let png = frame.to_pixbuf().save_to_bufferv("png", &[])?;
+    let mut child = command(&TESSERACT, &["stdin", "stdout"])?;
+    // Dropped at the end of the statement, so tesseract sees the end of its input
+    child.stdin.take().unwrap().write_all(&png)?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(TESSERACT.failed(&output));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Read `text` out loud, waiting until speech-dispatcher is done.
+pub fn speak(text: &str) -> Result<(), Error> {
+    let mut child = command(&SPD_SAY, &["--wait", "-e"])?;
+    child.stdin.take().unwrap().write_all(text.as_bytes())?;
+    child.wait()?;
+    Ok(())
+}
+
+/// Copy of the part of `frame` visible on a screen of `screen` logical pixels.
+fn visible(frame: &Frame, state: &ImageState, screen: (f64, f64)) -> Option<Frame> {
+    let t = state.viewport(screen);
+    let ((x1, y1), (x2, y2)) = t.visible_region((0.0, 0.0), screen)?;
+    // Recognized at the full resolution of the capture
+    let d = state.density.get();
+    let (x, y) = ((x1 * d).floor() as i32, (y1 * d).floor() as i32);
+    let (w, h) = ((x2 * d).ceil() as i32 - x, (y2 * d).ceil() as i32 - y);
+    let region = frame.to_pixbuf().new_subpixbuf(x, y, w, h)?.copy()?;
+    Frame::from_pixbuf(&region).ok()
+}
+
+/// Recognize the text in view on the key with keysym `key`, printing it to stdout and
+/// reading it out if `speak` is set.
+pub fn connect(
+    window: &gtk::ApplicationWindow,
+    canvas: &Rc<Canvas>,
+    state: &Rc<ImageState>,
+    key: u32,
+    speak_text: bool,
+) {
+    window.connect_key_press_event(clone!(@strong canvas, @strong state => move |window, evt| {
+        if input::keysym(evt) != key {
+            return Inhibit(false);
+        }
+        let screen = screen_size(window);
+        let region = match canvas.frame().and_then(|frame| visible(&frame, &state, screen)) {
+            Some(region) => region,
+            None => return Inhibit(false),
+        };
+        thread::spawn(move || {
+            let text = match recognize(&region) {
+                Ok(text) if text.is_empty() => {
+                    tracing::info!("no text recognized");
+                    return;
+                }
+                Ok(text) => text,
+                Err(e) => {
+                    tracing::error!(error = %e, "text recognition failed");
+                    return;
+                }
+            };
+            println!("{}", text);
+            if speak_text {
+                if let Err(e) = speak(&text) {
+                    tracing::error!(error = %e, "could not read text out loud");
+                }
+            }
+        });
+        Inhibit(true)
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        gtk::gdk_pixbuf::{Colorspace, Pixbuf},
+    };
+
+    #[test]
+    fn visible_region_is_cropped() {
+        let pixbuf = Pixbuf::new(Colorspace::Rgb, false, 8, 100, 40).unwrap();
+        let frame = Frame::from_pixbuf(&pixbuf).unwrap();
+        let state = ImageState::default();
+
+        let region = visible(&frame, &state, (100.0, 40.0)).unwrap();
+        assert_eq!((region.width(), region.height()), (100, 40));
+
+        state.set_scale(2.0);
+        let region = visible(&frame, &state, (100.0, 40.0)).unwrap();
+        assert_eq!((region.width(), region.height()), (50, 20));
+
+        state.offset.set((1000.0, 0.0));
+        assert!(visible(&frame, &state, (100.0, 40.0)).is_none());
+    }
+}
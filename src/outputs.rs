@@ -0,0 +1,261 @@
+//! Which outputs there are, where they are in the layout and which one has focus, asked from
+//! whichever compositor is running: sway and Hyprland over their IPC sockets, and any other
+//! compositor through `wl_output` and xdg-output when built with `screencopy`. Only Hyprland also
+//! says where the pointer is.
+
+use {
+    crate::{sway, tree::Rect, Error},
+    serde::{de::DeserializeOwned, Deserialize},
+    std::{
+        env,
+        io::{Read, Write},
+        os::unix::net::UnixStream,
+        path::PathBuf,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Output {
+    pub name: String,
+    pub focused: bool,
+    /// Disabled outputs are listed too
+    #[serde(default)]
+    pub active: bool,
+    /// Where the output is in the layout, in logical pixels.
+    pub rect: Rect,
+    /// Physical pixels per logical pixel.
+    #[serde(default = "unscaled")]
+    pub scale: f64,
+}
+
+fn unscaled() -> f64 {
+    1.0
+}
+
+/// A way of asking the compositor about its outputs.
+trait Discovery: Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether the compositor this asks seems to be running.
+    fn available(&self) -> bool;
+
+    /// Whether [`Self::outputs`] says which output has focus.
+    fn knows_focus(&self) -> bool;
+
+    fn outputs(&self) -> Result<Vec<Output>, Error>;
+
+    /// Where the pointer is in the layout, if the compositor tells anyone.
+    fn pointer(&self) -> Result<Option<(f64, f64)>, Error> {
+        Ok(None)
+    }
+}
+
+struct Sway;
+
+impl Discovery for Sway {
+    fn name(&self) -> &'static str {
+        "sway"
+    }
+
+    fn available(&self) -> bool {
+        env::var_os("SWAYSOCK").is_some()
+    }
+
+    fn knows_focus(&self) -> bool {
+        true
+    }
+
+    fn outputs(&self) -> Result<Vec<Output>, Error> {
+        sway::Connection::connect()?.request(sway::Request::GetOutputs, "")
+    }
+}
+
+struct Hyprland;
+
+/// Monitor as `hyprctl -j monitors` lists it, sized in physical pixels.
+#[derive(Debug, Deserialize)]
+struct Monitor {
+    name: String,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    scale: f64,
+    /// `wl_output` transform, odd ones are rotated by 90 or 270 degrees
+    #[serde(default)]
+    transform: u32,
+    focused: bool,
+    #[serde(default)]
+    disabled: bool,
+}
+
+impl From<Monitor> for Output {
+    fn from(m: Monitor) -> Self {
+        let (width, height) = match m.transform % 2 {
+            0 => (m.width, m.height),
+            _ => (m.height, m.width),
+        };
+        Self {
+            name: m.name,
+            focused: m.focused,
+            active: !m.disabled,
+            rect: Rect {
+                x: m.x,
+                y: m.y,
+                width: (width as f64 / m.scale).round() as i32,
+                height: (height as f64 / m.scale).round() as i32,
+            },
+            scale: m.scale,
+        }
+    }
+}
+
+/// Pointer position as `hyprctl -j cursorpos` gives it, in logical pixels.
+#[derive(Debug, Deserialize)]
+struct CursorPos {
+    x: f64,
+    y: f64,
+}
+
+impl Hyprland {
+    fn socket() -> Option<PathBuf> {
+        let signature = env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+        // Hyprland moved its sockets out of /tmp in 0.40
+        let runtime = env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("hypr"));
+        [runtime, Some("/tmp/hypr".into())]
+            .into_iter()
+            .flatten()
+            .map(|dir| dir.join(&signature).join(".socket.sock"))
+            .find(|path| path.exists())
+    }
+
+    /// The JSON reply to `command`.
+    fn request<T: DeserializeOwned>(command: &str) -> Result<T, Error> {
+        let path = Self::socket().ok_or(Error::NoOutput)?;
+        let mut stream = UnixStream::connect(path)?;
+        stream.write_all(format!("j/{}", command).as_bytes())?;
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply)?;
+        Ok(serde_json::from_slice(&reply)?)
+    }
+}
+
+impl Discovery for Hyprland {
+    fn name(&self) -> &'static str {
+        "hyprland"
+    }
+
+    fn available(&self) -> bool {
+        Self::socket().is_some()
+    }
+
+    fn knows_focus(&self) -> bool {
+        true
+    }
+
+    fn outputs(&self) -> Result<Vec<Output>, Error> {
+        // `all` includes disabled monitors
+        let monitors: Vec<Monitor> = Self::request("monitors all")?;
+        Ok(monitors.into_iter().map(Output::from).collect())
+    }
+
+    fn pointer(&self) -> Result<Option<(f64, f64)>, Error> {
+        let pos: CursorPos = Self::request("cursorpos")?;
+        Ok(Some((pos.x, pos.y)))
+    }
+}
+
+/// Any compositor with xdg-output, which doesn't tell where focus is.
+#[cfg(feature = "screencopy")]
+struct Wayland;
+
+#[cfg(feature = "screencopy")]
+impl Discovery for Wayland {
+    fn name(&self) -> &'static str {
+        "wayland"
+    }
+
+    fn available(&self) -> bool {
+        env::var_os("WAYLAND_DISPLAY").is_some()
+    }
+
+    fn knows_focus(&self) -> bool {
+        false
+    }
+
+    fn outputs(&self) -> Result<Vec<Output>, Error> {
+        crate::xdg_output::outputs()
+    }
+}
+
+/// Tried in order, the first available one is asked.
+const DISCOVERIES: &[&dyn Discovery] = &[
+    &Sway,
+    &Hyprland,
+    #[cfg(feature = "screencopy")]
+    &Wayland,
+];
+
+fn discovery() -> Option<&'static dyn Discovery> {
+    DISCOVERIES.iter().copied().find(|d| d.available())
+}
+
+/// Whether outputs can be listed at all.
+pub fn available() -> bool {
+    discovery().is_some()
+}
+
+/// Whether the running compositor says which output has focus.
+pub fn knows_focus() -> bool {
+    discovery().is_some_and(|d| d.knows_focus())
+}
+
+/// All outputs of the running compositor.
+pub fn list() -> Result<Vec<Output>, Error> {
+    let discovery = discovery().ok_or(Error::NoOutput)?;
+    tracing::debug!(compositor = discovery.name(), "listing outputs");
+    discovery.outputs()
+}
+
+/// Where the pointer is in the layout, `None` unless the running compositor tells.
+pub fn pointer() -> Result<Option<(f64, f64)>, Error> {
+    match discovery() {
+        Some(discovery) => discovery.pointer(),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hyprland_monitors_are_sized_in_logical_pixels() {
+        let monitors: Vec<Monitor> = serde_json::from_str(
+            r#"[
+                {"id": 0, "name": "DP-1", "x": 0, "y": 0, "width": 3840, "height": 2160,
+                 "scale": 2.0, "transform": 0, "focused": false, "disabled": false},
+                {"id": 1, "name": "HDMI-A-1", "x": 1920, "y": 0, "width": 1920, "height": 1080,
+                 "scale": 1.0, "transform": 1, "focused": true, "disabled": false}
+            ]"#,
+        )
+        .unwrap();
+        let outputs: Vec<Output> = monitors.into_iter().map(Output::from).collect();
+        assert_eq!(
+            outputs[0].rect,
+            Rect {
+                x: 0,
+                y: 0,
+                width: 1920,
+                height: 1080
+            }
+        );
+        // Rotated
+        assert_eq!(
+            (outputs[1].rect.width, outputs[1].rect.height),
+            (1080, 1920)
+        );
+        assert_eq!(outputs[0].scale, 2.0);
+        assert!(outputs[1].focused && outputs[1].active);
+    }
+}
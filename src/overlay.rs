@@ -0,0 +1,155 @@
+//! What to capture for the overlay and which output it goes on: the output `--output` picks or
+//! the focused one, GDK's monitor for it and its size, and one capture per output for
+//! `--all-outputs` and `--output-at-cursor`.
+
+use {
+    crate::{
+        args::Args,
+        capture::{self, Capture},
+        outputs,
+        tree::{self, Rect},
+        view::Geometry,
+        Error,
+    },
+    gtk::{
+        glib::{self, clone},
+        prelude::*,
+    },
+};
+
+/// One overlay per output: the capture, the output it is of, and the arguments the overlay would
+/// have been started with alone.
+pub type Overlay = (Capture, Option<String>, Args);
+
+/// The output the overlay is for: the one given with `--output`, or the focused one.
+pub fn output(args: &Args) -> Result<String, Error> {
+    match &args.output {
+        Some(name) => Ok(name.clone()),
+        None => capture::focused_output(),
+    }
+}
+
+/// GDK's monitor for the output called `name`, found by where it is in the layout.
+pub fn monitor(window: &gtk::ApplicationWindow, name: &str) -> Option<gtk::gdk::Monitor> {
+    let rect = capture::output_rect(name)
+        .map_err(|e| tracing::warn!(error = %e, "showing on the default monitor"))
+        .ok()?;
+    let display = WidgetExt::display(window);
+    (0..display.n_monitors())
+        .filter_map(|i| display.monitor(i))
+        .find(|m| (m.geometry().x, m.geometry().y) == (rect.x, rect.y))
+}
+
+/// Logical size of the output the overlay shows up on: the chosen one when the compositor can
+/// say where that is, otherwise the first one GDK knows about.
+pub fn output_size(window: &gtk::ApplicationWindow, args: &Args) -> Option<(i32, i32)> {
+    let known = match args.output {
+        Some(_) => outputs::available(),
+        None => outputs::knows_focus(),
+    };
+    let size = match known {
+        true => output(args)
+            .and_then(|name| capture::output_rect(&name))
+            .map(|r| (r.width, r.height)),
+        false => WidgetExt::display(window)
+            .monitor(0)
+            .map(|m| (m.geometry().width, m.geometry().height))
+            .ok_or(Error::NoOutput),
+    };
+    size.map_err(|e| tracing::warn!(error = %e, "covering the whole output"))
+        .ok()
+}
+
+/// Close every overlay of `app` but the first one the pointer enters.
+///
+/// Neither sway's IPC nor Wayland tell clients where the pointer is, only which of their surfaces
+/// it is over, so every output gets one and the one it turns out to be on stays.
+pub fn keep_under_pointer(app: &gtk::Application) {
+    for window in app.windows() {
+        window.connect_enter_notify_event(
+            clone!(@weak app => @default-return Inhibit(false), move |window, _| {
+                app.windows()
+                    .iter()
+                    .filter(|w| *w != window)
+                    .for_each(|w| w.close());
+                Inhibit(false)
+            }),
+        );
+    }
+}
+
+/// The part of the layout `--region`, `--con-id`, `--window` or `--select` asks for.
+fn capture_region(args: &Args) -> Result<Rect, Error> {
+    match (args.region, args.con_id) {
+        (Some(region), _) => Ok(region),
+        (None, Some(id)) => tree::container_rect(Some(id)),
+        (None, None) if args.window => tree::container_rect(None),
+        (None, None) => capture::select_region(),
+    }
+}
+
+/// Capture what `args` ask for.
+pub fn capture(args: &Args) -> Result<Vec<Overlay>, Error> {
+    Ok(match &args.file {
+        Some(path) => vec![(capture::load_file(path)?, None, args.clone())],
+        None if args.captures_region() => {
+            let region = capture_region(args)?;
+            // The overlay covers just the region, so the window is still the size of the image
+            let (output, rect) = capture::output_containing(&region)?;
+            let args = Args {
+                output: Some(output.clone()),
+                region: Some(region),
+                geometry: Some(Geometry::Rect(
+                    region.x - rect.x,
+                    region.y - rect.y,
+                    region.width,
+                    region.height,
+                )),
+                ..args.clone()
+            };
+            vec![(
+                capture::screenshot_region(region, args.cursor)?,
+                Some(output),
+                args,
+            )]
+        }
+        None if args.all_outputs || args.output_at_cursor => {
+            capture::check_tools()?;
+            // Capture everything before any overlay covers an output
+            capture::active_outputs()?
+                .into_iter()
+                .map(|name| {
+                    let img = capture::screenshot(Some(&name), args.cursor)?;
+                    let args = Args {
+                        output: Some(name.clone()),
+                        ..args.clone()
+                    };
+                    Ok((img, Some(name), args))
+                })
+                .collect::<Result<Vec<_>, Error>>()?
+        }
+        None => {
+            let output = chosen_output(args)?;
+            vec![(
+                capture::screenshot(output.as_deref(), args.cursor)?,
+                output,
+                args.clone(),
+            )]
+        }
+    })
+}
+
+/// The output to capture when nothing else picks what to capture.
+pub fn chosen_output(args: &Args) -> Result<Option<String>, Error> {
+    capture::check_tools()?;
+    match &args.output {
+        Some(name) => {
+            // Fail with a clear message rather than whatever grim says
+            if outputs::available() {
+                capture::output_rect(name)?;
+            }
+            Ok(Some(name.clone()))
+        }
+        None => capture::default_output(),
+    }
+}
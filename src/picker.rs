@@ -0,0 +1,150 @@
+//! Color picker: a key (P by default) shows the color of the capture under the pointer next to it
+//! and copies it as `#rrggbb`. The color is also printed to stdout, one per line, which scripts
+//! reading it rely on, so it doesn't go through the logs.
+
+use {
+    crate::{
+        canvas::Canvas,
+        capture::Frame,
+        clipboard,
+        config::PICK_SHOWN_MS,
+        input,
+        view::{screen_size, ImageState},
+        Error,
+    },
+    gtk::{
+        gdk,
+        glib::{self, clone, Continue},
+        prelude::*,
+    },
+    std::{cell::Cell, rc::Rc, time::Duration},
+};
+
+/// Screen pixels between the pointer and the color shown next to it.
+const OFFSET: i32 = 16;
+
+/// Color of the pixel of `frame` at `pos`, in image pixels.
+fn sample(frame: &Frame, (x, y): (f64, f64)) -> Option<[u8; 3]> {
+    if x < 0.0 || y < 0.0 || x >= frame.width() as f64 || y >= frame.height() as f64 {
+        return None;
+    }
+    let channels = if frame.has_alpha() { 4 } else { 3 };
+    let i = y as usize * frame.rowstride() as usize + x as usize * channels;
+    let px = frame.data().get(i..i + 3)?;
+    Some([px[0], px[1], px[2]])
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Show `rgb` in `label` beside `pointer`, on the side of it with more room on a `screen` sized
+/// window.
+fn show(label: &gtk::Label, rgb: [u8; 3], pointer: (f64, f64), screen: (f64, f64)) {
+    let [r, g, b] = rgb;
+    label.set_markup(&format!(
+        "<span font_family=\"monospace\" background=\"#000000c0\" foreground=\"#ffffff\"> <span foreground=\"{0}\">██</span> {0}  rgb({1}, {2}, {3}) </span>",
+        hex(rgb), r, g, b
+    ));
+    let (x, y) = (pointer.0 as i32, pointer.1 as i32);
+    match pointer.0 > screen.0 / 2.0 {
+        true => {
+            label.set_halign(gtk::Align::End);
+            label.set_margin_start(0);
+            label.set_margin_end(screen.0 as i32 - x + OFFSET);
+        }
+        false => {
+            label.set_halign(gtk::Align::Start);
+            label.set_margin_start(x + OFFSET);
+            label.set_margin_end(0);
+        }
+    }
+    match pointer.1 > screen.1 / 2.0 {
+        true => {
+            label.set_valign(gtk::Align::End);
+            label.set_margin_top(0);
+            label.set_margin_bottom(screen.1 as i32 - y + OFFSET);
+        }
+        false => {
+            label.set_valign(gtk::Align::Start);
+            label.set_margin_top(y + OFFSET);
+            label.set_margin_bottom(0);
+        }
+    }
+    label.show();
+}
+
+/// Put `text` on the clipboard, with GTK if `wl-copy` isn't there, in which case it only lasts
+/// until the overlay quits.
+fn copy(text: &str) {
+    match clipboard::copy(text.as_bytes(), "text/plain;charset=utf-8") {
+        Ok(()) => {}
+        Err(e @ Error::MissingTool { .. }) => {
+            tracing::warn!(error = %e, "the copied color is gone once the overlay quits");
+            gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(text);
+        }
+        Err(e) => tracing::error!(error = %e, "could not copy the color"),
+    }
+}
+
+/// Pick the color under the pointer with `label` on the key with keysym `key`.
+pub fn connect(
+    window: &gtk::ApplicationWindow,
+    label: &gtk::Label,
+    canvas: &Rc<Canvas>,
+    state: &Rc<ImageState>,
+    key: u32,
+) {
+    // Only the timeout of the latest pick hides the label
+    let picks = Rc::new(Cell::new(0u32));
+    window.connect_key_press_event(
+        clone!(@strong label, @strong canvas, @strong state => move |window, evt| {
+            if input::keysym(evt) != key {
+                return Inhibit(false);
+            }
+            let frame = match canvas.frame() {
+                Some(frame) => frame,
+                None => return Inhibit(false),
+            };
+            let screen = screen_size(window);
+            let pointer = state.pointer(screen);
+            let (x, y) = state.viewport(screen).screen_to_image(pointer);
+            let density = state.density.get();
+            let rgb = match sample(&frame, (x * density, y * density)) {
+                Some(rgb) => rgb,
+                None => return Inhibit(true),
+            };
+            show(&label, rgb, pointer, screen);
+            copy(&hex(rgb));
+            println!("{}", hex(rgb));
+
+            let pick = picks.get().wrapping_add(1);
+            picks.set(pick);
+            glib::timeout_add_local(
+                Duration::from_millis(PICK_SHOWN_MS),
+                clone!(@weak label, @strong picks => @default-return Continue(false), move || {
+                    if picks.get() == pick {
+                        label.hide();
+                    }
+                    Continue(false)
+                }),
+            );
+            Inhibit(true)
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_the_pixel_under_a_point() {
+        let frame = Frame::from_rgb((1..=12).collect(), 2, 2).unwrap();
+        assert_eq!(sample(&frame, (1.7, 1.2)), Some([10, 11, 12]));
+        assert_eq!(sample(&frame, (0.0, 0.9)), Some([1, 2, 3]));
+        assert_eq!(sample(&frame, (2.0, 0.0)), None);
+        assert_eq!(sample(&frame, (-0.5, 0.0)), None);
+        assert_eq!(hex([10, 11, 255]), "#0a0bff");
+    }
+}
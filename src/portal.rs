@@ -0,0 +1,114 @@
+//! Capture everything through the screenshot interface of xdg-desktop-portal, the last resort on
+//! compositors that neither offer wlr-screencopy nor work with grim or wayshot.
+//!
+//! The portal answers on a request object with the URI of a PNG it saved, which is read and
+//! removed again.
+
+use {
+    crate::Error,
+    std::{collections::HashMap, path::PathBuf},
+    zbus::{
+        blocking::{Connection, MessageIterator},
+        zvariant::{OwnedValue, Value},
+        MatchRule,
+    },
+};
+
+/// Path of a local `file://` URI, with percent escapes decoded.
+fn file_path(uri: &str) -> Option<PathBuf> {
+    let escaped = uri.strip_prefix("file://")?.as_bytes();
+    let mut path = Vec::with_capacity(escaped.len());
+    let mut i = 0;
+    while i < escaped.len() {
+        match escaped[i] {
+            b'%' => {
+                let hex = std::str::from_utf8(escaped.get(i + 1..i + 3)?).ok()?;
+                path.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            byte => {
+                path.push(byte);
+                i += 1;
+            }
+        }
+    }
+    Some(String::from_utf8(path).ok()?.into())
+}
+
+/// PNG of everything on screen.
+#[tracing::instrument]
+pub fn screenshot() -> Result<Vec<u8>, Error> {
+    let uri = request().map_err(|e| Error::Portal(e.to_string()))??;
+    let path = file_path(&uri).ok_or_else(|| Error::Portal(format!("unexpected URI `{}`", uri)))?;
+    let png = std::fs::read(&path)?;
+    // The portal saves where screenshots go, but this one was only taken to be shown
+    if let Err(e) = std::fs::remove_file(&path) {
+        tracing::warn!(error = %e, path = %path.display(), "could not remove the portal's screenshot");
+    }
+    Ok(png)
+}
+
+/// Ask for a screenshot and wait for the URI the portal saved it at.
+fn request() -> zbus::Result<Result<String, Error>> {
+    let bus = Connection::session()?;
+    let token = format!("sway_boomer_{}", std::process::id());
+    // The request object is only returned by the call, so subscribe to where it will be first
+    let sender = bus
+        .unique_name()
+        .map(|name| name.trim_start_matches(':').replace('.', "_"))
+        .unwrap_or_default();
+    let path = format!(
+        "/org/freedesktop/portal/desktop/request/{}/{}",
+        sender, token
+    );
+    let rule = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface("org.freedesktop.portal.Request")?
+        .member("Response")?
+        .path(path.as_str())?
+        .build();
+    let mut responses = MessageIterator::for_match_rule(rule, &bus, Some(1))?;
+
+    let options = HashMap::from([
+        ("handle_token", Value::from(token.as_str())),
+        ("interactive", Value::from(false)),
+    ]);
+    bus.call_method(
+        Some("org.freedesktop.portal.Desktop"),
+        "/org/freedesktop/portal/desktop",
+        Some("org.freedesktop.portal.Screenshot"),
+        "Screenshot",
+        &("", options),
+    )?;
+
+    let msg = match responses.next() {
+        Some(msg) => msg?,
+        None => return Ok(Err(Error::Portal("no response".into()))),
+    };
+    let (response, mut results) = msg
+        .body()
+        .deserialize::<(u32, HashMap<String, OwnedValue>)>()?;
+    Ok(
+        match (response, results.remove("uri").map(String::try_from)) {
+            (0, Some(Ok(uri))) => Ok(uri),
+            (0, _) => Err(Error::Portal("no URI in the response".into())),
+            (1, _) => Err(Error::Portal("the screenshot was cancelled".into())),
+            (_, _) => Err(Error::Portal("the screenshot failed".into())),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uris_become_paths() {
+        assert_eq!(
+            file_path("file:///home/me/Pictures/Screenshot%20from%202024.png"),
+            Some(PathBuf::from("/home/me/Pictures/Screenshot from 2024.png"))
+        );
+        assert_eq!(file_path("https://example.com/a.png"), None);
+        assert_eq!(file_path("file:///a%2"), None);
+    }
+}
@@ -0,0 +1,90 @@
+//! Battery detection for low-power mode.
+//!
+//! Reads the kernel's power supply class directly, which is where upower gets its data from too,
+//! so there's no D-Bus round trip before the overlay can show up.
+
+use {
+    clap::ValueEnum,
+    std::{fs, path::Path},
+};
+
+const POWER_SUPPLY: &str = "/sys/class/power_supply";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PowerMode {
+    /// Low power while running on battery
+    Auto,
+    /// Always use the full renderer
+    Normal,
+    /// Always save power
+    Low,
+}
+
+impl PowerMode {
+    /// Whether to skip work that only makes things look smoother: pre-scaling and animations.
+    pub fn is_low(self) -> bool {
+        match self {
+            Self::Auto => on_battery(),
+            Self::Normal => false,
+            Self::Low => true,
+        }
+    }
+}
+
+/// Whether the machine runs on battery, i.e. it has one and no external supply is online.
+pub fn on_battery() -> bool {
+    let on_battery = on_battery_in(Path::new(POWER_SUPPLY));
+    tracing::debug!(on_battery, "checked power supply");
+    on_battery
+}
+
+fn on_battery_in(dir: &Path) -> bool {
+    let read = |supply: &Path, attr: &str| {
+        fs::read_to_string(supply.join(attr))
+            .map(|s| s.trim().to_owned())
+            .unwrap_or_default()
+    };
+    let supplies: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(_) => return false,
+    };
+
+    let external_online = supplies
+        .iter()
+        .any(|s| read(s, "type") != "Battery" && read(s, "online") == "1");
+    let battery = supplies.iter().any(|s| read(s, "type") == "Battery");
+    battery && !external_online
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supply(dir: &Path, name: &str, attrs: &[(&str, &str)]) {
+        let path = dir.join(name);
+        fs::create_dir_all(&path).unwrap();
+        for (attr, value) in attrs {
+            fs::write(path.join(attr), format!("{}\n", value)).unwrap();
+        }
+    }
+
+    #[test]
+    fn battery_only_counts_without_external_power() {
+        let dir = std::env::temp_dir().join(format!("sway-boomer-power-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(!on_battery_in(&dir));
+        supply(&dir, "AC", &[("type", "Mains"), ("online", "1")]);
+        assert!(!on_battery_in(&dir));
+        supply(
+            &dir,
+            "BAT0",
+            &[("type", "Battery"), ("status", "Discharging")],
+        );
+        assert!(!on_battery_in(&dir));
+        supply(&dir, "AC", &[("online", "0")]);
+        assert!(on_battery_in(&dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,339 @@
+//! Scaled copies of the capture, generated on a worker thread.
+//!
+//! Rendering asks for the copy matching the current scale. Until the worker has produced it the
+//! closest available copy is returned together with the factor still to be applied by cairo, so
+//! the main loop never waits on `scale_simple`.
+
+use {
+    crate::{capture::Frame, config::SCALE_DELTA},
+    gtk::{
+        gdk_pixbuf::{InterpType, Pixbuf},
+        glib::{self, Continue},
+    },
+    std::{
+        cell::RefCell,
+        collections::{BTreeMap, BTreeSet},
+        rc::Rc,
+        sync::mpsc,
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+/// Scale in thousandths, so levels can be used as map keys.
+type Level = u32;
+
+fn level(scale: f64) -> Level {
+    (scale * 1000.0).round() as Level
+}
+
+fn scale(level: Level) -> f64 {
+    level as f64 / 1000.0
+}
+
+/// Pixel data size of `pb`, ignoring row padding.
+fn byte_size(pb: &Pixbuf) -> usize {
+    pb.width() as usize * pb.height() as usize * pb.n_channels() as usize
+}
+
+#[derive(Debug, Default)]
+struct Levels {
+    ready: BTreeMap<Level, Pixbuf>,
+    pending: BTreeSet<Level>,
+    /// How long the worker took for the last copy.
+    last_scaled: Option<Duration>,
+}
+
+impl Levels {
+    fn bytes(&self) -> usize {
+        self.ready.values().map(byte_size).sum()
+    }
+}
+
+#[derive(Debug)]
+pub struct Pyramid {
+    source: Pixbuf,
+    /// Size of the capture before any downscaling to fit the memory budget.
+    size: (f64, f64),
+    /// Bytes the scaled copies may use besides the source.
+    budget: usize,
+    /// Whether to request scaled copies at all, see [`Self::without_prescaling`].
+    prescale: bool,
+    /// Distance to the neighbouring copies requested along with the one asked for.
+    step: Level,
+    levels: Rc<RefCell<Levels>>,
+    requests: mpsc::Sender<Level>,
+}
+
+impl Pyramid {
+    /// Start a worker scaling `frame`; `on_ready` is called on the main loop for every new copy.
+    ///
+    /// The source and its scaled copies stay within `budget` bytes: a capture larger than that is
+    /// downscaled up front, and copies that don't fit are left to cairo to scale while painting.
+    pub fn new<F: Fn() + 'static>(frame: Frame, budget: usize, on_ready: F) -> Self {
+        let full = frame.to_pixbuf();
+        let size = (full.width() as f64, full.height() as f64);
+        let source = match byte_size(&full) > budget {
+            true => {
+                let factor = (budget as f64 / byte_size(&full) as f64).sqrt();
+                tracing::warn!(
+                    bytes = byte_size(&full),
+                    budget,
+                    factor,
+                    "capture exceeds memory budget, downscaling"
+                );
+                full.scale_simple(
+                    ((size.0 * factor) as i32).max(1),
+                    ((size.1 * factor) as i32).max(1),
+                    InterpType::Bilinear,
+                )
+                .unwrap_or(full)
+            }
+            false => full,
+        };
+        let budget = budget.saturating_sub(byte_size(&source));
+        let levels = Rc::new(RefCell::new(Levels::default()));
+        let (requests, rx) = mpsc::channel::<Level>();
+        let (tx, ready) = glib::MainContext::channel(glib::PRIORITY_DEFAULT_IDLE);
+
+        let worker_source = Frame::from_pixbuf(&source);
+        thread::spawn(move || {
+            let source = match worker_source {
+                Ok(frame) => frame.to_pixbuf(),
+                Err(_) => return,
+            };
+            for level in rx {
+                let s = scale(level);
+                let _span = tracing::debug_span!("prescale", scale = s).entered();
+                let start = Instant::now();
+                let scaled = source
+                    .scale_simple(
+                        (size.0 * s) as i32,
+                        (size.1 * s) as i32,
+                        InterpType::Nearest,
+                    )
+                    .and_then(|pb| Frame::from_pixbuf(&pb).ok());
+                if tx.send((level, scaled, start.elapsed())).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let weak = Rc::downgrade(&levels);
+        ready.attach(
+            None,
+            move |(level, scaled, took): (Level, Option<Frame>, Duration)| {
+                let levels = match weak.upgrade() {
+                    Some(levels) => levels,
+                    None => return Continue(false),
+                };
+                let mut levels = levels.borrow_mut();
+                levels.pending.remove(&level);
+                levels.last_scaled = Some(took);
+                match scaled {
+                    Some(frame) => {
+                        levels.ready.insert(level, frame.to_pixbuf());
+                        drop(levels);
+                        on_ready();
+                    }
+                    None => tracing::warn!(scale = scale(level), "failed to scale capture"),
+                }
+                Continue(true)
+            },
+        );
+
+        Self {
+            source,
+            size,
+            budget,
+            prescale: true,
+            step: level(SCALE_DELTA),
+            levels,
+            requests,
+        }
+    }
+
+    /// Never scale on the worker and leave all scaling to cairo while painting, which is less work
+    /// overall at the price of slower frames while zoomed.
+    pub fn without_prescaling(mut self) -> Self {
+        self.prescale = false;
+        self
+    }
+
+    /// Prefetch copies `step` apart instead of [`SCALE_DELTA`], to match configured zoom steps.
+    pub fn with_step(mut self, step: f64) -> Self {
+        self.step = level(step).max(1);
+        self
+    }
+
+    /// Size of the captured image, which is what scales are relative to.
+    pub fn size(&self) -> (f64, f64) {
+        self.size
+    }
+
+    /// Scale of the source relative to [`Self::size`]; below 1 if it had to be downscaled.
+    fn source_scale(&self) -> f64 {
+        self.source.width() as f64 / self.size.0
+    }
+
+    /// Bytes held by the source and the scaled copies.
+    pub fn bytes(&self) -> usize {
+        byte_size(&self.source) + self.levels.borrow().bytes()
+    }
+
+    /// How long the worker took to scale the last copy it delivered.
+    pub fn last_scaled(&self) -> Option<Duration> {
+        self.levels.borrow().last_scaled
+    }
+
+    /// Whether the worker still has copies to deliver.
+    pub fn is_busy(&self) -> bool {
+        !self.levels.borrow().pending.is_empty()
+    }
+
+    /// The source and the factor that brings it to scale 1.
+    pub fn unscaled(&self) -> (Pixbuf, f64) {
+        (self.source.clone(), 1.0 / self.source_scale())
+    }
+
+    /// Best available copy for `scale` and the factor left to apply when painting it.
+    ///
+    /// Missing copies for `scale` and its neighbouring zoom steps are requested from the worker.
+    pub fn get(&self, scale: f64) -> (Pixbuf, f64) {
+        let wanted = level(scale);
+        self.prefetch(wanted);
+
+        let levels = self.levels.borrow();
+        match levels.ready.get(&wanted) {
+            Some(pb) => (pb.clone(), 1.0),
+            None => (self.source.clone(), scale / self.source_scale()),
+        }
+    }
+
+    fn prefetch(&self, wanted: Level) {
+        if !self.prescale {
+            return;
+        }
+        let mut levels = self.levels.borrow_mut();
+        let step = self.step;
+        let source = level(self.source_scale());
+        let channels = self.source.n_channels() as f64;
+
+        for l in [wanted, wanted + step, wanted.saturating_sub(step)] {
+            if l == 0 || l == source || levels.ready.contains_key(&l) || levels.pending.contains(&l)
+            {
+                continue;
+            }
+            let bytes = self.size.0 * self.size.1 * scale(l).powi(2) * channels;
+            if bytes > self.budget as f64 {
+                continue;
+            }
+            if self.requests.send(l).is_ok() {
+                levels.pending.insert(l);
+            }
+        }
+
+        // Drop the copies furthest from what is being looked at
+        while levels.bytes() > self.budget {
+            let furthest = *levels
+                .ready
+                .keys()
+                .max_by_key(|l| l.abs_diff(wanted))
+                .unwrap();
+            levels.ready.remove(&furthest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        gtk::gdk_pixbuf::Colorspace,
+        std::{cell::Cell, rc::Rc},
+    };
+
+    #[test]
+    fn level_round_trips_zoom_steps() {
+        (1..=30).for_each(|i| {
+            let s = i as f64 * SCALE_DELTA;
+            assert_eq!(level(scale(level(s))), level(s));
+        });
+    }
+
+    #[test]
+    fn scaled_copy_replaces_cairo_fallback() {
+        let ctx = glib::MainContext::new();
+        let _owner = ctx.acquire().unwrap();
+        ctx.with_thread_default(|| {
+            let pixbuf = Pixbuf::new(Colorspace::Rgb, false, 8, 10, 4).unwrap();
+            let frame = Frame::from_pixbuf(&pixbuf).unwrap();
+            let ready = Rc::new(Cell::new(0));
+            let pyramid = Pyramid::new(frame, usize::MAX, {
+                let ready = ready.clone();
+                move || ready.set(ready.get() + 1)
+            });
+
+            let (pb, residual) = pyramid.get(2.0);
+            assert_eq!((pb.width(), residual), (10, 2.0));
+
+            // 2.0 and both neighbouring steps
+            while ready.get() < 3 {
+                ctx.iteration(true);
+            }
+
+            let (pb, residual) = pyramid.get(2.0);
+            assert_eq!((pb.width(), pb.height(), residual), (20, 8, 1.0));
+            assert!(pyramid.last_scaled().is_some());
+            assert!(pyramid.bytes() > byte_size(&pyramid.source));
+        });
+    }
+
+    #[test]
+    fn nothing_is_scaled_without_prescaling() {
+        let ctx = glib::MainContext::new();
+        let _owner = ctx.acquire().unwrap();
+        ctx.with_thread_default(|| {
+            let pixbuf = Pixbuf::new(Colorspace::Rgb, false, 8, 10, 4).unwrap();
+            let frame = Frame::from_pixbuf(&pixbuf).unwrap();
+            let pyramid = Pyramid::new(frame, usize::MAX, || {}).without_prescaling();
+
+            let (pb, residual) = pyramid.get(2.0);
+            assert_eq!((pb.width(), residual), (10, 2.0));
+            assert!(!pyramid.is_busy());
+        });
+    }
+
+    #[test]
+    fn neighbours_follow_the_zoom_step() {
+        let ctx = glib::MainContext::new();
+        let _owner = ctx.acquire().unwrap();
+        ctx.with_thread_default(|| {
+            let pixbuf = Pixbuf::new(Colorspace::Rgb, false, 8, 10, 4).unwrap();
+            let frame = Frame::from_pixbuf(&pixbuf).unwrap();
+            let pyramid = Pyramid::new(frame, usize::MAX, || {}).with_step(0.25);
+
+            pyramid.get(1.0);
+            let pending = pyramid.levels.borrow().pending.clone();
+            assert_eq!(pending, [750, 1250].into());
+        });
+    }
+
+    #[test]
+    fn oversized_capture_is_downscaled_to_budget() {
+        let ctx = glib::MainContext::new();
+        let _owner = ctx.acquire().unwrap();
+        ctx.with_thread_default(|| {
+            let pixbuf = Pixbuf::new(Colorspace::Rgb, false, 8, 100, 100).unwrap();
+            let frame = Frame::from_pixbuf(&pixbuf).unwrap();
+            let pyramid = Pyramid::new(frame, 100 * 100 * 3 / 4, || {});
+
+            assert_eq!(pyramid.size(), (100.0, 100.0));
+            assert!(byte_size(&pyramid.source) <= 100 * 100 * 3 / 4);
+            // No room for copies, so cairo scales the source up to the logical size
+            let (pb, residual) = pyramid.get(1.0);
+            assert_eq!(pb.width() as f64 * residual, 100.0);
+            assert!(pyramid.levels.borrow().pending.is_empty());
+        });
+    }
+}
@@ -0,0 +1,72 @@
+//! Capture again on a key (F5 by default) when what's on screen changed since the overlay opened,
+//! keeping the zoom and position.
+//!
+//! The overlay covers what would be captured, so it is hidden while capturing and shown again
+//! with the new capture.
+
+use {
+    crate::{
+        canvas::Canvas,
+        capture::{self, Frame},
+        config::RECAPTURE_DELAY_MS,
+        input,
+        schedule::Scheduler,
+        tree::Rect,
+        Error,
+    },
+    gtk::{
+        glib::{self, clone, Continue},
+        prelude::*,
+    },
+    std::{cell::Cell, rc::Rc, thread, time::Duration},
+};
+
+/// Capture `output`, or just `region` of the layout, again and show it in `canvas` when the key
+/// with keysym `key` is pressed on `window`. The pointer is left in if `cursor`.
+pub fn connect(
+    window: &gtk::ApplicationWindow,
+    canvas: &Rc<Canvas>,
+    scheduler: &Rc<Scheduler>,
+    output: Option<String>,
+    region: Option<Rect>,
+    cursor: bool,
+    key: u32,
+) {
+    let busy = Rc::new(Cell::new(false));
+    window.connect_key_press_event(
+        clone!(@strong canvas, @strong scheduler => move |window, evt| {
+            if input::keysym(evt) != key {
+                return Inhibit(false);
+            }
+            if busy.replace(true) {
+                return Inhibit(true);
+            }
+            window.hide();
+
+            let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+            let output = output.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(RECAPTURE_DELAY_MS));
+                let capture = match region {
+                    Some(region) => capture::screenshot_region(region, cursor),
+                    None => capture::screenshot(output.as_deref(), cursor),
+                };
+                let _ = tx.send(capture.and_then(|c| c.into_frame()));
+            });
+            rx.attach(
+                None,
+                clone!(@weak window, @strong canvas, @strong scheduler, @strong busy => @default-return Continue(false), move |frame: Result<Frame, Error>| {
+                    match frame {
+                        Ok(frame) => canvas.set_frame(frame),
+                        Err(e) => tracing::warn!(error = %e, "capturing again failed, keeping the old capture"),
+                    }
+                    busy.set(false);
+                    window.show();
+                    scheduler.redraw();
+                    Continue(false)
+                }),
+            );
+            Inhibit(true)
+        }),
+    );
+}
@@ -0,0 +1,185 @@
+//! Remote control over TCP, for driving the overlay from a phone or a second laptop during a talk.
+//!
+//! Off unless `--remote` is given. A connection first has to send `auth <token>`, after that every
+//! line is a [`command`](crate::command) and gets answered with `ok` or `error: <reason>`.
+
+use {
+    crate::command::{self, Command},
+    gtk::glib::{self, Continue},
+    std::{
+        fs::File,
+        io::{self, BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::mpsc,
+        thread,
+        time::Duration,
+    },
+};
+
+/// Longest line a client may send, anything longer ends the connection.
+const MAX_LINE: u64 = 4096;
+/// How long a client has to authenticate, so connections that never do don't pile up.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) type Reply = mpsc::Sender<Result<(), String>>;
+
+/// Random token for when none is configured, as hex.
+pub fn generate_token() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Compare without bailing out at the first difference, so timing doesn't give the token away.
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Accept clients on `listener`, calling `on_command` on the main loop for every command from a
+/// client that sent `token`.
+pub fn serve<F: Fn(Command) -> Result<(), String> + 'static>(
+    listener: TcpListener,
+    token: String,
+    on_command: F,
+) {
+    let (tx, rx) = glib::MainContext::channel::<(Command, Reply)>(glib::PRIORITY_DEFAULT);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    tracing::debug!(error = %e, "remote connection failed");
+                    continue;
+                }
+            };
+            let (tx, token) = (tx.clone(), token.clone());
+            thread::spawn(move || {
+                let peer = stream.peer_addr().ok();
+                let send = |cmd, reply| tx.send((cmd, reply)).is_ok();
+                if let Err(e) = client(stream, &token, AUTH_TIMEOUT, send) {
+                    tracing::debug!(error = %e, ?peer, "remote client disconnected");
+                }
+            });
+        }
+    });
+    rx.attach(None, move |(cmd, reply): (Command, Reply)| {
+        let _ = reply.send(on_command(cmd));
+        Continue(true)
+    });
+}
+
+/// Read one line of at most [`MAX_LINE`] bytes, `None` at the end of the stream.
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    match reader.take(MAX_LINE).read_line(&mut line)? {
+        0 => Ok(None),
+        _ if !line.ends_with('\n') && line.len() as u64 == MAX_LINE => {
+            Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"))
+        }
+        _ => Ok(Some(line)),
+    }
+}
+
+/// Talk to a single client, passing its commands to `send` until it returns `false`. The client
+/// is dropped unless it authenticates within `timeout`.
+fn client(
+    stream: TcpStream,
+    token: &str,
+    timeout: Duration,
+    send: impl Fn(Command, Reply) -> bool,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(timeout))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    let authed = read_line(&mut reader)?
+        .as_deref()
+        .and_then(|line| line.trim().strip_prefix("auth "))
+        .is_some_and(|given| token_matches(given.trim(), token));
+    if !authed {
+        tracing::warn!(peer = ?writer.peer_addr().ok(), "remote client failed to authenticate");
+        return writer.write_all(b"error: not authenticated\n");
+    }
+    writer.write_all(b"ok\n")?;
+    // Commands can be far apart during a talk
+    writer.set_read_timeout(None)?;
+    commands(&mut reader, &mut writer, send)
+}
+
+/// Answer the command lines from `reader` on `writer`, passing the commands to `send` until it
+/// returns `false`.
+pub(crate) fn commands(
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+    send: impl Fn(Command, Reply) -> bool,
+) -> io::Result<()> {
+    while let Some(line) = read_line(reader)? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = match command::parse(&line) {
+            Ok(cmd) => {
+                let (reply, response) = mpsc::channel();
+                if !send(cmd, reply) {
+                    break;
+                }
+                response.recv().unwrap_or(Err("overlay closed".into()))
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => writer.write_all(b"ok\n")?,
+            Err(e) => writeln!(writer, "error: {}", e)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::input::Action, std::net::Shutdown};
+
+    #[test]
+    fn commands_need_the_token() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                let _ = client(stream, "secret", AUTH_TIMEOUT, |cmd, reply| {
+                    assert_eq!(cmd, Command::Do(Action::ZoomIn));
+                    reply.send(Ok(())).is_ok()
+                });
+            }
+        });
+
+        let talk = |input: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(input.as_bytes()).unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+            let mut output = String::new();
+            stream.read_to_string(&mut output).unwrap();
+            output
+        };
+        assert_eq!(talk("zoom-in\n"), "error: not authenticated\n");
+        assert_eq!(
+            talk("auth secret\nzoom-in\nfly\n"),
+            "ok\nok\nerror: unknown command `fly`\n"
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn silent_clients_are_dropped() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let timeout = Duration::from_millis(20);
+        assert!(client(stream, "secret", timeout, |_, _| true).is_err());
+    }
+}
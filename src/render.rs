@@ -0,0 +1,614 @@
+use {
+    crate::{
+        annotate,
+        capture::Frame,
+        config::{DOCK_BORDER, DOCK_BORDER_WIDTH, HIGH_CONTRAST},
+        cursor,
+        filter::Filters,
+        pyramid::Pyramid,
+        view::{screen_size, Dock, ImageState, Lens, Viewport},
+        Error,
+    },
+    gtk::{
+        cairo::{Context, FillRule, Filter, Format, ImageSurface, Surface},
+        gdk_pixbuf::Pixbuf,
+        prelude::*,
+    },
+    std::{cell::RefCell, fs::File, path::Path},
+};
+
+/// Pixels for [`paint`]: a copy of the image and the factor cairo still has to scale it by.
+pub trait Source {
+    /// Best copy for showing the image at `scale`.
+    fn get(&self, scale: f64) -> (Pixbuf, f64);
+
+    /// Copy for showing the image at scale 1, without asking for anything to be scaled.
+    fn unscaled(&self) -> (Pixbuf, f64);
+}
+
+impl Source for Pyramid {
+    fn get(&self, scale: f64) -> (Pixbuf, f64) {
+        Pyramid::get(self, scale)
+    }
+
+    fn unscaled(&self) -> (Pixbuf, f64) {
+        Pyramid::unscaled(self)
+    }
+}
+
+impl Source for Pixbuf {
+    fn get(&self, scale: f64) -> (Pixbuf, f64) {
+        (self.clone(), scale)
+    }
+
+    fn unscaled(&self) -> (Pixbuf, f64) {
+        (self.clone(), 1.0)
+    }
+}
+
+/// Everything the view of the capture looks like depends on, apart from what's drawn over it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ViewKey {
+    viewport: Viewport,
+    /// Width of the copy painted, and what cairo still scales it by.
+    copy: (i32, f64),
+    filter: Filter,
+    filters: Filters,
+    high_contrast: bool,
+    background: (f64, f64, f64),
+    device_scale: f64,
+}
+
+/// The view of the capture as last painted, without the highlight, cursor or lens over it. While
+/// only those move, as they do with the pointer, it's copied instead of scaling the capture again.
+#[derive(Debug, Default)]
+pub struct ViewCache(RefCell<Option<(ViewKey, Surface)>>);
+
+impl ViewCache {
+    /// Forget the view, for when the capture itself changed.
+    pub fn clear(&self) {
+        self.0.replace(None);
+    }
+
+    /// Paint what `paint_view` paints for `key` onto `ctx`, covering `size`, from the cache when
+    /// `key` is what it was painted for.
+    fn paint(
+        &self,
+        ctx: &Context,
+        key: ViewKey,
+        size: (f64, f64),
+        paint_view: impl FnOnce(&Context),
+    ) {
+        let mut cached = self.0.borrow_mut();
+        if cached.as_ref().is_none_or(|(k, _)| *k != key) {
+            let s = key.device_scale;
+            let surface = ctx.target().create_similar_image(
+                Format::Rgb24,
+                (size.0 * s).ceil() as i32,
+                (size.1 * s).ceil() as i32,
+            );
+            let surface = match surface.and_then(|surface| Ok((Context::new(&surface)?, surface))) {
+                Ok((view, surface)) => {
+                    surface.set_device_scale(s, s);
+                    let (r, g, b) = key.background;
+                    view.set_source_rgb(r, g, b);
+                    let _ = view.paint();
+                    paint_view(&view);
+                    surface
+                }
+                Err(e) => {
+                    tracing::debug!(error = %e, "painting the view uncached");
+                    return paint_view(ctx);
+                }
+            };
+            *cached = Some((key, surface));
+        }
+        if let Some((_, surface)) = &*cached {
+            let _ = ctx.set_source_surface(surface, 0.0, 0.0);
+            let _ = ctx.paint();
+        }
+    }
+}
+
+/// Draw the capture as seen through `state` on a screen of `size` logical pixels, or just the
+/// background while it is still loading. `cache` keeps the view for redraws where only what's
+/// drawn at the pointer changed.
+pub fn draw(
+    ctx: &Context,
+    pyramid: Option<&Pyramid>,
+    size: (f64, f64),
+    state: &ImageState,
+    cache: &ViewCache,
+) {
+    let scale = state.scale.get();
+    let _span = tracing::trace_span!("render", scale).entered();
+
+    match pyramid {
+        Some(pyramid) => paint(ctx, Some(pyramid), size, state, Some(cache)),
+        None => paint(ctx, None, size, state, None),
+    }
+}
+
+/// Paint `source`, the capture, as seen through `state` on a screen of `size` logical pixels,
+/// keeping the view in `cache` if there is one.
+pub fn paint(
+    ctx: &Context,
+    source: Option<&dyn Source>,
+    size: (f64, f64),
+    state: &ImageState,
+    cache: Option<&ViewCache>,
+) {
+    // Fill background
+    let background = state.background.get();
+    ctx.set_source_rgba(background.0, background.1, background.2, 1f64);
+    let _ = ctx.paint();
+
+    let source = match source {
+        Some(source) => source,
+        None => return,
+    };
+
+    // Sources are scaled in capture pixels
+    let density = state.density.get();
+    let interpolation = state.interpolation.get();
+    let filter = |scale: f64| match interpolation.smooth(scale) {
+        true => Filter::Bilinear,
+        false => Filter::Nearest,
+    };
+    // Prescaled copies are scaled with nearest, so blending starts from the source
+    let copy = |scale: f64| match interpolation.smooth(scale) {
+        true => {
+            let (pb, factor) = source.unscaled();
+            (pb, factor * scale / density)
+        }
+        false => source.get(scale / density),
+    };
+    match state.dock.get() {
+        None => {
+            // Let cairo make up for a scaled copy that isn't ready yet
+            let t = state.viewport(size);
+            let (pb, factor) = copy(t.scale);
+            let paint = |ctx: &Context| paint_view(ctx, &pb, factor, filter(t.scale), t, state);
+            match cache {
+                Some(cache) => {
+                    let key = ViewKey {
+                        viewport: t,
+                        copy: (pb.width(), factor),
+                        filter: filter(t.scale),
+                        filters: state.filters.get(),
+                        high_contrast: state.high_contrast.get(),
+                        background,
+                        device_scale: ctx.target().device_scale().0,
+                    };
+                    cache.paint(ctx, key, size, paint);
+                }
+                None => paint(ctx),
+            }
+        }
+        Some(dock) => {
+            let (pb, factor) = source.unscaled();
+            paint_view(
+                ctx,
+                &pb,
+                factor / density,
+                filter(1.0),
+                state.unscaled(size),
+                state,
+            );
+
+            let (top, height) = dock.band(size.1);
+            ctx.save().ok();
+            ctx.rectangle(0.0, top, size.0, height);
+            ctx.clip();
+            ctx.set_source_rgb(background.0, background.1, background.2);
+            let _ = ctx.paint();
+            let t = state.dock_viewport(size, (top, height));
+            let (pb, factor) = copy(t.scale);
+            paint_view(ctx, &pb, factor, filter(t.scale), t, state);
+            ctx.restore().ok();
+
+            let edge = match dock {
+                Dock::Top => top + height,
+                Dock::Bottom => top,
+            };
+            ctx.set_source_rgb(DOCK_BORDER.0, DOCK_BORDER.1, DOCK_BORDER.2);
+            ctx.rectangle(
+                0.0,
+                edge - DOCK_BORDER_WIDTH / 2.0,
+                size.0,
+                DOCK_BORDER_WIDTH,
+            );
+            let _ = ctx.fill();
+        }
+    }
+
+    let (mx, my) = state.pointer(size);
+    if let Some(lens) = state.lens.get() {
+        let (hw, hh) = lens.half_size(state.lens_radius.get());
+        let outline = |ctx: &Context| match lens {
+            Lens::Circle => ctx.arc(mx, my, hw, 0.0, std::f64::consts::TAU),
+            Lens::Rectangle => ctx.rectangle(mx - hw, my - hh, hw * 2.0, hh * 2.0),
+        };
+        ctx.save().ok();
+        outline(ctx);
+        ctx.clip();
+        ctx.set_source_rgb(background.0, background.1, background.2);
+        let _ = ctx.paint();
+        let t = state.lens_viewport(size);
+        let (pb, factor) = copy(t.scale);
+        paint_view(ctx, &pb, factor, filter(t.scale), t, state);
+        ctx.restore().ok();
+
+        outline(ctx);
+        ctx.set_source_rgb(DOCK_BORDER.0, DOCK_BORDER.1, DOCK_BORDER.2);
+        ctx.set_line_width(DOCK_BORDER_WIDTH);
+        let _ = ctx.stroke();
+    }
+    if state.highlight.get() {
+        let (hx, hy) = state.highlight_pos(size);
+        let radius = state.highlight_radius.get();
+        match state.dim_outside.get() {
+            Some(dim) => {
+                // The circle is a hole in a screen sized rectangle
+                ctx.set_source_rgba(0.0, 0.0, 0.0, dim);
+                ctx.set_fill_rule(FillRule::EvenOdd);
+                ctx.rectangle(0.0, 0.0, size.0, size.1);
+                ctx.new_sub_path();
+                ctx.arc(hx, hy, radius, 0.0, std::f64::consts::TAU);
+                let _ = ctx.fill();
+                ctx.set_fill_rule(FillRule::Winding);
+            }
+            None => {
+                let (r, g, b, a) = state.highlight_color.get();
+                ctx.set_source_rgba(r, g, b, a);
+                ctx.arc(hx, hy, radius, 0.0, std::f64::consts::TAU);
+                let _ = ctx.fill();
+            }
+        }
+    }
+
+    if let Some(cursor_size) = state.cursor_size.get() {
+        cursor::draw(ctx, (mx, my), cursor_size);
+    }
+}
+
+/// Paint `pb`, a copy of the image `t` shows that still needs scaling by `factor` with `filter`,
+/// through `t`.
+fn paint_view(
+    ctx: &Context,
+    pb: &Pixbuf,
+    factor: f64,
+    filter: Filter,
+    t: Viewport,
+    state: &ImageState,
+) {
+    let (x, y) = t.origin();
+
+    // Only hand cairo the part of the pixbuf that ends up on screen, at high zoom that's a small
+    // fraction of it
+    let visible = ctx
+        .clip_extents()
+        .ok()
+        .and_then(|(x1, y1, x2, y2)| t.visible_region((x1, y1), (x2, y2)));
+    if let Some(((x1, y1), (x2, y2))) = visible {
+        // Pixels of `pb` per image pixel, below 1 for a downscaled source
+        let k = pb.width() as f64 / t.source_size.0;
+        let px = ((x1 * k).floor() as i32).clamp(0, pb.width() - 1);
+        let py = ((y1 * k).floor() as i32).clamp(0, pb.height() - 1);
+        let pw = ((x2 * k).ceil() as i32).min(pb.width()) - px;
+        let ph = ((y2 * k).ceil() as i32).min(pb.height()) - py;
+
+        let region = pb
+            .new_subpixbuf(px, py, pw.max(1), ph.max(1))
+            .and_then(|region| match state.filters.get().matrix() {
+                Some(matrix) => matrix.apply_to_pixbuf(&region),
+                None => Some(region),
+            })
+            .and_then(|region| match state.high_contrast.get() {
+                true => HIGH_CONTRAST.apply_to_pixbuf(&region),
+                false => Some(region),
+            });
+        if let Some(region) = region {
+            ctx.save().ok();
+            ctx.translate(x, y);
+            ctx.scale(factor, factor);
+            ctx.set_source_pixbuf(&region, px as f64, py as f64);
+            ctx.source().set_filter(filter);
+            let _ = ctx.paint();
+            ctx.restore().ok();
+        }
+    }
+}
+
+/// Render `frame` as a screen of logical size `widget_size` shows it through `state`, annotations
+/// included, without a window. The image has as many pixels per logical one as the capture.
+pub fn view(
+    frame: &Frame,
+    state: &ImageState,
+    widget_size: (f64, f64),
+) -> Result<ImageSurface, Error> {
+    let d = state.density.get();
+    let pixels = |v: f64| ((v * d).round() as i32).max(1);
+    let surface =
+        ImageSurface::create(Format::Rgb24, pixels(widget_size.0), pixels(widget_size.1))?;
+    let ctx = Context::new(&surface)?;
+    ctx.scale(d, d);
+    let source = frame.to_pixbuf();
+    paint(&ctx, Some(&source), widget_size, state, None);
+    annotate::draw(
+        &ctx,
+        &state.annotations.borrow(),
+        &state.viewport(widget_size),
+    );
+    drop(ctx);
+    surface.flush();
+    Ok(surface)
+}
+
+/// What `widget` shows of `frame` through `state`, rendered like [`view`].
+pub fn shown(
+    widget: &impl IsA<gtk::Widget>,
+    frame: &Frame,
+    state: &ImageState,
+) -> Result<ImageSurface, Error> {
+    view(frame, state, screen_size(widget))
+}
+
+/// Render `frame` as seen through `state` into an image of the same size, for when there is no
+/// window. That's the view at the resolution of the output it was captured on.
+pub fn offscreen(frame: &Frame, state: &ImageState) -> Result<ImageSurface, Error> {
+    view(
+        frame,
+        state,
+        state.logical_size((frame.width(), frame.height())),
+    )
+}
+
+/// Render like [`offscreen`] and save the result as a PNG at `path`.
+pub fn offscreen_to_png(frame: &Frame, state: &ImageState, path: &Path) -> Result<(), Error> {
+    write_png(&offscreen(frame, state)?, path)?;
+    tracing::info!(path = %path.display(), "wrote offscreen render");
+    Ok(())
+}
+
+/// Save `surface` as a PNG at `path`.
+pub fn write_png(surface: &ImageSurface, path: &Path) -> Result<(), Error> {
+    Ok(surface.write_to_png(&mut File::create(path)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::view::Interpolation, gtk::gdk_pixbuf::Colorspace};
+
+    /// 8x4 image, left half red and right half blue.
+    fn frame() -> Frame {
+        let pb = Pixbuf::new(Colorspace::Rgb, false, 8, 8, 4).unwrap();
+        pb.fill(0xff0000ff);
+        pb.new_subpixbuf(4, 0, 4, 4).unwrap().fill(0x0000ffff);
+        Frame::from_pixbuf(&pb).unwrap()
+    }
+
+    /// RGB of the pixel at `(x, y)`.
+    fn pixel(surface: &mut ImageSurface, x: usize, y: usize) -> (u8, u8, u8) {
+        let stride = surface.stride() as usize;
+        let data = surface.data().unwrap();
+        // Rgb24 is stored as native-endian 0xXXRRGGBB
+        let px = u32::from_ne_bytes(data[y * stride + x * 4..][..4].try_into().unwrap());
+        ((px >> 16) as u8, (px >> 8) as u8, px as u8)
+    }
+
+    fn background() -> (u8, u8, u8) {
+        rgb(ImageState::default().background.get())
+    }
+
+    fn rgb((r, g, b): (f64, f64, f64)) -> (u8, u8, u8) {
+        // cairo goes through 16 bits per channel
+        let c = |v: f64| ((v * 65535.0).round() as u32 >> 8) as u8;
+        (c(r), c(g), c(b))
+    }
+
+    #[test]
+    fn identity_view_matches_source() {
+        let mut surface = offscreen(&frame(), &ImageState::default()).unwrap();
+        assert_eq!(pixel(&mut surface, 0, 0), (255, 0, 0));
+        assert_eq!(pixel(&mut surface, 7, 3), (0, 0, 255));
+    }
+
+    #[test]
+    fn zoom_out_reveals_background() {
+        let state = ImageState::default();
+        state.scale.set(0.5);
+        let mut surface = offscreen(&frame(), &state).unwrap();
+        assert_eq!(pixel(&mut surface, 0, 0), background());
+        assert_eq!(pixel(&mut surface, 2, 1), (255, 0, 0));
+        assert_eq!(pixel(&mut surface, 5, 2), (0, 0, 255));
+        assert_eq!(pixel(&mut surface, 6, 3), background());
+    }
+
+    #[test]
+    fn configured_background_is_used() {
+        let state = ImageState::default();
+        state.scale.set(0.5);
+        state.background.set((0.0, 0.5, 1.0));
+        let mut surface = offscreen(&frame(), &state).unwrap();
+        assert_eq!(pixel(&mut surface, 0, 0), rgb((0.0, 0.5, 1.0)));
+    }
+
+    #[test]
+    fn zoom_in_keeps_center() {
+        let state = ImageState::default();
+        state.scale.set(2.0);
+        let mut surface = offscreen(&frame(), &state).unwrap();
+        assert_eq!(pixel(&mut surface, 3, 0), (255, 0, 0));
+        assert_eq!(pixel(&mut surface, 4, 0), (0, 0, 255));
+    }
+
+    #[test]
+    fn dense_capture_is_shown_pixel_for_pixel() {
+        let state = ImageState::default();
+        state.density.set(2.0);
+        let mut surface = offscreen(&frame(), &state).unwrap();
+        assert_eq!(pixel(&mut surface, 3, 0), (255, 0, 0));
+        assert_eq!(pixel(&mut surface, 4, 0), (0, 0, 255));
+
+        // A capture pixel per logical pixel
+        state.set_scale(2.0);
+        let mut surface = offscreen(&frame(), &state).unwrap();
+        assert_eq!(pixel(&mut surface, 1, 0), (255, 0, 0));
+        assert_eq!(pixel(&mut surface, 2, 0), (255, 0, 0));
+        assert_eq!(pixel(&mut surface, 4, 0), (0, 0, 255));
+    }
+
+    #[test]
+    fn bilinear_blends_where_nearest_does_not() {
+        let state = ImageState::default();
+        state.set_scale(1.5);
+        state.interpolation.set(Interpolation::Nearest);
+        let mut surface = offscreen(&frame(), &state).unwrap();
+        assert_eq!(pixel(&mut surface, 4, 2), (0, 0, 255));
+
+        state.interpolation.set(Interpolation::Bilinear);
+        let mut surface = offscreen(&frame(), &state).unwrap();
+        let (r, g, b) = pixel(&mut surface, 4, 2);
+        assert!(r > 0 && g == 0 && b > r);
+    }
+
+    #[test]
+    fn highlight_brightens_around_cursor() {
+        let state = ImageState::default();
+        state.highlight.set(true);
+        state.mouse_pos.set((0.0, 0.0));
+        let mut surface = offscreen(&frame(), &state).unwrap();
+        let (r, g, b) = pixel(&mut surface, 0, 0);
+        assert_eq!(r, 255);
+        assert!(g > 0 && b > 0);
+    }
+
+    #[test]
+    fn dimming_darkens_outside_the_highlight() {
+        let state = ImageState::default();
+        state.highlight.set(true);
+        state.highlight_radius.set(2.0);
+        state.dim_outside.set(Some(1.0));
+        state.mouse_pos.set((0.0, 0.0));
+        let mut surface = offscreen(&frame(), &state).unwrap();
+        assert_eq!(pixel(&mut surface, 0, 0), (255, 0, 0));
+        assert_eq!(pixel(&mut surface, 7, 3), (0, 0, 0));
+    }
+
+    #[test]
+    fn annotations_follow_the_image() {
+        let state = ImageState::default();
+        state.scale.set(2.0);
+        state.offset.set((-4.0, -2.0));
+        // A white line across the top row of the red half, drawn in image pixels
+        let mut annotations = state.annotations.borrow_mut();
+        annotations.begin(annotate::Shape::Line, (1.0, 1.0, 1.0, 1.0), 1.0, (0.0, 0.5));
+        annotations.extend((4.0, 0.5));
+        drop(annotations);
+        let mut surface = offscreen(&frame(), &state).unwrap();
+        // At scale 2 it covers the top two screen rows of the left half
+        assert_eq!(pixel(&mut surface, 3, 0), (255, 255, 255));
+        assert_eq!(pixel(&mut surface, 3, 2), (255, 0, 0));
+    }
+
+    #[test]
+    fn enlarged_cursor_is_drawn_at_pointer() {
+        let pb = Pixbuf::new(Colorspace::Rgb, false, 8, 40, 40).unwrap();
+        pb.fill(0x808080ff);
+        let state = ImageState::default();
+        state.cursor_size.set(Some(32.0));
+        state.mouse_pos.set((4.0, 4.0));
+        let mut surface = offscreen(&Frame::from_pixbuf(&pb).unwrap(), &state).unwrap();
+        // Outline at the tip, fill inside, untouched beside the arrow
+        assert_eq!(pixel(&mut surface, 4, 4), (0, 0, 0));
+        assert_eq!(pixel(&mut surface, 7, 16), (255, 255, 255));
+        assert_eq!(pixel(&mut surface, 30, 8), (128, 128, 128));
+    }
+
+    #[test]
+    fn dock_magnifies_band_only() {
+        let pb = Pixbuf::new(Colorspace::Rgb, false, 8, 8, 8).unwrap();
+        pb.fill(0xff0000ff);
+        pb.new_subpixbuf(4, 0, 4, 8).unwrap().fill(0x0000ffff);
+        let state = ImageState::default();
+        state.set_scale(2.0);
+        state.dock.set(Some(Dock::Top));
+        state.mouse_pos.set((6.0, 6.0));
+        let mut surface = offscreen(&Frame::from_pixbuf(&pb).unwrap(), &state).unwrap();
+        // Image x 4.5 in the band, the screen as it is below it
+        assert_eq!(pixel(&mut surface, 3, 0), (0, 0, 255));
+        assert_eq!(pixel(&mut surface, 3, 5), (255, 0, 0));
+    }
+
+    #[test]
+    fn cached_view_is_reused_until_it_changes() {
+        let pb = Pixbuf::new(Colorspace::Rgb, false, 8, 8, 4).unwrap();
+        pb.fill(0xff0000ff);
+        let state = ImageState::default();
+        let cache = ViewCache::default();
+        let mut surface = ImageSurface::create(Format::Rgb24, 8, 4).unwrap();
+        let draw = |surface: &ImageSurface| {
+            let ctx = Context::new(surface).unwrap();
+            paint(&ctx, Some(&pb), (8.0, 4.0), &state, Some(&cache));
+        };
+        draw(&surface);
+        // Not scaled again while only the pointer moves
+        pb.fill(0x0000ffff);
+        state.mouse_pos.set((3.0, 2.0));
+        draw(&surface);
+        assert_eq!(pixel(&mut surface, 0, 0), (255, 0, 0));
+
+        state.set_scale(2.0);
+        draw(&surface);
+        assert_eq!(pixel(&mut surface, 0, 0), (0, 0, 255));
+    }
+
+    #[test]
+    fn capture_is_centered_on_a_larger_screen() {
+        let frame = frame();
+        let state = ImageState::default();
+        state.pixels.set(Some((frame.width(), frame.height())));
+        let mut surface = ImageSurface::create(Format::Rgb24, 16, 8).unwrap();
+        {
+            let ctx = Context::new(&surface).unwrap();
+            paint(&ctx, Some(&frame.to_pixbuf()), (16.0, 8.0), &state, None);
+        }
+        assert_eq!(pixel(&mut surface, 3, 1), background());
+        assert_eq!(pixel(&mut surface, 4, 2), (255, 0, 0));
+        assert_eq!(pixel(&mut surface, 11, 5), (0, 0, 255));
+        assert_eq!(pixel(&mut surface, 12, 6), background());
+    }
+
+    #[test]
+    fn view_is_rendered_at_the_window_size() {
+        let frame = frame();
+        let state = ImageState::default();
+        state.pixels.set(Some((frame.width(), frame.height())));
+        state.density.set(2.0);
+        // A window twice as large as the capture's logical size, in pixels of the capture
+        let mut surface = view(&frame, &state, (8.0, 4.0)).unwrap();
+        assert_eq!((surface.width(), surface.height()), (16, 8));
+        assert_eq!(pixel(&mut surface, 3, 1), background());
+        assert_eq!(pixel(&mut surface, 4, 2), (255, 0, 0));
+        assert_eq!(pixel(&mut surface, 11, 5), (0, 0, 255));
+        assert_eq!(pixel(&mut surface, 12, 6), background());
+    }
+
+    #[test]
+    fn lens_magnifies_around_pointer_only() {
+        let pb = Pixbuf::new(Colorspace::Rgb, false, 8, 40, 40).unwrap();
+        pb.fill(0xff0000ff);
+        pb.new_subpixbuf(20, 0, 20, 40).unwrap().fill(0x0000ffff);
+        let state = ImageState::default();
+        state.lens.set(Some(Lens::Rectangle));
+        state.lens_radius.set(4.0);
+        state.set_lens_scale(3.0);
+        state.mouse_pos.set((22.0, 20.0));
+        let mut surface = offscreen(&Frame::from_pixbuf(&pb).unwrap(), &state).unwrap();
+        // Blue reaches three times as far from the pointer in the lens, and no further above it
+        assert_eq!(pixel(&mut surface, 19, 20), (0, 0, 255));
+        assert_eq!(pixel(&mut surface, 14, 20), (255, 0, 0));
+        assert_eq!(pixel(&mut surface, 19, 10), (255, 0, 0));
+    }
+}
@@ -0,0 +1,78 @@
+//! Save what's on screen, zoom, highlight and annotations included, as a PNG on a key (S by
+//! default). The PNG is the size of the window, in pixels of the capture.
+
+use {
+    crate::{canvas::Canvas, input, render, view::ImageState},
+    gtk::{
+        glib::{self, clone},
+        prelude::*,
+    },
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        rc::Rc,
+    },
+};
+
+/// `template` with a leading `~` replaced by `home`, for [`glib::DateTime::format`] to fill in.
+fn expand_home(template: &str, home: &Path) -> PathBuf {
+    match template.strip_prefix("~/") {
+        Some(rest) => home.join(rest),
+        None if template == "~" => home.to_path_buf(),
+        None => PathBuf::from(template),
+    }
+}
+
+/// Where a view saved now goes, following `template`.
+pub fn path(template: &str) -> Option<PathBuf> {
+    let now = glib::DateTime::new_now_local().ok()?;
+    let name = now.format(template).ok()?;
+    Some(expand_home(&name, &glib::home_dir()))
+}
+
+/// Save the view of `canvas` to files named after `template` on the key with keysym `key`.
+pub fn connect(
+    window: &gtk::ApplicationWindow,
+    canvas: &Rc<Canvas>,
+    state: &Rc<ImageState>,
+    key: u32,
+    template: String,
+) {
+    window.connect_key_press_event(clone!(@strong canvas, @strong state => move |window, evt| {
+        if input::keysym(evt) != key {
+            return Inhibit(false);
+        }
+        let (frame, path) = match (canvas.frame(), path(&template)) {
+            (Some(frame), Some(path)) => (frame, path),
+            (_, None) => {
+                tracing::error!(%template, "invalid file name template");
+                return Inhibit(true);
+            }
+            (None, _) => return Inhibit(true),
+        };
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        match render::shown(window, &frame, &state).and_then(|view| render::write_png(&view, &path)) {
+            Ok(()) => tracing::info!(path = %path.display(), "saved the view"),
+            Err(e) => tracing::error!(error = %e, path = %path.display(), "could not save the view"),
+        }
+        Inhibit(true)
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn home_is_expanded() {
+        let home = Path::new("/home/me");
+        assert_eq!(
+            expand_home("~/Pictures/a.png", home),
+            Path::new("/home/me/Pictures/a.png")
+        );
+        assert_eq!(expand_home("/tmp/a.png", home), Path::new("/tmp/a.png"));
+        assert_eq!(expand_home("~user/a.png", home), Path::new("~user/a.png"));
+    }
+}
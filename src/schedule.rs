@@ -0,0 +1,127 @@
+//! Redraws paced by the widget's frame clock.
+//!
+//! State changes only mark the view dirty; the actual redraw happens at most once per frame from
+//! a tick callback, which is removed again as soon as nothing is changing.
+
+use {
+    gtk::{gdk::FrameClock, glib::Continue, prelude::*},
+    std::{
+        cell::{Cell, RefCell},
+        rc::{Rc, Weak},
+    },
+};
+
+/// Per-frame step of an animation, given the frame time in microseconds.
+/// Returns `false` once the animation has finished.
+type Animation = Box<dyn FnMut(i64) -> bool>;
+
+pub struct Scheduler {
+    widget: gtk::Widget,
+    /// Drawn over `widget`, so redrawn along with it.
+    above: RefCell<Vec<gtk::Widget>>,
+    this: Weak<Scheduler>,
+    dirty: Cell<bool>,
+    ticking: Cell<bool>,
+    animated: Cell<bool>,
+    animations: RefCell<Vec<Animation>>,
+}
+
+impl Scheduler {
+    pub fn new(widget: &impl IsA<gtk::Widget>) -> Rc<Self> {
+        Rc::new_cyclic(|this| Self {
+            widget: widget.clone().upcast(),
+            above: RefCell::new(Vec::new()),
+            this: this.clone(),
+            dirty: Cell::new(false),
+            ticking: Cell::new(false),
+            animated: Cell::new(true),
+            animations: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Redraw on the next frame. Any number of calls within a frame cause a single redraw.
+    pub fn redraw(&self) {
+        self.dirty.set(true);
+        self.ensure_ticking();
+    }
+
+    /// Run `step` every frame until it returns `false`, redrawing after each step.
+    ///
+    /// With animations disabled `step` runs once right away with a frame time of `i64::MAX`, which
+    /// has to take it to its final state.
+    pub fn animate<F: FnMut(i64) -> bool + 'static>(&self, mut step: F) {
+        match self.animated.get() {
+            true => self.animations.borrow_mut().push(Box::new(step)),
+            false => {
+                step(i64::MAX);
+                self.dirty.set(true);
+            }
+        }
+        self.ensure_ticking();
+    }
+
+    /// Fade `widget` out over `duration_ms`, stopping where it is once `current` returns `false`.
+    pub fn fade_out(
+        &self,
+        widget: &impl IsA<gtk::Widget>,
+        duration_ms: f64,
+        current: impl Fn() -> bool + 'static,
+    ) {
+        let widget = widget.clone().upcast::<gtk::Widget>();
+        let mut start = None;
+        self.animate(move |now| {
+            if !current() {
+                return false;
+            }
+            let start = *start.get_or_insert(now);
+            let t = match now {
+                i64::MAX => 1.0,
+                _ => ((now - start) as f64 / (duration_ms * 1000.0)).min(1.0),
+            };
+            widget.set_opacity(1.0 - t);
+            t < 1.0
+        });
+    }
+
+    /// Redraw `widget`, which is drawn over the view, whenever the view is redrawn.
+    pub fn redraw_above(&self, widget: &impl IsA<gtk::Widget>) {
+        self.above.borrow_mut().push(widget.clone().upcast());
+    }
+
+    /// Turn animations off to save power, see [`Self::animate`].
+    pub fn set_animated(&self, animated: bool) {
+        self.animated.set(animated);
+    }
+
+    fn ensure_ticking(&self) {
+        if self.ticking.replace(true) {
+            return;
+        }
+        let this = self.this.clone();
+        self.widget
+            .add_tick_callback(move |_, clock| match this.upgrade() {
+                Some(scheduler) => Continue(scheduler.tick(clock)),
+                None => Continue(false),
+            });
+    }
+
+    fn tick(&self, clock: &FrameClock) -> bool {
+        let now = clock.frame_time();
+        let mut animations = self.animations.take();
+        let animated = !animations.is_empty();
+        animations.retain_mut(|step| step(now));
+        // Animations may have scheduled new ones from within their step
+        let mut current = self.animations.borrow_mut();
+        animations.append(&mut current);
+        *current = animations;
+
+        if self.dirty.replace(false) || animated {
+            self.widget.queue_draw();
+            self.above.borrow().iter().for_each(|w| w.queue_draw());
+        }
+
+        let keep = !current.is_empty();
+        self.ticking.set(keep);
+        keep
+    }
+}
@@ -0,0 +1,325 @@
+//! Capture an output through the wlr-screencopy protocol, straight into shared memory, instead of
+//! having grim encode it and reading it back.
+//!
+//! Outputs are matched by the name `wl_output` version 4 announces, which is the name sway uses.
+//! Their contents are copied the way the output scans them out, so captures of rotated or flipped
+//! outputs are turned back the way they're seen, like grim does.
+
+use {
+    crate::{capture::Frame, Error},
+    memmap2::Mmap,
+    std::{
+        fmt,
+        fs::File,
+        io,
+        os::fd::{AsFd, FromRawFd, OwnedFd},
+    },
+    wayland_client::{
+        delegate_noop,
+        globals::{registry_queue_init, GlobalListContents},
+        protocol::{wl_buffer, wl_output, wl_registry, wl_shm, wl_shm_pool},
+        Connection, Dispatch, Proxy, QueueHandle, WEnum,
+    },
+    wayland_protocols_wlr::screencopy::v1::client::{
+        zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+        zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
+    },
+};
+
+/// Shared memory buffer the compositor asks for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Buffer {
+    format: wl_shm::Format,
+    width: u32,
+    height: u32,
+    stride: u32,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Names of the bound outputs, by the index they were bound with.
+    names: Vec<Option<String>>,
+    /// Transforms of the bound outputs, likewise.
+    transforms: Vec<wl_output::Transform>,
+    buffer: Option<Buffer>,
+    y_invert: bool,
+    /// Whether the copy succeeded, once it's over.
+    done: Option<bool>,
+}
+
+fn failed(e: impl fmt::Display) -> Error {
+    Error::Screencopy(e.to_string())
+}
+
+/// Capture the output called `output`, with the pointer composited in if `cursor`.
+#[tracing::instrument]
+pub fn capture(output: &str, cursor: bool) -> Result<Frame, Error> {
+    let conn = Connection::connect_to_env().map_err(failed)?;
+    let (globals, mut queue) = registry_queue_init::<State>(&conn).map_err(failed)?;
+    let qh = queue.handle();
+    let shm: wl_shm::WlShm = globals.bind(&qh, 1..=1, ()).map_err(failed)?;
+    // Version 3 adds DMA-BUF buffers, which would only have to be mapped again
+    let manager: ZwlrScreencopyManagerV1 = globals.bind(&qh, 1..=2, ()).map_err(failed)?;
+
+    let outputs: Vec<wl_output::WlOutput> = globals
+        .contents()
+        .clone_list()
+        .into_iter()
+        .filter(|g| g.interface == wl_output::WlOutput::interface().name && g.version >= 4)
+        .enumerate()
+        .map(|(i, g)| globals.registry().bind(g.name, 4, &qh, i))
+        .collect();
+    let mut state = State {
+        names: vec![None; outputs.len()],
+        transforms: vec![wl_output::Transform::Normal; outputs.len()],
+        ..Default::default()
+    };
+    queue.roundtrip(&mut state).map_err(failed)?;
+    let index = state
+        .names
+        .iter()
+        .position(|name| name.as_deref() == Some(output))
+        .ok_or_else(|| Error::UnknownOutput(output.to_string()))?;
+    let (target, transform) = (&outputs[index], state.transforms[index]);
+
+    let frame = manager.capture_output(cursor as i32, target, &qh, ());
+    while state.buffer.is_none() && state.done.is_none() {
+        queue.blocking_dispatch(&mut state).map_err(failed)?;
+    }
+    let buffer = match state.buffer {
+        Some(buffer) => buffer,
+        None => return Err(failed("compositor refused to capture")),
+    };
+
+    let size = buffer.stride as usize * buffer.height as usize;
+    let fd = unsafe { libc::memfd_create(c"sway-boomer-screencopy".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+    file.set_len(size as u64)?;
+    let pool = shm.create_pool(file.as_fd(), size as i32, &qh, ());
+    let wl_buffer = pool.create_buffer(
+        0,
+        buffer.width as i32,
+        buffer.height as i32,
+        buffer.stride as i32,
+        buffer.format,
+        &qh,
+        (),
+    );
+    frame.copy(&wl_buffer);
+    while state.done.is_none() {
+        queue.blocking_dispatch(&mut state).map_err(failed)?;
+    }
+
+    frame.destroy();
+    wl_buffer.destroy();
+    pool.destroy();
+    manager.destroy();
+    if state.done != Some(true) {
+        return Err(failed("copying the output failed"));
+    }
+
+    let pixels = unsafe { Mmap::map(&file)? };
+    let rgb = to_rgb(&pixels, &buffer, state.y_invert)
+        .ok_or_else(|| failed(format!("unsupported pixel format {:?}", buffer.format)))?;
+    let (rgb, (width, height)) = upright(
+        rgb,
+        (buffer.width as usize, buffer.height as usize),
+        transform,
+    );
+    Frame::from_rgb(rgb, width as i32, height as i32)
+}
+
+/// Undo `transform` on packed RGB pixels of `size` as an output scans them out, returning them
+/// as they're seen, and their size. Untransformed pixels are handed back as they are.
+fn upright(
+    rgb: Vec<u8>,
+    (width, height): (usize, usize),
+    transform: wl_output::Transform,
+) -> (Vec<u8>, (usize, usize)) {
+    use wl_output::Transform as T;
+    if transform == T::Normal {
+        return (rgb, (width, height));
+    }
+    let size = match transform {
+        T::_90 | T::_270 | T::Flipped90 | T::Flipped270 => (height, width),
+        _ => (width, height),
+    };
+    let (w, h) = size;
+    // Where the pixel seen at (x, y) is in the buffer: the transform flips around the vertical
+    // axis first, then turns counter-clockwise
+    let source = |x: usize, y: usize| match transform {
+        T::_90 => (y, w - 1 - x),
+        T::_180 => (w - 1 - x, h - 1 - y),
+        T::_270 => (h - 1 - y, x),
+        T::Flipped => (w - 1 - x, y),
+        T::Flipped90 => (y, x),
+        T::Flipped180 => (x, h - 1 - y),
+        T::Flipped270 => (h - 1 - y, w - 1 - x),
+        _ => (x, y),
+    };
+    let mut out = Vec::with_capacity(rgb.len());
+    for y in 0..h {
+        for x in 0..w {
+            let (sx, sy) = source(x, y);
+            let i = (sy * width + sx) * 3;
+            out.extend_from_slice(&rgb[i..i + 3]);
+        }
+    }
+    (out, size)
+}
+
+/// Pack the pixels of `buffer` into unpadded RGB rows, top to bottom.
+fn to_rgb(data: &[u8], buffer: &Buffer, y_invert: bool) -> Option<Vec<u8>> {
+    // Where red, green and blue are in the little endian pixels
+    let (r, g, b) = match buffer.format {
+        wl_shm::Format::Xrgb8888 | wl_shm::Format::Argb8888 => (2, 1, 0),
+        wl_shm::Format::Xbgr8888 | wl_shm::Format::Abgr8888 => (0, 1, 2),
+        _ => return None,
+    };
+    let (width, height, stride) = (
+        buffer.width as usize,
+        buffer.height as usize,
+        buffer.stride as usize,
+    );
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        let y = match y_invert {
+            true => height - 1 - y,
+            false => y,
+        };
+        let row = data.get(y * stride..y * stride + width * 4)?;
+        for px in row.chunks_exact(4) {
+            rgb.extend_from_slice(&[px[r], px[g], px[b]]);
+        }
+    }
+    Some(rgb)
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, usize> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_output::WlOutput,
+        event: wl_output::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_output::Event::Name { name } => state.names[*index] = Some(name),
+            wl_output::Event::Geometry {
+                transform: WEnum::Value(transform),
+                ..
+            } => state.transforms[*index] = transform,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrScreencopyFrameV1,
+        event: zwlr_screencopy_frame_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_screencopy_frame_v1::Event::Buffer {
+                format: WEnum::Value(format),
+                width,
+                height,
+                stride,
+            } => {
+                state.buffer = Some(Buffer {
+                    format,
+                    width,
+                    height,
+                    stride,
+                })
+            }
+            zwlr_screencopy_frame_v1::Event::Flags {
+                flags: WEnum::Value(flags),
+            } => state.y_invert = flags.contains(zwlr_screencopy_frame_v1::Flags::YInvert),
+            zwlr_screencopy_frame_v1::Event::Ready { .. } => state.done = Some(true),
+            zwlr_screencopy_frame_v1::Event::Failed => state.done = Some(false),
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(State: ignore wl_shm::WlShm);
+delegate_noop!(State: ignore wl_buffer::WlBuffer);
+delegate_noop!(State: wl_shm_pool::WlShmPool);
+delegate_noop!(State: ZwlrScreencopyManagerV1);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_are_packed_and_flipped() {
+        // Two rows of two pixels, each row padded to 12 bytes
+        let data = [
+            1, 2, 3, 0, 4, 5, 6, 0, 9, 9, 9, 9, //
+            7, 8, 9, 0, 10, 11, 12, 0, 9, 9, 9, 9,
+        ];
+        let buffer = Buffer {
+            format: wl_shm::Format::Xrgb8888,
+            width: 2,
+            height: 2,
+            stride: 12,
+        };
+        assert_eq!(
+            to_rgb(&data, &buffer, false).unwrap(),
+            [3, 2, 1, 6, 5, 4, 9, 8, 7, 12, 11, 10]
+        );
+        assert_eq!(to_rgb(&data, &buffer, true).unwrap()[..3], [9, 8, 7]);
+
+        let bgr = Buffer {
+            format: wl_shm::Format::Abgr8888,
+            ..buffer
+        };
+        assert_eq!(to_rgb(&data, &bgr, false).unwrap()[..3], [1, 2, 3]);
+
+        let other = Buffer {
+            format: wl_shm::Format::Rgb565,
+            ..buffer
+        };
+        assert_eq!(to_rgb(&data, &other, false), None);
+        assert_eq!(to_rgb(&data[..16], &buffer, false), None);
+    }
+
+    #[test]
+    fn transformed_outputs_are_turned_upright() {
+        use wl_output::Transform as T;
+        // A buffer of three pixels in a row, 1, 2 and 3
+        let rgb = [1, 1, 1, 2, 2, 2, 3, 3, 3];
+        let seen = |transform| {
+            let (pixels, size) = upright(rgb.to_vec(), (3, 1), transform);
+            (pixels.iter().step_by(3).copied().collect::<Vec<u8>>(), size)
+        };
+        assert_eq!(seen(T::Normal), (vec![1, 2, 3], (3, 1)));
+        assert_eq!(seen(T::_180), (vec![3, 2, 1], (3, 1)));
+        assert_eq!(seen(T::Flipped), (vec![3, 2, 1], (3, 1)));
+        // Turned counter-clockwise to scan out, so the start of the row was at the top
+        assert_eq!(seen(T::_90), (vec![1, 2, 3], (1, 3)));
+        assert_eq!(seen(T::_270), (vec![3, 2, 1], (1, 3)));
+        assert_eq!(seen(T::Flipped90), (vec![1, 2, 3], (1, 3)));
+    }
+}
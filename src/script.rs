@@ -0,0 +1,385 @@
+//! User scripts in [rhai](https://rhai.rs), for custom overlays and automation.
+//!
+//! A script can define any of these hooks:
+//!
+//! ```text
+//! fn on_capture(width, height)  // once the capture is decoded
+//! fn on_key(name)               // on every key press, return true to skip the default binding
+//! fn on_draw_overlay()          // whenever the view is redrawn
+//! ```
+//!
+//! From within hooks it can look at the view with `scale()`, `offset()`, `pointer()`,
+//! `screen_size()`, `to_screen(x, y)` and `to_image(x, y)`, act on it with `run(command)` taking
+//! a [`command`](crate::command) line, and draw over it in screen pixels with `color(r, g, b, a)`,
+//! `rect(x, y, w, h)`, `fill(x, y, w, h)` and `text(x, y, text)`.
+
+use {
+    crate::{
+        command::{self, Command},
+        config::Preset,
+        input,
+        schedule::Scheduler,
+        view::{screen_size, ImageState, Viewport},
+        Error,
+    },
+    gtk::{
+        cairo::Context,
+        glib::{self, clone},
+        prelude::*,
+    },
+    rhai::{Array, Dynamic, Engine, EvalAltResult, FuncArgs, Scope, AST},
+    std::{
+        cell::RefCell,
+        collections::BTreeMap,
+        path::Path,
+        rc::{Rc, Weak},
+    },
+};
+
+type Rgba = (f64, f64, f64, f64);
+
+/// Something a script drew, in screen pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Rect((f64, f64, f64, f64), Rgba),
+    Fill((f64, f64, f64, f64), Rgba),
+    Text((f64, f64), String, Rgba),
+}
+
+/// What the running hook sees of the overlay and what it asked for.
+#[derive(Debug)]
+struct Host {
+    viewport: Viewport,
+    pointer: (f64, f64),
+    commands: Vec<Command>,
+    shapes: Vec<Shape>,
+    color: Rgba,
+}
+
+impl Default for Host {
+    fn default() -> Self {
+        Self {
+            viewport: ImageState::default().viewport((0.0, 0.0)),
+            pointer: (0.0, 0.0),
+            commands: Vec::new(),
+            shapes: Vec::new(),
+            color: (1.0, 1.0, 1.0, 1.0),
+        }
+    }
+}
+
+fn number(value: Dynamic) -> Result<f64, Box<EvalAltResult>> {
+    match value.as_float() {
+        Ok(f) => Ok(f),
+        Err(_) => value
+            .as_int()
+            .map(|i| i as f64)
+            .map_err(|kind| format!("expected a number, got {}", kind).into()),
+    }
+}
+
+fn pair((x, y): (f64, f64)) -> Array {
+    vec![Dynamic::from_float(x), Dynamic::from_float(y)]
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: RefCell<Scope<'static>>,
+    host: Rc<RefCell<Host>>,
+}
+
+impl Script {
+    /// Compile the script at `path` and run its top level.
+    #[tracing::instrument]
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let host = Rc::new(RefCell::new(Host::default()));
+        let engine = engine(&host);
+        let ast = engine
+            .compile_file(path.into())
+            .map_err(|e| Error::Script(format!("{}: {}", path.display(), e)))?;
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| Error::Script(format!("{}: {}", path.display(), e)))?;
+        Ok(Self {
+            engine,
+            ast,
+            scope: RefCell::new(scope),
+            host,
+        })
+    }
+
+    /// Call `hook` if the script defines it, seeing the view through `state`.
+    fn call(
+        &self,
+        hook: &str,
+        args: impl FuncArgs,
+        state: &ImageState,
+        size: (f64, f64),
+    ) -> Option<Dynamic> {
+        if !self.ast.iter_functions().any(|f| f.name == hook) {
+            return None;
+        }
+        {
+            let mut host = self.host.borrow_mut();
+            host.viewport = state.viewport(size);
+            host.pointer = state.pointer(size);
+        }
+        let result =
+            self.engine
+                .call_fn::<Dynamic>(&mut self.scope.borrow_mut(), &self.ast, hook, args);
+        result
+            .map_err(|e| tracing::warn!(error = %e, hook, "script failed"))
+            .ok()
+    }
+
+    /// Commands the last hooks asked for.
+    fn take_commands(&self) -> Vec<Command> {
+        std::mem::take(&mut self.host.borrow_mut().commands)
+    }
+
+    /// Run `on_capture` for a capture of `size` on a screen of `screen`.
+    pub fn captured(
+        &self,
+        state: &ImageState,
+        size: (f64, f64),
+        screen: (f64, f64),
+    ) -> Vec<Command> {
+        let args = (size.0 as rhai::INT, size.1 as rhai::INT);
+        self.call("on_capture", args, state, screen);
+        self.take_commands()
+    }
+
+    /// Run `on_key`, returning whether the script handled the key.
+    pub fn key(&self, name: &str, state: &ImageState, size: (f64, f64)) -> (bool, Vec<Command>) {
+        let handled = self
+            .call("on_key", (name.to_string(),), state, size)
+            .and_then(|r| r.as_bool().ok())
+            .unwrap_or(false);
+        (handled, self.take_commands())
+    }
+
+    /// Run `on_draw_overlay`, returning what it drew.
+    pub fn draw_overlay(&self, state: &ImageState, size: (f64, f64)) -> Vec<Shape> {
+        self.call("on_draw_overlay", (), state, size);
+        std::mem::take(&mut self.host.borrow_mut().shapes)
+    }
+}
+
+/// Run `f` on the host, if it is still around.
+fn with<T: Default>(host: &Weak<RefCell<Host>>, f: impl FnOnce(&mut Host) -> T) -> T {
+    host.upgrade()
+        .map(|host| f(&mut host.borrow_mut()))
+        .unwrap_or_default()
+}
+
+/// Engine with the API scripts get, working on `host`.
+fn engine(host: &Rc<RefCell<Host>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.on_print(|text| tracing::info!(text, "script"));
+    engine.on_debug(|text, _, pos| tracing::debug!(text, %pos, "script"));
+    type Result<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+    let h = Rc::downgrade(host);
+    engine.register_fn("scale", move || with(&h, |h| h.viewport.scale));
+    let h = Rc::downgrade(host);
+    engine.register_fn("offset", move || with(&h, |h| pair(h.viewport.offset)));
+    let h = Rc::downgrade(host);
+    engine.register_fn("pointer", move || with(&h, |h| pair(h.pointer)));
+    let h = Rc::downgrade(host);
+    engine.register_fn("screen_size", move || {
+        with(&h, |h| pair(h.viewport.widget_size))
+    });
+    let h = Rc::downgrade(host);
+    engine.register_fn(
+        "to_screen",
+        move |x: Dynamic, y: Dynamic| -> Result<Array> {
+            let p = (number(x)?, number(y)?);
+            Ok(with(&h, |h| pair(h.viewport.image_to_screen(p))))
+        },
+    );
+    let h = Rc::downgrade(host);
+    engine.register_fn("to_image", move |x: Dynamic, y: Dynamic| -> Result<Array> {
+        let p = (number(x)?, number(y)?);
+        Ok(with(&h, |h| pair(h.viewport.screen_to_image(p))))
+    });
+    let h = Rc::downgrade(host);
+    engine.register_fn("run", move |line: &str| -> Result<()> {
+        let cmd = command::parse(line)?;
+        with(&h, |h| h.commands.push(cmd));
+        Ok(())
+    });
+    let h = Rc::downgrade(host);
+    engine.register_fn(
+        "color",
+        move |r: Dynamic, g: Dynamic, b: Dynamic, a: Dynamic| -> Result<()> {
+            let color = (number(r)?, number(g)?, number(b)?, number(a)?);
+            with(&h, |h| h.color = color);
+            Ok(())
+        },
+    );
+    for (name, fill) in [("rect", false), ("fill", true)] {
+        let h = Rc::downgrade(host);
+        engine.register_fn(
+            name,
+            move |x: Dynamic, y: Dynamic, w: Dynamic, height: Dynamic| -> Result<()> {
+                let r = (number(x)?, number(y)?, number(w)?, number(height)?);
+                with(&h, |h| {
+                    let shape = match fill {
+                        true => Shape::Fill(r, h.color),
+                        false => Shape::Rect(r, h.color),
+                    };
+                    h.shapes.push(shape);
+                });
+                Ok(())
+            },
+        );
+    }
+    let h = Rc::downgrade(host);
+    engine.register_fn(
+        "text",
+        move |x: Dynamic, y: Dynamic, text: &str| -> Result<()> {
+            let p = (number(x)?, number(y)?);
+            with(&h, |h| {
+                h.shapes.push(Shape::Text(p, text.to_string(), h.color))
+            });
+            Ok(())
+        },
+    );
+    engine
+}
+
+/// Draw `shapes` with cairo.
+pub fn draw(ctx: &Context, shapes: &[Shape]) {
+    for shape in shapes {
+        match shape {
+            Shape::Rect((x, y, w, h), (r, g, b, a)) => {
+                ctx.set_source_rgba(*r, *g, *b, *a);
+                ctx.set_line_width(2.0);
+                ctx.rectangle(*x, *y, *w, *h);
+                let _ = ctx.stroke();
+            }
+            Shape::Fill((x, y, w, h), (r, g, b, a)) => {
+                ctx.set_source_rgba(*r, *g, *b, *a);
+                ctx.rectangle(*x, *y, *w, *h);
+                let _ = ctx.fill();
+            }
+            Shape::Text((x, y), text, (r, g, b, a)) => {
+                ctx.set_source_rgba(*r, *g, *b, *a);
+                ctx.set_font_size(16.0);
+                ctx.move_to(*x, *y);
+                let _ = ctx.show_text(text);
+            }
+        }
+    }
+}
+
+/// Run `script`'s hooks for the overlay in `window`, drawing on `decorations` over the view.
+/// Key presses go to the script before any handler that is connected later.
+///
+/// Returns what to call with the size of the capture in pixels once it's decoded.
+pub fn connect(
+    script: Script,
+    app: &gtk::Application,
+    window: &gtk::ApplicationWindow,
+    decorations: &gtk::DrawingArea,
+    scheduler: &Rc<Scheduler>,
+    state: &Rc<ImageState>,
+    presets: BTreeMap<String, Preset>,
+) -> impl Fn((i32, i32)) {
+    let script = Rc::new(script);
+    let perform = Rc::new(
+        clone!(@weak app, @weak window, @weak scheduler, @weak state => move |commands: Vec<Command>| {
+            for cmd in commands {
+                match cmd.action(presets.iter()) {
+                    Ok(action) => input::perform(action, &app, &window, &scheduler, &state),
+                    Err(e) => tracing::warn!(error = %e, "script command ignored"),
+                }
+            }
+        }),
+    );
+
+    window.connect_key_press_event(
+        clone!(@strong script, @strong state, @strong perform => move |window, event| {
+            let size = screen_size(window);
+            let name = match event.keyval().name() {
+                Some(name) => name,
+                None => return Inhibit(false),
+            };
+            let (handled, commands) = script.key(&name, &state, size);
+            perform(commands);
+            Inhibit(handled)
+        }),
+    );
+
+    decorations.show();
+    scheduler.redraw_above(decorations);
+    decorations.connect_draw(clone!(@strong script, @strong state => move |area, ctx| {
+        let size = screen_size(area);
+        draw(ctx, &script.draw_overlay(&state, size));
+        Inhibit(false)
+    }));
+
+    clone!(@strong state, @strong window => move |pixels| {
+        perform(script.captured(&state, state.logical_size(pixels), screen_size(&window)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::input::Action, std::io::Write};
+
+    fn load(source: &str) -> Script {
+        let mut file = std::env::temp_dir();
+        file.push(format!("sway-boomer-script-{}.rhai", std::process::id()));
+        std::fs::File::create(&file)
+            .unwrap()
+            .write_all(source.as_bytes())
+            .unwrap();
+        let script = Script::load(&file).unwrap();
+        let _ = std::fs::remove_file(&file);
+        script
+    }
+
+    #[test]
+    fn hooks_run_commands_and_draw() {
+        let script = load(
+            r#"
+            let zooms = 0;
+            fn on_key(name) {
+                if name == "z" { run("zoom-in"); true } else { false }
+            }
+            fn on_draw_overlay() {
+                let p = to_screen(10, 20);
+                color(1, 0, 0, 0.5);
+                fill(p[0], p[1], 100, 50);
+                text(0, 16, `scale ${scale()}`);
+            }
+            "#,
+        );
+        let state = ImageState::default();
+        let size = (800.0, 600.0);
+
+        assert_eq!(
+            script.key("z", &state, size),
+            (true, vec![Command::Do(Action::ZoomIn)])
+        );
+        assert_eq!(script.key("x", &state, size), (false, vec![]));
+        assert_eq!(script.captured(&state, size, size), vec![]);
+        assert_eq!(
+            script.draw_overlay(&state, size),
+            [
+                Shape::Fill((10.0, 20.0, 100.0, 50.0), (1.0, 0.0, 0.0, 0.5)),
+                Shape::Text((0.0, 16.0), "scale 1.0".into(), (1.0, 0.0, 0.0, 0.5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_commands_fail_the_hook() {
+        let script = load(r#"fn on_key(name) { run("fly"); true }"#);
+        let state = ImageState::default();
+        assert_eq!(script.key("a", &state, (1.0, 1.0)), (false, vec![]));
+    }
+}
@@ -0,0 +1,226 @@
+//! Rubber band selection of part of the capture by dragging with Ctrl held, and copying it, or the
+//! whole view without one, to the clipboard with Ctrl and a key (C by default). Dragging with the
+//! right button zooms in on the dragged region instead.
+//!
+//! The selection is copied from the capture at its own resolution, the view as the window shows
+//! it, like [`save`](crate::save) saves it.
+
+use {
+    crate::{
+        canvas::Canvas,
+        capture::Frame,
+        clipboard,
+        config::SELECTION_COLOR,
+        input::{self, PanButton},
+        render,
+        schedule::Scheduler,
+        view::{screen_size, ImageState},
+        Error,
+    },
+    gtk::{
+        gdk::{self, ModifierType},
+        gdk_pixbuf::Pixbuf,
+        glib::clone,
+        prelude::*,
+    },
+    std::{cell::Cell, rc::Rc},
+};
+
+/// Corners of a selection, where the drag began and where it is now, in image pixels.
+type Corners = ((f64, f64), (f64, f64));
+
+/// Screen pixels a right button drag has to cover to zoom, so clicks don't.
+const MIN_ZOOM_DRAG: f64 = 8.0;
+
+/// Whole pixels (x, y, width, height) of an image of `size` covered by `corners`, or `None` if
+/// that's none at all.
+fn pixels((a, b): Corners, size: (i32, i32)) -> Option<(i32, i32, i32, i32)> {
+    let x1 = (a.0.min(b.0).floor() as i32).max(0);
+    let y1 = (a.1.min(b.1).floor() as i32).max(0);
+    let x2 = (a.0.max(b.0).ceil() as i32).min(size.0);
+    let y2 = (a.1.max(b.1).ceil() as i32).min(size.1);
+    match x2 > x1 && y2 > y1 {
+        true => Some((x1, y1, x2 - x1, y2 - y1)),
+        false => None,
+    }
+}
+
+/// What gets copied: the selected part of `frame`, or what `window` shows of it through `state`.
+fn image(
+    window: &gtk::ApplicationWindow,
+    frame: &Frame,
+    state: &ImageState,
+    selection: Option<Corners>,
+) -> Result<Pixbuf, Error> {
+    let source = frame.to_pixbuf();
+    let d = state.density.get();
+    let selection = selection.map(|(a, b)| ((a.0 * d, a.1 * d), (b.0 * d, b.1 * d)));
+    match selection.and_then(|s| pixels(s, (frame.width(), frame.height()))) {
+        Some((x, y, w, h)) => Ok(source.new_subpixbuf(x, y, w, h).ok_or(Error::Decode)?),
+        None => {
+            let surface = render::shown(window, frame, state)?;
+            gdk::pixbuf_get_from_surface(&surface, 0, 0, surface.width(), surface.height())
+                .ok_or(Error::Decode)
+        }
+    }
+}
+
+/// Put `image` on the clipboard, with GTK if `wl-copy` isn't there, in which case it only lasts
+/// until the overlay quits.
+fn copy(image: &Pixbuf) -> Result<(), Error> {
+    match clipboard::copy(&image.save_to_bufferv("png", &[])?, "image/png") {
+        Err(e @ Error::MissingTool { .. }) => {
+            tracing::warn!(error = %e, "the copied image is gone once the overlay quits");
+            gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_image(image);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Select with Ctrl and the left button on `window`, outlining the selection on `area`, and copy
+/// on Ctrl and the key with keysym `key`. The right button zooms unless it pans.
+pub fn connect(
+    window: &gtk::ApplicationWindow,
+    area: &gtk::DrawingArea,
+    canvas: &Rc<Canvas>,
+    scheduler: &Rc<Scheduler>,
+    state: &Rc<ImageState>,
+    pan_button: PanButton,
+    key: u32,
+) {
+    let selection = Rc::new(Cell::new(None::<Corners>));
+    let selecting = Rc::new(Cell::new(false));
+    let zooming = Rc::new(Cell::new(None::<Corners>));
+
+    scheduler.redraw_above(area);
+    area.connect_draw(
+        clone!(@strong state, @strong selection, @strong zooming => move |area, ctx| {
+            let size = screen_size(area);
+            let t = state.viewport(size);
+            let (r, g, b, a) = SELECTION_COLOR;
+            ctx.set_source_rgba(r, g, b, a);
+            ctx.set_line_width(1.0);
+            ctx.set_dash(&[6.0, 4.0], 0.0);
+            for (from, to) in [selection.get(), zooming.get()].into_iter().flatten() {
+                let (from, to) = (t.image_to_screen(from), t.image_to_screen(to));
+                ctx.rectangle(
+                    from.0.min(to.0).round() + 0.5,
+                    from.1.min(to.1).round() + 0.5,
+                    (to.0 - from.0).abs().round(),
+                    (to.1 - from.1).abs().round(),
+                );
+                let _ = ctx.stroke();
+            }
+            Inhibit(false)
+        }),
+    );
+
+    let to_image = clone!(@strong state => move |window: &gtk::ApplicationWindow, pos| {
+        let size = screen_size(window);
+        state.viewport(size).screen_to_image(pos)
+    });
+
+    window.connect_button_press_event(
+        clone!(@strong area, @strong scheduler, @strong state, @strong selection, @strong selecting, @strong zooming, @strong to_image => move |window, evt| {
+            if evt.button() == 3 && pan_button != PanButton::Right {
+                let pos = to_image(window, evt.position());
+                zooming.set(Some((pos, pos)));
+                area.show();
+                return Inhibit(false);
+            }
+            if evt.button() != 1 || state.drawing.get() {
+                return Inhibit(false);
+            }
+            // Any other click clears the selection
+            match evt.state().contains(ModifierType::CONTROL_MASK) {
+                true => {
+                    let pos = to_image(window, evt.position());
+                    selection.set(Some((pos, pos)));
+                    selecting.set(true);
+                    area.show();
+                }
+                false => {
+                    selection.set(None);
+                    area.hide();
+                }
+            }
+            scheduler.redraw();
+            Inhibit(false)
+        }),
+    );
+
+    window.connect_motion_notify_event(
+        clone!(@strong scheduler, @strong selection, @strong selecting, @strong zooming, @strong to_image => move |window, evt| {
+            if let (true, Some((start, _))) = (selecting.get(), selection.get()) {
+                selection.set(Some((start, to_image(window, evt.position()))));
+                scheduler.redraw();
+            }
+            if let Some((start, _)) = zooming.get() {
+                zooming.set(Some((start, to_image(window, evt.position()))));
+                scheduler.redraw();
+            }
+            Inhibit(false)
+        }),
+    );
+
+    window.connect_button_release_event(
+        clone!(@strong area, @strong scheduler, @strong state, @strong selection, @strong selecting => move |window, evt| {
+            if evt.button() == 1 {
+                selecting.set(false);
+            }
+            let (from, to) = match (evt.button(), zooming.take()) {
+                (3, Some(corners)) => corners,
+                (_, corners) => {
+                    zooming.set(corners);
+                    return Inhibit(false);
+                }
+            };
+            if selection.get().is_none() {
+                area.hide();
+            }
+            scheduler.redraw();
+            let size = screen_size(window);
+            let scale = state.viewport(size).scale;
+            let region = (from.0.min(to.0), from.1.min(to.1), (to.0 - from.0).abs(), (to.1 - from.1).abs());
+            if region.2.max(region.3) * scale >= MIN_ZOOM_DRAG {
+                input::zoom_to_region(&scheduler, &state, region, size);
+            }
+            Inhibit(false)
+        }),
+    );
+
+    window.connect_key_press_event(clone!(@strong canvas, @strong state => move |window, evt| {
+        if input::keysym(evt) != key || !evt.state().contains(ModifierType::CONTROL_MASK) {
+            return Inhibit(false);
+        }
+        let frame = match canvas.frame() {
+            Some(frame) => frame,
+            None => return Inhibit(true),
+        };
+        match image(window, &frame, &state, selection.get()).and_then(|image| copy(&image)) {
+            Ok(()) => tracing::info!(selection = ?selection.get(), "copied"),
+            Err(e) => tracing::error!(error = %e, "could not copy"),
+        }
+        Inhibit(true)
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selections_cover_whole_pixels_inside_the_image() {
+        assert_eq!(
+            pixels(((10.5, 20.2), (2.2, 4.0)), (100, 100)),
+            Some((2, 4, 9, 17))
+        );
+        assert_eq!(
+            pixels(((-5.0, 90.0), (20.0, 120.0)), (100, 100)),
+            Some((0, 90, 20, 10))
+        );
+        assert_eq!(pixels(((3.0, 3.0), (3.0, 8.0)), (100, 100)), None);
+        assert_eq!(pixels(((150.0, 0.0), (160.0, 10.0)), (100, 100)), None);
+    }
+}
@@ -0,0 +1,167 @@
+//! Frame rate, timings and memory use, shown over the view on a key (F12 by default) so
+//! performance can be reported with actual numbers.
+
+use {
+    crate::{canvas::Canvas, input},
+    gtk::{
+        glib::{self, clone, Continue},
+        prelude::*,
+    },
+    std::{
+        cell::RefCell,
+        collections::VecDeque,
+        fmt,
+        rc::Rc,
+        time::{Duration, Instant},
+    },
+};
+
+/// How often the numbers on screen are updated.
+const REFRESH: Duration = Duration::from_millis(500);
+
+/// Recently drawn frames.
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// When each frame of the last second was drawn, and how long painting it took.
+    frames: RefCell<VecDeque<(Instant, Duration)>>,
+}
+
+impl Stats {
+    /// Note a frame that just took `paint` to draw.
+    pub fn frame(&self, paint: Duration) {
+        self.record(Instant::now(), paint);
+    }
+
+    fn record(&self, at: Instant, paint: Duration) {
+        let mut frames = self.frames.borrow_mut();
+        frames.push_back((at, paint));
+        while frames
+            .front()
+            .is_some_and(|(t, _)| at.duration_since(*t) > Duration::from_secs(1))
+        {
+            frames.pop_front();
+        }
+    }
+
+    /// Frames drawn within the second before `now`.
+    fn fps(&self, now: Instant) -> usize {
+        self.frames
+            .borrow()
+            .iter()
+            .filter(|(t, _)| now.duration_since(*t) <= Duration::from_secs(1))
+            .count()
+    }
+
+    /// How long painting the last frame took.
+    fn paint(&self) -> Option<Duration> {
+        self.frames.borrow().back().map(|(_, paint)| *paint)
+    }
+
+    pub fn report(&self, renderer: &'static str, cached: usize, scale: Option<Duration>) -> Report {
+        Report {
+            renderer,
+            fps: self.fps(Instant::now()),
+            paint: self.paint(),
+            scale,
+            cached,
+        }
+    }
+}
+
+/// What is shown on screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// `gl` or `cairo`.
+    pub renderer: &'static str,
+    pub fps: usize,
+    pub paint: Option<Duration>,
+    /// How long scaling the last pre-scaled copy took.
+    pub scale: Option<Duration>,
+    /// Bytes of pixels held for drawing.
+    pub cached: usize,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Option<Duration>| match d {
+            Some(d) => format!("{:.2} ms", d.as_secs_f64() * 1000.0),
+            None => "-".into(),
+        };
+        writeln!(f, "{} fps ({})", self.fps, self.renderer)?;
+        writeln!(f, "paint {}", ms(self.paint))?;
+        writeln!(f, "scale {}", ms(self.scale))?;
+        write!(
+            f,
+            "cached {:.1} MiB",
+            self.cached as f64 / (1024.0 * 1024.0)
+        )
+    }
+}
+
+fn show(label: &gtk::Label, report: &Report) {
+    label.set_markup(&format!(
+        "<span font_family=\"monospace\" background=\"#000000c0\" foreground=\"#ffffff\">{}</span>",
+        glib::markup_escape_text(&report.to_string())
+    ));
+}
+
+/// Toggle `label` showing how `canvas` is doing on the key with keysym `key`.
+pub fn connect(window: &gtk::ApplicationWindow, label: &gtk::Label, canvas: &Rc<Canvas>, key: u32) {
+    window.connect_key_press_event(clone!(@strong label, @strong canvas => move |_, evt| {
+        if input::keysym(evt) != key {
+            return Inhibit(false);
+        }
+        if label.is_visible() {
+            label.hide();
+            return Inhibit(true);
+        }
+        show(&label, &canvas.report());
+        label.show();
+        glib::timeout_add_local(
+            REFRESH,
+            clone!(@weak label, @weak canvas => @default-return Continue(false), move || {
+                if label.is_visible() {
+                    show(&label, &canvas.report());
+                }
+                Continue(label.is_visible())
+            }),
+        );
+        Inhibit(true)
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_older_than_a_second_are_dropped() {
+        let stats = Stats::default();
+        let start = Instant::now();
+        for i in 0..90 {
+            stats.record(
+                start + Duration::from_millis(i * 1000 / 60),
+                Duration::from_micros(i),
+            );
+        }
+        let end = start + Duration::from_millis(89 * 1000 / 60);
+        assert_eq!(stats.fps(end), 61);
+        assert_eq!(stats.paint(), Some(Duration::from_micros(89)));
+        assert_eq!(stats.fps(end + Duration::from_secs(2)), 0);
+    }
+
+    #[test]
+    fn report_is_readable() {
+        let report = Report {
+            renderer: "cairo",
+            fps: 60,
+            paint: Some(Duration::from_micros(1500)),
+            scale: None,
+            cached: 3 * 1024 * 1024,
+        };
+        assert_eq!(
+            report.to_string(),
+            "60 fps (cairo)\npaint 1.50 ms\nscale -\ncached 3.0 MiB"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Client for sway's IPC socket, speaking the i3 protocol sway exports at `$SWAYSOCK`.
+//!
+//! Every message is the magic string, the payload length and the message type in native byte
+//! order, followed by a JSON payload. Events use the same framing with the high bit of the type
+//! set.
+
+use {
+    crate::Error,
+    serde::{de::DeserializeOwned, Deserialize},
+    std::{
+        env,
+        io::{self, Read, Write},
+        os::unix::net::UnixStream,
+    },
+};
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+
+/// Set in the type of messages that are events rather than replies.
+const EVENT: u32 = 1 << 31;
+
+/// Messages used here, see `sway-ipc(7)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    Subscribe = 2,
+    GetOutputs = 3,
+    GetTree = 4,
+}
+
+#[derive(Debug, Deserialize)]
+struct Success {
+    success: bool,
+}
+
+#[derive(Debug)]
+pub struct Connection {
+    stream: UnixStream,
+}
+
+impl Connection {
+    /// Connect to the sway instance named by `SWAYSOCK`.
+    pub fn connect() -> Result<Self, Error> {
+        let path = env::var_os("SWAYSOCK").ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "SWAYSOCK is not set, is sway running?",
+            )
+        })?;
+        Ok(Self {
+            stream: UnixStream::connect(path)?,
+        })
+    }
+
+    fn send(&mut self, request: Request, payload: &str) -> io::Result<()> {
+        let mut message = Vec::with_capacity(14 + payload.len());
+        message.extend_from_slice(MAGIC);
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&(request as u32).to_ne_bytes());
+        message.extend_from_slice(payload.as_bytes());
+        self.stream.write_all(&message)
+    }
+
+    /// Next message, with its type and payload.
+    fn receive(&mut self) -> io::Result<(u32, Vec<u8>)> {
+        let mut header = [0u8; 14];
+        self.stream.read_exact(&mut header)?;
+        if &header[..6] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an i3-ipc message",
+            ));
+        }
+        let len = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+        let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+        let mut payload = vec![0; len as usize];
+        self.stream.read_exact(&mut payload)?;
+        Ok((kind, payload))
+    }
+
+    /// Send `request` with `payload` and parse the reply.
+    pub fn request<T: DeserializeOwned>(
+        &mut self,
+        request: Request,
+        payload: &str,
+    ) -> Result<T, Error> {
+        self.send(request, payload)?;
+        loop {
+            let (kind, reply) = self.receive()?;
+            // Events of an earlier subscription may come in before the reply
+            if kind & EVENT == 0 {
+                return Ok(serde_json::from_slice(&reply)?);
+            }
+        }
+    }
+
+    /// Subscribe to the event types in `events`, turning the connection into the stream of them.
+    pub fn subscribe<T: DeserializeOwned>(
+        mut self,
+        events: &[&str],
+    ) -> Result<impl Iterator<Item = Result<T, Error>>, Error> {
+        let reply: Success = self.request(Request::Subscribe, &serde_json::to_string(events)?)?;
+        if !reply.success {
+            return Err(
+                io::Error::other(format!("sway refused to subscribe to {:?}", events)).into(),
+            );
+        }
+        Ok(std::iter::from_fn(move || match self.receive() {
+            Ok((_, payload)) => Some(serde_json::from_slice(&payload).map_err(Error::from)),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(e) => Some(Err(e.into())),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    /// Answer the messages read from `stream` with `replies` in turn, then send `events`.
+    fn fake_sway(mut stream: UnixStream, replies: Vec<&'static str>, events: Vec<&'static str>) {
+        let frame = |kind: u32, payload: &str| {
+            let mut message = MAGIC.to_vec();
+            message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+            message.extend_from_slice(&kind.to_ne_bytes());
+            message.extend_from_slice(payload.as_bytes());
+            message
+        };
+        for reply in replies {
+            let mut header = [0u8; 14];
+            stream.read_exact(&mut header).unwrap();
+            let len = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+            let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+            let mut payload = vec![0; len as usize];
+            stream.read_exact(&mut payload).unwrap();
+            stream.write_all(&frame(kind, reply)).unwrap();
+        }
+        for event in events {
+            stream.write_all(&frame(EVENT | 3, event)).unwrap();
+        }
+    }
+
+    #[test]
+    fn requests_and_events() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let sway = thread::spawn(move || {
+            fake_sway(
+                server,
+                vec![r#"[{"name":"DP-1"}]"#, r#"{"success":true}"#],
+                vec![r#"{"change":"focus"}"#, r#"{"change":"close"}"#],
+            )
+        });
+
+        let mut conn = Connection { stream: client };
+        let outputs: Vec<serde_json::Value> = conn.request(Request::GetOutputs, "").unwrap();
+        assert_eq!(outputs[0]["name"], "DP-1");
+
+        let events: Vec<serde_json::Value> = conn
+            .subscribe(&["window"])
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1]["change"], "close");
+        sway.join().unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+//! Make sure a panic never leaves the overlay stuck on screen.
+//!
+//! The overlay covers the whole output and grabs the keyboard, so if the process hung around in
+//! a broken state after a panic the user would have no way to get rid of it. The hook unmaps the
+//! layer surface, gives the keyboard back and aborts. Panics on other threads, such as a remote
+//! client's, only end that thread, which the overlay can do without.
+
+use {
+    gtk::{gdk, glib, prelude::*},
+    std::{cell::RefCell, sync::Once, thread},
+};
+
+static HOOK: Once = Once::new();
+
+thread_local! {
+    // Only the GTK main thread ever fills this, other threads always see it empty
+    static OVERLAYS: RefCell<Vec<glib::WeakRef<gtk::ApplicationWindow>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Install a panic hook that tears down `window`, along with the overlays of earlier calls. Must
+/// be called on the GTK main thread.
+pub fn install(window: &gtk::ApplicationWindow) {
+    OVERLAYS.with(|overlays| {
+        let mut overlays = overlays.borrow_mut();
+        // A daemon shows new overlays for as long as it runs
+        overlays.retain(|w| w.upgrade().is_some());
+        overlays.push(window.downgrade());
+    });
+    HOOK.call_once(set_hook);
+}
+
+fn set_hook() {
+    let main = thread::current().id();
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default(info);
+        if thread::current().id() != main {
+            return;
+        }
+
+        let windows: Vec<_> = OVERLAYS
+            .try_with(|overlays| {
+                overlays
+                    .try_borrow()
+                    .map(|w| w.iter().filter_map(|w| w.upgrade()).collect())
+                    .unwrap_or_default()
+            })
+            .unwrap_or_default();
+        for window in &windows {
+            gtk_layer_shell::set_keyboard_interactivity(window, false);
+            unsafe { window.destroy() };
+        }
+        // Push the requests out before the connection goes away with the process
+        if let (false, Some(display)) = (windows.is_empty(), gdk::Display::default()) {
+            display.flush();
+        }
+
+        // GTK state can't be trusted after a panic, and unwinding through its callbacks is
+        // undefined anyway
+        std::process::abort();
+    }));
+}
@@ -0,0 +1,293 @@
+//! Where windows are, from sway's layout tree.
+
+use {
+    crate::{
+        capture,
+        sway::{Connection, Request},
+        Error,
+    },
+    gtk::glib::{self, Continue},
+    serde::Deserialize,
+    std::thread,
+};
+
+/// Rectangle in sway's logical coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn center(&self) -> (f64, f64) {
+        (
+            self.x as f64 + self.width as f64 / 2.0,
+            self.y as f64 + self.height as f64 / 2.0,
+        )
+    }
+
+    pub(crate) fn contains(&self, (x, y): (f64, f64)) -> bool {
+        (self.x as f64..(self.x + self.width) as f64).contains(&x)
+            && (self.y as f64..(self.y + self.height) as f64).contains(&y)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Node {
+    #[serde(default)]
+    id: i64,
+    #[serde(rename = "type")]
+    kind: String,
+    name: Option<String>,
+    #[serde(default)]
+    focused: bool,
+    rect: Rect,
+    visible: Option<bool>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    floating_nodes: Vec<Node>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WindowEvent {
+    change: String,
+    container: Node,
+}
+
+impl WindowEvent {
+    /// Where the window that just got focus is in the layout, if this is a focus change.
+    fn focused(&self) -> Option<Rect> {
+        (self.change == "focus").then_some(self.container.rect)
+    }
+
+    /// Where the window that just got focus is relative to the output at `output`, if this is
+    /// a focus change on that output.
+    fn focused_on(&self, output: &Rect) -> Option<Rect> {
+        let rect = self.focused()?;
+        match output.contains(rect.center()) {
+            true => Some(Rect {
+                x: rect.x - output.x,
+                y: rect.y - output.y,
+                ..rect
+            }),
+            false => None,
+        }
+    }
+}
+
+impl Node {
+    fn children(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.iter().chain(&self.floating_nodes)
+    }
+
+    /// This node or the first one below it `pred` holds for.
+    fn find(&self, pred: &impl Fn(&Node) -> bool) -> Option<&Node> {
+        match pred(self) {
+            true => Some(self),
+            false => self.children().find_map(|child| child.find(pred)),
+        }
+    }
+
+    /// Visible windows below this node, in the same coordinates as the tree.
+    fn windows(&self, out: &mut Vec<Rect>) {
+        let is_window = matches!(self.kind.as_str(), "con" | "floating_con")
+            && self.nodes.is_empty()
+            && self.visible == Some(true);
+        if is_window {
+            out.push(self.rect);
+        }
+        self.children().for_each(|child| child.windows(out));
+    }
+}
+
+/// Visible windows on the output called `output`, relative to its top left corner.
+fn windows_on(root: &Node, output: &str) -> Vec<Rect> {
+    let output = match root
+        .children()
+        .find(|n| n.kind == "output" && n.name.as_deref() == Some(output))
+    {
+        Some(output) => output,
+        None => return Vec::new(),
+    };
+    let mut windows = Vec::new();
+    output.windows(&mut windows);
+    windows
+        .into_iter()
+        .map(|r| Rect {
+            x: r.x - output.rect.x,
+            y: r.y - output.rect.y,
+            ..r
+        })
+        .collect()
+}
+
+/// Visible windows on the sway output called `output`, relative to its top left corner.
+#[tracing::instrument]
+pub fn windows(output: &str) -> Result<Vec<Rect>, Error> {
+    let root: Node = Connection::connect()?.request(Request::GetTree, "")?;
+    let windows = windows_on(&root, output);
+    tracing::debug!(count = windows.len(), "visible windows");
+    Ok(windows)
+}
+
+/// Where the container with the id `id`, or the focused one, is in the layout.
+#[tracing::instrument]
+pub fn container_rect(id: Option<i64>) -> Result<Rect, Error> {
+    let root: Node = Connection::connect()?.request(Request::GetTree, "")?;
+    container_in(&root, id)
+}
+
+fn container_in(root: &Node, id: Option<i64>) -> Result<Rect, Error> {
+    let node = match id {
+        Some(id) => root
+            .find(&|n| n.id == id)
+            .ok_or(Error::UnknownContainer(id))?,
+        None => root.find(&|n| n.focused).ok_or(Error::NoOutput)?,
+    };
+    Ok(node.rect)
+}
+
+/// Call `on_focus` on the main loop with where a window is, relative to the sway output called
+/// `output`, whenever a window on it gets focus. Without an output that's any window, and where
+/// it is in the layout.
+pub fn follow_focus<F: Fn(Rect) + 'static>(output: Option<String>, on_focus: F) {
+    let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    thread::spawn(move || {
+        if let Err(e) = listen(output.as_deref(), |rect| tx.send(rect).is_ok()) {
+            tracing::warn!(error = %e, "not following window focus");
+        }
+    });
+    rx.attach(None, move |rect| {
+        on_focus(rect);
+        Continue(true)
+    });
+}
+
+/// Pass every focus change on `output`, or on any output, to `send` until it returns `false`.
+fn listen(output: Option<&str>, send: impl Fn(Rect) -> bool) -> Result<(), Error> {
+    let origin = output.map(capture::output_rect).transpose()?;
+    for event in Connection::connect()?.subscribe::<WindowEvent>(&["window"])? {
+        let event = event?;
+        let focused = match &origin {
+            Some(origin) => event.focused_on(origin),
+            None => event.focused(),
+        };
+        if let Some(rect) = focused {
+            tracing::debug!(?rect, "window focused");
+            if !send(rect) {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The window among `windows` at `point`, the smallest one where windows overlap.
+pub fn window_at(windows: &[Rect], point: (f64, f64)) -> Option<Rect> {
+    windows
+        .iter()
+        .filter(|w| w.contains(point))
+        .min_by_key(|w| w.width as i64 * w.height as i64)
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TREE: &str = r#"{
+        "id": 1, "type": "root", "name": "root", "rect": {"x": 0, "y": 0, "width": 3840, "height": 1080},
+        "nodes": [
+            {"type": "output", "name": "DP-1", "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+             "nodes": [{"type": "workspace", "name": "1", "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+                        "nodes": [{"type": "con", "name": "left", "visible": true,
+                                   "rect": {"x": 0, "y": 0, "width": 960, "height": 1080}}]}]},
+            {"type": "output", "name": "HDMI-A-1", "rect": {"x": 1920, "y": 0, "width": 1920, "height": 1080},
+             "nodes": [{"type": "workspace", "name": "2", "rect": {"x": 1920, "y": 0, "width": 1920, "height": 1080},
+                        "nodes": [
+                            {"type": "con", "name": null, "rect": {"x": 1920, "y": 0, "width": 1920, "height": 1080},
+                             "nodes": [
+                                 {"id": 7, "type": "con", "name": "a", "visible": true, "focused": true,
+                                  "rect": {"x": 1920, "y": 0, "width": 1920, "height": 540}},
+                                 {"type": "con", "name": "b", "visible": false,
+                                  "rect": {"x": 1920, "y": 540, "width": 1920, "height": 540}}]}],
+                        "floating_nodes": [{"id": 9, "type": "floating_con", "name": "f", "visible": true,
+                                            "rect": {"x": 2000, "y": 100, "width": 300, "height": 200}}]}]}
+        ]
+    }"#;
+
+    #[test]
+    fn visible_windows_relative_to_output() {
+        let root: Node = serde_json::from_str(TREE).unwrap();
+        assert_eq!(
+            windows_on(&root, "HDMI-A-1"),
+            [
+                Rect {
+                    x: 0,
+                    y: 0,
+                    width: 1920,
+                    height: 540
+                },
+                Rect {
+                    x: 80,
+                    y: 100,
+                    width: 300,
+                    height: 200
+                },
+            ]
+        );
+        assert!(windows_on(&root, "eDP-1").is_empty());
+    }
+
+    #[test]
+    fn focus_events_on_other_outputs_are_ignored() {
+        let event = |change, x| -> WindowEvent {
+            serde_json::from_str(&format!(
+                r#"{{"change": "{}", "container": {{"type": "con", "name": "w", "visible": true,
+                    "rect": {{"x": {}, "y": 10, "width": 400, "height": 300}}}}}}"#,
+                change, x
+            ))
+            .unwrap()
+        };
+        let output = Rect {
+            x: 1920,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        };
+        assert_eq!(
+            event("focus", 2020).focused_on(&output),
+            Some(Rect {
+                x: 100,
+                y: 10,
+                width: 400,
+                height: 300
+            })
+        );
+        assert_eq!(event("focus", 100).focused_on(&output), None);
+        assert_eq!(event("title", 2020).focused_on(&output), None);
+    }
+
+    #[test]
+    fn containers_are_found_by_id_or_focus() {
+        let root: Node = serde_json::from_str(TREE).unwrap();
+        assert_eq!(container_in(&root, Some(9)).unwrap().x, 2000);
+        assert_eq!(container_in(&root, None).unwrap().height, 540);
+        assert!(matches!(
+            container_in(&root, Some(3)),
+            Err(Error::UnknownContainer(3))
+        ));
+    }
+
+    #[test]
+    fn floating_window_wins_over_the_one_behind() {
+        let root: Node = serde_json::from_str(TREE).unwrap();
+        let windows = windows_on(&root, "HDMI-A-1");
+        assert_eq!(window_at(&windows, (100.0, 150.0)).map(|w| w.x), Some(80));
+        assert_eq!(window_at(&windows, (1000.0, 150.0)).map(|w| w.x), Some(0));
+        assert_eq!(window_at(&windows, (1000.0, 800.0)), None);
+    }
+}
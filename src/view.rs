@@ -0,0 +1,1074 @@
+use {
+    crate::{
+        annotate::Annotations,
+        config::{
+            ViewConfig, DOCK_HEIGHT, FLING_FRICTION, FRAME_MARGIN, HIGHLIGHT_RADIUS_MAX,
+            HIGHLIGHT_RADIUS_MIN, LENS_ASPECT, NEAREST_FROM_SCALE, SCALE_MIN, SNAP_DISTANCE,
+        },
+        filter::Filters,
+        tree::{self, Rect},
+    },
+    clap::ValueEnum,
+    gtk::prelude::*,
+    serde::Deserialize,
+    std::cell::{Cell, RefCell},
+};
+
+/// Part of the output the overlay covers, leaving the rest of the screen usable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Geometry {
+    LeftHalf,
+    RightHalf,
+    TopHalf,
+    BottomHalf,
+    /// x, y, width and height in logical pixels.
+    Rect(i32, i32, i32, i32),
+}
+
+impl Geometry {
+    /// Position and size as (x, y, width, height) on an output of `size`.
+    pub fn resolve(self, size: (i32, i32)) -> (i32, i32, i32, i32) {
+        let (w, h) = size;
+        match self {
+            Self::LeftHalf => (0, 0, w / 2, h),
+            Self::RightHalf => (w - w / 2, 0, w / 2, h),
+            Self::TopHalf => (0, 0, w, h / 2),
+            Self::BottomHalf => (0, h - h / 2, w, h / 2),
+            Self::Rect(x, y, rw, rh) => {
+                let (x, y) = (x.clamp(0, w - 1), y.clamp(0, h - 1));
+                (x, y, rw.min(w - x), rh.min(h - y))
+            }
+        }
+    }
+}
+
+/// How the capture is scaled between its pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    /// Sharp squares, for looking at single pixels.
+    Nearest,
+    /// Blended, smoother for showing things to others.
+    Bilinear,
+    /// Bilinear while zoomed in less than [`NEAREST_FROM_SCALE`], nearest from there on.
+    Auto,
+}
+
+impl Interpolation {
+    /// Whether pixels are blended when shown at `scale`.
+    pub fn smooth(self, scale: f64) -> bool {
+        match self {
+            Self::Nearest => false,
+            Self::Bilinear => true,
+            Self::Auto => scale < NEAREST_FROM_SCALE,
+        }
+    }
+
+    /// The mode after this one.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Nearest => Self::Bilinear,
+            Self::Bilinear => Self::Auto,
+            Self::Auto => Self::Nearest,
+        }
+    }
+}
+
+/// Screen edge the magnified band sits at in docked mode.
+///
+/// The pointer can only be tracked over our own surface, so rather than a separate strip window
+/// the overlay shows the capture unmagnified and the band on top of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Dock {
+    Top,
+    Bottom,
+}
+
+impl Dock {
+    /// Top and height of the band on a screen `height` pixels tall.
+    pub fn band(self, height: f64) -> (f64, f64) {
+        let band = (height * DOCK_HEIGHT).round();
+        match self {
+            Self::Top => (0.0, band),
+            Self::Bottom => (height - band, band),
+        }
+    }
+}
+
+/// Shape of the magnifying lens that follows the pointer, leaving the rest of the view as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Lens {
+    Circle,
+    /// Wider than tall, for reading along a line.
+    Rectangle,
+}
+
+impl Lens {
+    /// Half the width and height of a lens of `radius`.
+    pub fn half_size(self, radius: f64) -> (f64, f64) {
+        match self {
+            Self::Circle => (radius, radius),
+            Self::Rectangle => (radius * LENS_ASPECT, radius),
+        }
+    }
+
+    /// The shape after `lens`, or none after the last.
+    pub fn next(lens: Option<Self>) -> Option<Self> {
+        match lens {
+            None => Some(Self::Circle),
+            Some(Self::Circle) => Some(Self::Rectangle),
+            Some(Self::Rectangle) => None,
+        }
+    }
+}
+
+/// Window edges the view snaps to while panning, in image pixels.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapEdges {
+    xs: Vec<f64>,
+    ys: Vec<f64>,
+}
+
+impl SnapEdges {
+    /// Edges of `windows`, each given as (x, y, width, height).
+    pub fn new(windows: impl IntoIterator<Item = (f64, f64, f64, f64)>) -> Self {
+        let mut edges = Self::default();
+        for (x, y, w, h) in windows {
+            edges.xs.extend([x, x + w]);
+            edges.ys.extend([y, y + h]);
+        }
+        edges
+    }
+
+    /// Screen pixels to add to the offset of `t` so that the screen edges line up with the
+    /// nearest window edges, if those are less than `distance` screen pixels away.
+    pub fn correction(&self, t: &Viewport, distance: f64) -> (f64, f64) {
+        let nearest = |edges: &[f64], size: f64, axis: fn((f64, f64)) -> f64| {
+            edges
+                .iter()
+                .map(|&e| axis(t.image_to_screen((e, e))))
+                .flat_map(|e| [e, e - size])
+                .filter(|d| d.abs() < distance)
+                .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+                .unwrap_or(0.0)
+        };
+        (
+            nearest(&self.xs, t.widget_size.0, |p| p.0),
+            nearest(&self.ys, t.widget_size.1, |p| p.1),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageState {
+    /// Scale in logical pixels: at 1 the view is the desktop as it was, pixel for pixel.
+    pub scale: Cell<f64>,
+    /// Capture pixels per logical pixel, the scale of the output the capture is of.
+    pub density: Cell<f64>,
+    /// Size of the capture in its own pixels, once there is one.
+    pub pixels: Cell<Option<(i32, i32)>>,
+    pub offset: Cell<(f64, f64)>,
+    pub mouse_pos: Cell<(f64, f64)>,
+    pub highlight: Cell<bool>,
+    /// Scale change of one zoom step, relative to the scale.
+    pub zoom_step: Cell<f64>,
+    pub min_scale: Cell<f64>,
+    pub max_scale: Cell<f64>,
+    /// Shown where there is no image.
+    pub background: Cell<(f64, f64, f64)>,
+    pub highlight_color: Cell<(f64, f64, f64, f64)>,
+    /// Radius of the highlight in logical pixels.
+    pub highlight_radius: Cell<f64>,
+    /// How much darker than the rest the highlight leaves the screen outside of it, if it's drawn
+    /// that way instead of as a tinted circle.
+    pub dim_outside: Cell<Option<f64>>,
+    /// Seconds the highlight takes to catch up with most of its way to the pointer, `None` to
+    /// keep it on the pointer.
+    pub highlight_lag: Cell<Option<f64>>,
+    /// Where the lagging highlight currently is on screen.
+    pub spotlight: Cell<Option<(f64, f64)>>,
+    pub interpolation: Cell<Interpolation>,
+    pub filters: Cell<Filters>,
+    /// Whether [`crate::config::HIGH_CONTRAST`] is applied.
+    pub high_contrast: Cell<bool>,
+    /// Height of the enlarged cursor drawn at `mouse_pos`, if any.
+    pub cursor_size: Cell<Option<f64>>,
+    /// Keep what's under the real pointer in the middle of the view, see [`Self::follow`].
+    pub follow_mouse: Cell<bool>,
+    /// Show the magnified view in a band along this edge only.
+    pub dock: Cell<Option<Dock>>,
+    /// Magnify around the pointer through a lens of this shape only.
+    pub lens: Cell<Option<Lens>>,
+    /// How much the lens magnifies on top of `scale`.
+    pub lens_scale: Cell<f64>,
+    pub lens_radius: Cell<f64>,
+    /// Region of the image (x, y, width, height) the view is locked onto, overriding scale
+    /// and offset until it's unlocked.
+    pub lock: Cell<Option<(f64, f64, f64, f64)>>,
+    /// Window edges panning snaps to, if enabled.
+    pub snap_edges: RefCell<Option<SnapEdges>>,
+    /// Windows in the capture, in image pixels.
+    pub windows: RefCell<Vec<Rect>>,
+    /// Whether dragging with the left button draws annotations instead of panning.
+    pub drawing: Cell<bool>,
+    pub annotations: RefCell<Annotations>,
+}
+
+impl Default for ImageState {
+    fn default() -> Self {
+        let config = ViewConfig::default();
+        Self {
+            scale: Cell::new(1f64),
+            density: Cell::new(1f64),
+            pixels: Cell::new(None),
+            offset: Cell::new((0f64, 0f64)),
+            mouse_pos: Cell::new((0f64, 0f64)),
+            highlight: Cell::new(false),
+            zoom_step: Cell::new(config.zoom_step),
+            min_scale: Cell::new(config.min_scale),
+            max_scale: Cell::new(config.max_scale),
+            background: Cell::new(config.background),
+            highlight_color: Cell::new(config.highlight_color),
+            highlight_radius: Cell::new(config.highlight_radius),
+            dim_outside: Cell::new(config.dim_outside),
+            highlight_lag: Cell::new(None),
+            spotlight: Cell::new(None),
+            interpolation: Cell::new(config.interpolation),
+            filters: Cell::new(Filters {
+                simulate: None,
+                invert: false,
+                grayscale: false,
+                adjust: false,
+                brightness: config.brightness,
+                contrast: config.contrast,
+            }),
+            high_contrast: Cell::new(false),
+            cursor_size: Cell::new(None),
+            follow_mouse: Cell::new(false),
+            dock: Cell::new(None),
+            lens: Cell::new(None),
+            lens_scale: Cell::new(config.lens_scale),
+            lens_radius: Cell::new(config.lens_radius),
+            lock: Cell::new(None),
+            snap_edges: RefCell::new(None),
+            windows: RefCell::new(Vec::new()),
+            drawing: Cell::new(false),
+            annotations: RefCell::new(Annotations::default()),
+        }
+    }
+}
+
+impl ImageState {
+    /// Take zoom steps and colors from `config`.
+    pub fn configure(&self, config: &ViewConfig) {
+        self.zoom_step.set(config.zoom_step.max(0.01));
+        self.min_scale.set(config.min_scale.max(0.01));
+        self.max_scale
+            .set(config.max_scale.max(self.min_scale.get()));
+        self.background.set(config.background);
+        self.highlight_color.set(config.highlight_color);
+        self.highlight_radius.set(config.highlight_radius);
+        self.dim_outside
+            .set(config.dim_outside.map(|dim| dim.clamp(0.0, 1.0)));
+        self.interpolation.set(config.interpolation);
+        self.filters.set(Filters {
+            brightness: config.brightness,
+            contrast: config.contrast,
+            ..self.filters.get()
+        });
+        self.set_lens_scale(config.lens_scale);
+        self.lens_radius.set(config.lens_radius.max(1.0));
+    }
+
+    /// Size in logical pixels of a capture of `pixels`, which is what all other sizes and
+    /// positions in the image are in.
+    pub fn logical_size(&self, pixels: (i32, i32)) -> (f64, f64) {
+        let density = self.density.get();
+        (pixels.0 as f64 / density, pixels.1 as f64 / density)
+    }
+
+    /// Size of the capture in logical pixels, or that of `widget_size` until there is a capture.
+    pub fn source_size(&self, widget_size: (f64, f64)) -> (f64, f64) {
+        match self.pixels.get() {
+            Some(pixels) => self.logical_size(pixels),
+            None => widget_size,
+        }
+    }
+
+    /// The whole capture as a region in logical pixels, for fitting all of it on a screen of
+    /// `widget_size`.
+    pub fn whole_capture(&self, widget_size: (f64, f64)) -> (f64, f64, f64, f64) {
+        let (w, h) = self.source_size(widget_size);
+        (0.0, 0.0, w, h)
+    }
+
+    /// Set the scale, kept within the configured range.
+    pub fn set_scale(&self, scale: f64) {
+        self.scale
+            .set(scale.clamp(self.min_scale.get(), self.max_scale.get()));
+    }
+
+    /// How much a zoom step of `delta` times the current scale changes the scale by, so that
+    /// every step looks alike at any scale. Zooming out divides by what zooming in multiplies
+    /// with, so a step in and one out cancel.
+    pub fn relative_step(&self, delta: f64) -> f64 {
+        let scale = self.scale.get();
+        match delta >= 0.0 {
+            true => scale * delta,
+            false => scale / (1.0 - delta) - scale,
+        }
+    }
+
+    /// Set how much the lens magnifies, from not at all up to the highest scale.
+    pub fn set_lens_scale(&self, scale: f64) {
+        self.lens_scale
+            .set(scale.clamp(1.0, self.max_scale.get().max(1.0)));
+    }
+
+    pub fn zoom_in(&self) {
+        self.set_scale(self.scale.get() + self.relative_step(self.zoom_step.get()));
+    }
+
+    pub fn zoom_out(&self) {
+        self.set_scale(self.scale.get() + self.relative_step(-self.zoom_step.get()));
+    }
+
+    /// Grow the highlight by `delta` times its radius, or shrink it for a negative `delta`.
+    pub fn resize_highlight(&self, delta: f64) {
+        let radius = self.highlight_radius.get() * (1.0 + delta);
+        self.highlight_radius
+            .set(radius.clamp(HIGHLIGHT_RADIUS_MIN, HIGHLIGHT_RADIUS_MAX));
+    }
+
+    /// Move the view by a pointer drag from `from` to `to`.
+    pub fn drag(&self, from: (f64, f64), to: (f64, f64)) {
+        let (xoff, yoff) = self.offset.get();
+        self.offset
+            .set((xoff + from.0 - to.0, yoff + from.1 - to.1));
+    }
+
+    /// Keep the view moving for `dt` seconds after a fling at `velocity`, in screen pixels per
+    /// second the way the pointer went, returning the velocity left after friction.
+    pub fn glide(&self, velocity: (f64, f64), dt: f64) -> (f64, f64) {
+        let decay = (-FLING_FRICTION * dt).exp();
+        // Distance covered while slowing down exponentially
+        let k = (1.0 - decay) / FLING_FRICTION;
+        self.drag((0.0, 0.0), (velocity.0 * k, velocity.1 * k));
+        (velocity.0 * decay, velocity.1 * decay)
+    }
+
+    /// Nudge the view on a screen of `widget_size` onto nearby window edges, if snapping.
+    pub fn snap(&self, widget_size: (f64, f64)) {
+        if let Some(edges) = self.snap_edges.borrow().as_ref() {
+            let (dx, dy) = edges.correction(&self.viewport(widget_size), SNAP_DISTANCE);
+            let (xoff, yoff) = self.offset.get();
+            self.offset.set((xoff + dx, yoff + dy));
+        }
+    }
+
+    /// Zoom and pan so that `region` (x, y, width, height) of the image fills as much of a
+    /// screen of `widget_size` as the zoom range allows, with a margin around it.
+    pub fn frame(&self, region: (f64, f64, f64, f64), widget_size: (f64, f64)) {
+        let (x, y, w, h) = region;
+        let m = FRAME_MARGIN;
+        self.show_region((x - m, y - m, w + 2.0 * m, h + 2.0 * m), None, widget_size);
+    }
+
+    /// Center `region` (x, y, width, height) of the image on a screen of `widget_size` at `scale`,
+    /// or as large as it fits and the zoom range allows.
+    pub fn show_region(
+        &self,
+        region: (f64, f64, f64, f64),
+        scale: Option<f64>,
+        widget_size: (f64, f64),
+    ) {
+        let (x, y, w, h) = region;
+        let scale = scale.unwrap_or_else(|| {
+            Viewport::fit(region, self.source_size(widget_size), widget_size).scale
+        });
+        self.set_scale(scale);
+        let center = (widget_size.0 / 2.0, widget_size.1 / 2.0);
+        self.center_on((x + w / 2.0, y + h / 2.0), center, widget_size);
+    }
+
+    /// Frame the window under the pointer on a screen of `widget_size`. Returns `false` if there is
+    /// none.
+    pub fn frame_window_at_pointer(&self, widget_size: (f64, f64)) -> bool {
+        let point = self
+            .viewport(widget_size)
+            .screen_to_image(self.pointer(widget_size));
+        let window = tree::window_at(&self.windows.borrow(), point);
+        match window {
+            Some(w) => {
+                let region = (w.x as f64, w.y as f64, w.width as f64, w.height as f64);
+                self.frame(region, widget_size);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Change the scale by `delta`, keeping what shows at `screen` in place on a screen of
+    /// `widget_size`.
+    pub fn zoom_at(&self, delta: f64, screen: (f64, f64), widget_size: (f64, f64)) {
+        let before = self.viewport(widget_size);
+        self.set_scale(self.scale.get() + delta);
+        // A locked view stays where the lock has it
+        if self.lock.get().is_none() {
+            let after = before.zoom_about_point(self.scale.get(), screen);
+            self.offset.set(after.offset);
+        }
+    }
+
+    /// Pan so that `point` of the image shows at `screen` on a screen of `widget_size`.
+    pub fn center_on(&self, point: (f64, f64), screen: (f64, f64), widget_size: (f64, f64)) {
+        let t = self.viewport(widget_size);
+        let (x, y) = t.image_to_screen(point);
+        let (xoff, yoff) = self.offset.get();
+        self.offset.set((xoff + x - screen.0, yoff + y - screen.1));
+    }
+
+    /// In follow-mouse mode, pan to what's under `mouse_pos` at scale 1 on a screen of
+    /// `widget_size`.
+    pub fn follow(&self, widget_size: (f64, f64)) {
+        if self.follow_mouse.get() && self.lock.get().is_none() {
+            let center = (widget_size.0 / 2.0, widget_size.1 / 2.0);
+            self.center_on(self.mouse_pos.get(), center, widget_size);
+        }
+    }
+
+    /// Where the pointer is drawn: on what it points at, which in follow-mouse mode is the
+    /// middle of the view rather than the real position.
+    pub fn pointer(&self, widget_size: (f64, f64)) -> (f64, f64) {
+        match self.follow_mouse.get() {
+            true => self
+                .viewport(widget_size)
+                .image_to_screen(self.mouse_pos.get()),
+            false => self.mouse_pos.get(),
+        }
+    }
+
+    /// Where the highlight is drawn: trailing behind the pointer with a lag, on it otherwise.
+    pub fn highlight_pos(&self, widget_size: (f64, f64)) -> (f64, f64) {
+        match self.highlight_lag.get() {
+            Some(_) => self
+                .spotlight
+                .get()
+                .unwrap_or_else(|| self.pointer(widget_size)),
+            None => self.pointer(widget_size),
+        }
+    }
+
+    /// Move the lagging highlight `dt` seconds closer to the pointer on a screen of `widget_size`.
+    /// Returns `false` once it has caught up.
+    pub fn ease_spotlight(&self, dt: f64, widget_size: (f64, f64)) -> bool {
+        let target = self.pointer(widget_size);
+        let (lag, (x, y)) = match (self.highlight_lag.get(), self.spotlight.get()) {
+            (Some(lag), Some(pos)) => (lag, pos),
+            _ => {
+                self.spotlight.set(Some(target));
+                return false;
+            }
+        };
+        let k = 1.0 - (-dt / lag).exp();
+        let pos = (x + (target.0 - x) * k, y + (target.1 - y) * k);
+        let done = (pos.0 - target.0).hypot(pos.1 - target.1) < 0.5;
+        self.spotlight.set(Some(if done { target } else { pos }));
+        !done
+    }
+
+    /// Viewport showing the image as it is around the docked band on a screen of `widget_size`.
+    pub fn unscaled(&self, widget_size: (f64, f64)) -> Viewport {
+        Viewport {
+            scale: 1.0,
+            offset: (0.0, 0.0),
+            source_size: self.source_size(widget_size),
+            widget_size,
+        }
+    }
+
+    /// Viewport of the docked `band` (top and height) on a screen of `widget_size`: magnified
+    /// around the pointer, which shows at its own horizontal position in the middle of the band.
+    pub fn dock_viewport(&self, widget_size: (f64, f64), band: (f64, f64)) -> Viewport {
+        let s = self.scale.get();
+        let (mx, my) = self.mouse_pos.get();
+        let origin = (mx - mx * s, band.0 + band.1 / 2.0 - my * s);
+        Viewport::with_origin(s, origin, self.source_size(widget_size), widget_size)
+    }
+
+    /// Viewport of the lens: the view, or the screen as it is around a docked band, magnified by
+    /// `lens_scale` around the pointer, which stays on what it points at.
+    pub fn lens_viewport(&self, widget_size: (f64, f64)) -> Viewport {
+        let view = match self.dock.get() {
+            Some(_) => self.unscaled(widget_size),
+            None => self.viewport(widget_size),
+        };
+        let (px, py) = self.pointer(widget_size);
+        let (x, y) = view.screen_to_image((px, py));
+        let s = view.scale * self.lens_scale.get();
+        let origin = (px - x * s, py - y * s);
+        Viewport::with_origin(s, origin, view.source_size, widget_size)
+    }
+
+    /// Lock the view onto what's currently shown on a screen of `widget_size`, or unlock it.
+    pub fn toggle_lock(&self, widget_size: (f64, f64)) {
+        let lock = match self.lock.get() {
+            Some(_) => None,
+            None => {
+                let t = self.viewport(widget_size);
+                let (x, y) = t.screen_to_image((0.0, 0.0));
+                Some((x, y, widget_size.0 / t.scale, widget_size.1 / t.scale))
+            }
+        };
+        self.lock.set(lock);
+    }
+
+    /// Snapshot of the current scale and offset on a screen of `widget_size`, or the viewport
+    /// fitting the locked region on it.
+    pub fn viewport(&self, widget_size: (f64, f64)) -> Viewport {
+        let source_size = self.source_size(widget_size);
+        match self.lock.get() {
+            Some(region) => Viewport::fit(region, source_size, widget_size),
+            None => Viewport {
+                scale: self.scale.get(),
+                offset: self.offset.get(),
+                source_size,
+                widget_size,
+            },
+        }
+    }
+}
+
+/// Size of `widget` in logical pixels, the screen the view maps the capture onto. Captures are
+/// measured in logical pixels too, so at scale 1 a capture of the output fills it exactly.
+pub fn screen_size(widget: &impl IsA<gtk::Widget>) -> (f64, f64) {
+    (
+        widget.allocated_width() as f64,
+        widget.allocated_height() as f64,
+    )
+}
+
+/// How the image shows in a widget: the mapping between source image pixels and screen
+/// coordinates, and zooming around a point of the screen.
+///
+/// The image is centered in the widget, scaled around its center and then shifted by `offset`,
+/// so at scale 1 with no offset an image the size of the widget covers it pixel for pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub scale: f64,
+    /// Translation from the centered image, in screen pixels.
+    pub offset: (f64, f64),
+    /// Size of the image in logical pixels.
+    pub source_size: (f64, f64),
+    /// Size of the screen the image is shown on, in logical pixels like the image.
+    pub widget_size: (f64, f64),
+}
+
+impl Viewport {
+    /// Show an image of `source_size` at `scale` on a screen of `widget_size`, with its top-left
+    /// corner at `origin`.
+    pub fn with_origin(
+        scale: f64,
+        origin: (f64, f64),
+        source_size: (f64, f64),
+        widget_size: (f64, f64),
+    ) -> Self {
+        let (w, h) = source_size;
+        let (vw, vh) = widget_size;
+        Self {
+            scale,
+            offset: (
+                (vw - w * scale) / 2.0 - origin.0,
+                (vh - h * scale) / 2.0 - origin.1,
+            ),
+            source_size,
+            widget_size,
+        }
+    }
+
+    /// Show `region` (x, y, width, height) of an image of `source_size` as large as it fits,
+    /// centered on a screen of `widget_size`.
+    pub fn fit(
+        region: (f64, f64, f64, f64),
+        source_size: (f64, f64),
+        widget_size: (f64, f64),
+    ) -> Self {
+        let (x, y, w, h) = region;
+        let (vw, vh) = widget_size;
+        let scale = (vw / w).min(vh / h).max(SCALE_MIN);
+        let origin = (
+            vw / 2.0 - (x + w / 2.0) * scale,
+            vh / 2.0 - (y + h / 2.0) * scale,
+        );
+        Self::with_origin(scale, origin, source_size, widget_size)
+    }
+
+    /// Screen position of the top-left corner of the scaled image.
+    pub fn origin(&self) -> (f64, f64) {
+        let (width, height) = self.source_size;
+        let (vw, vh) = self.widget_size;
+        (
+            (vw - width * self.scale) / 2.0 - self.offset.0,
+            (vh - height * self.scale) / 2.0 - self.offset.1,
+        )
+    }
+
+    pub fn image_to_screen(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (ox, oy) = self.origin();
+        (ox + x * self.scale, oy + y * self.scale)
+    }
+
+    pub fn screen_to_image(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (ox, oy) = self.origin();
+        ((x - ox) / self.scale, (y - oy) / self.scale)
+    }
+
+    /// The same view at `scale`, with what shows at `screen` still there.
+    pub fn zoom_about_point(&self, scale: f64, screen: (f64, f64)) -> Self {
+        let point = self.screen_to_image(screen);
+        let zoomed = Self { scale, ..*self };
+        let (x, y) = zoomed.image_to_screen(point);
+        Self {
+            offset: (self.offset.0 + x - screen.0, self.offset.1 + y - screen.1),
+            ..zoomed
+        }
+    }
+
+    /// Part of the source shown in the screen rectangle from `top_left` to `bottom_right`, as the
+    /// image coordinates of its corners. `None` if the image is entirely out of view.
+    pub fn visible_region(
+        &self,
+        top_left: (f64, f64),
+        bottom_right: (f64, f64),
+    ) -> Option<((f64, f64), (f64, f64))> {
+        let (w, h) = self.source_size;
+        let (x1, y1) = self.screen_to_image(top_left);
+        let (x2, y2) = self.screen_to_image(bottom_right);
+        let (x1, y1) = (x1.max(0.0), y1.max(0.0));
+        let (x2, y2) = (x2.min(w), y2.min(h));
+        match x1 < x2 && y1 < y2 {
+            true => Some(((x1, y1), (x2, y2))),
+            false => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::config::{SCALE_MAX, SCALE_MIN},
+        proptest::prelude::*,
+    };
+
+    fn assert_close(a: (f64, f64), b: (f64, f64)) {
+        let eps = 1e-6 * (1.0 + a.0.abs().max(a.1.abs()));
+        assert!(
+            (a.0 - b.0).abs() < eps && (a.1 - b.1).abs() < eps,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn identity_at_scale_one() {
+        let t = ImageState::default().viewport((1920.0, 1080.0));
+        assert_eq!(t.origin(), (0.0, 0.0));
+        assert_eq!(t.image_to_screen((10.0, 20.0)), (10.0, 20.0));
+    }
+
+    #[test]
+    fn non_square_image_zooms_around_center() {
+        let t = Viewport {
+            scale: 2.0,
+            offset: (0.0, 0.0),
+            source_size: (1920.0, 1080.0),
+            widget_size: (1920.0, 1080.0),
+        };
+        assert_eq!(t.origin(), (-960.0, -540.0));
+        assert_eq!(t.image_to_screen((960.0, 540.0)), (960.0, 540.0));
+    }
+
+    #[test]
+    fn offset_shifts_image() {
+        let t = Viewport {
+            scale: 1.0,
+            offset: (30.0, -40.0),
+            source_size: (100.0, 100.0),
+            widget_size: (100.0, 100.0),
+        };
+        assert_eq!(t.image_to_screen((0.0, 0.0)), (-30.0, 40.0));
+    }
+
+    #[test]
+    fn zooming_about_a_point_of_a_non_square_image() {
+        let t = Viewport {
+            scale: 1.5,
+            offset: (100.0, -20.0),
+            source_size: (1920.0, 1080.0),
+            widget_size: (1920.0, 1080.0),
+        };
+        let screen = (1700.0, 90.0);
+        let under = t.screen_to_image(screen);
+        assert_close(t.image_to_screen(under), screen);
+        let zoomed = t.zoom_about_point(6.0, screen);
+        assert_eq!(zoomed.scale, 6.0);
+        assert_close(zoomed.screen_to_image(screen), under);
+        assert_close(zoomed.zoom_about_point(1.5, screen).offset, t.offset);
+    }
+
+    #[test]
+    fn capture_of_another_size_than_the_screen() {
+        let state = ImageState::default();
+        state.pixels.set(Some((800, 600)));
+        let screen = (1920.0, 1080.0);
+        let t = state.viewport(screen);
+        assert_eq!(t.image_to_screen((400.0, 300.0)), (960.0, 540.0));
+
+        state.zoom_at(1.0, (100.0, 100.0), screen);
+        let zoomed = state.viewport(screen);
+        assert_eq!(zoomed.scale, 2.0);
+        assert_close(
+            zoomed.screen_to_image((100.0, 100.0)),
+            t.screen_to_image((100.0, 100.0)),
+        );
+
+        // Locking keeps the view as it is
+        state.toggle_lock(screen);
+        assert_close(state.viewport(screen).origin(), zoomed.origin());
+        state.toggle_lock(screen);
+
+        state.show_region((0.0, 0.0, 800.0, 600.0), None, screen);
+        let t = state.viewport(screen);
+        assert_close(t.image_to_screen((0.0, 0.0)), (240.0, 0.0));
+        assert_close(t.image_to_screen((800.0, 600.0)), (1680.0, 1080.0));
+    }
+
+    #[test]
+    fn fitting_the_whole_capture_uses_its_size() {
+        let state = ImageState::default();
+        // A HiDPI capture shown in a smaller window
+        state.pixels.set(Some((2400, 1200)));
+        state.density.set(2.0);
+        let window = (1000.0, 1000.0);
+        assert_eq!(state.whole_capture(window), (0.0, 0.0, 1200.0, 600.0));
+        state.show_region(state.whole_capture(window), None, window);
+        let t = state.viewport(window);
+        assert_close(t.image_to_screen((0.0, 0.0)), (0.0, 250.0));
+        assert_close(t.image_to_screen((1200.0, 600.0)), (1000.0, 750.0));
+    }
+
+    #[test]
+    fn visible_region_shrinks_with_zoom() {
+        let screen = ((0.0, 0.0), (100.0, 50.0));
+        let mut t = Viewport {
+            scale: 1.0,
+            offset: (0.0, 0.0),
+            source_size: (100.0, 50.0),
+            widget_size: (100.0, 50.0),
+        };
+        assert_eq!(t.visible_region(screen.0, screen.1), Some(screen));
+
+        t.scale = 4.0;
+        assert_eq!(
+            t.visible_region(screen.0, screen.1),
+            Some(((37.5, 18.75), (62.5, 31.25)))
+        );
+
+        t.scale = 0.5;
+        assert_eq!(t.visible_region(screen.0, screen.1), Some(screen));
+
+        t.offset = (1000.0, 0.0);
+        assert_eq!(t.visible_region(screen.0, screen.1), None);
+    }
+
+    #[test]
+    fn center_on_moves_point_to_screen_position() {
+        let state = ImageState::default();
+        state.set_scale(2.5);
+        state.center_on((10.0, 90.0), (50.0, 50.0), (100.0, 100.0));
+        let t = state.viewport((100.0, 100.0));
+        assert_eq!(t.image_to_screen((10.0, 90.0)), (50.0, 50.0));
+    }
+
+    #[test]
+    fn follow_mouse_centers_pointer() {
+        let state = ImageState::default();
+        state.set_scale(2.0);
+        state.mouse_pos.set((10.0, 20.0));
+        assert_eq!(state.pointer((100.0, 100.0)), (10.0, 20.0));
+
+        state.follow_mouse.set(true);
+        state.follow((100.0, 100.0));
+        assert_eq!(state.pointer((100.0, 100.0)), (50.0, 50.0));
+    }
+
+    #[test]
+    fn dock_magnifies_around_pointer() {
+        assert_eq!(Dock::Top.band(1000.0), (0.0, 250.0));
+        assert_eq!(Dock::Bottom.band(1000.0), (750.0, 250.0));
+
+        let state = ImageState::default();
+        state.set_scale(3.0);
+        state.mouse_pos.set((300.0, 800.0));
+        let t = state.dock_viewport((1000.0, 1000.0), Dock::Top.band(1000.0));
+        assert_close(t.image_to_screen((300.0, 800.0)), (300.0, 125.0));
+        assert_close(t.image_to_screen((310.0, 800.0)), (330.0, 125.0));
+    }
+
+    #[test]
+    fn lens_magnifies_the_view_around_pointer() {
+        let state = ImageState::default();
+        state.set_scale(1.5);
+        state.set_lens_scale(2.0);
+        state.mouse_pos.set((300.0, 800.0));
+        let view = state.viewport((1000.0, 1000.0));
+        let t = state.lens_viewport((1000.0, 1000.0));
+        let under = view.screen_to_image((300.0, 800.0));
+        assert_close(t.image_to_screen(under), (300.0, 800.0));
+        assert_close(t.image_to_screen((under.0 + 10.0, under.1)), (330.0, 800.0));
+
+        state.set_lens_scale(0.5);
+        assert_eq!(state.lens_scale.get(), 1.0);
+        assert_eq!(Lens::next(Some(Lens::Rectangle)), None);
+    }
+
+    #[test]
+    fn lagging_highlight_catches_up() {
+        let state = ImageState::default();
+        state.highlight_lag.set(Some(0.1));
+        state.mouse_pos.set((0.0, 0.0));
+        assert!(!state.ease_spotlight(0.016, (800.0, 600.0)));
+
+        state.mouse_pos.set((100.0, 0.0));
+        assert!(state.ease_spotlight(0.1, (800.0, 600.0)));
+        let (x, _) = state.highlight_pos((800.0, 600.0));
+        assert!((x - 100.0 * (1.0 - (-1f64).exp())).abs() < 1e-9);
+
+        assert!(!state.ease_spotlight(f64::INFINITY, (800.0, 600.0)));
+        assert_eq!(state.highlight_pos((800.0, 600.0)), (100.0, 0.0));
+    }
+
+    #[test]
+    fn region_is_centered_at_scale() {
+        let state = ImageState::default();
+        state.show_region((0.0, 0.0, 400.0, 40.0), Some(2.0), (1600.0, 900.0));
+        let t = state.viewport((1600.0, 900.0));
+        assert_eq!(t.scale, 2.0);
+        assert_close(t.image_to_screen((200.0, 20.0)), (800.0, 450.0));
+    }
+
+    #[test]
+    fn window_under_pointer_is_framed() {
+        let state = ImageState::default();
+        state.windows.replace(vec![Rect {
+            x: 400,
+            y: 300,
+            width: 300,
+            height: 200,
+        }]);
+        // It would fit larger
+        state.max_scale.set(3.0);
+        state.mouse_pos.set((500.0, 400.0));
+        assert!(state.frame_window_at_pointer((1200.0, 800.0)));
+        let t = state.viewport((1200.0, 800.0));
+        assert_eq!(t.scale, 3.0);
+        assert_close(t.image_to_screen((550.0, 400.0)), (600.0, 400.0));
+
+        state.mouse_pos.set((10.0, 10.0));
+        assert!(!state.frame_window_at_pointer((1200.0, 800.0)));
+    }
+
+    #[test]
+    fn panning_snaps_to_window_edges() {
+        let state = ImageState::default();
+        state.set_scale(2.0);
+        state
+            .snap_edges
+            .replace(Some(SnapEdges::new([(100.0, 0.0, 200.0, 50.0)])));
+        // Window edge at x 100 lands 5 px right of the screen's left edge
+        let t = state.viewport((800.0, 600.0));
+        state
+            .offset
+            .set((t.image_to_screen((100.0, 0.0)).0 - 5.0, 0.0));
+        state.snap((800.0, 600.0));
+        let t = state.viewport((800.0, 600.0));
+        assert!(t.image_to_screen((100.0, 0.0)).0.abs() < 1e-9);
+
+        // Nothing close enough, nothing moves
+        state.offset.set((-300.0, 0.0));
+        state.snap((800.0, 600.0));
+        assert_eq!(state.offset.get(), (-300.0, 0.0));
+    }
+
+    #[test]
+    fn geometry_stays_on_output() {
+        assert_eq!(
+            Geometry::RightHalf.resolve((1921, 1080)),
+            (961, 0, 960, 1080)
+        );
+        assert_eq!(
+            Geometry::BottomHalf.resolve((1920, 1080)),
+            (0, 540, 1920, 540)
+        );
+        assert_eq!(
+            Geometry::Rect(1800, 100, 400, 400).resolve((1920, 1080)),
+            (1800, 100, 120, 400)
+        );
+    }
+
+    #[test]
+    fn locked_region_fills_screen() {
+        let state = ImageState::default();
+        state.lock.set(Some((100.0, 50.0, 200.0, 100.0)));
+        let t = state.viewport((800.0, 400.0));
+        assert_eq!(t.scale, 4.0);
+        assert_close(t.image_to_screen((100.0, 50.0)), (0.0, 0.0));
+        assert_close(t.image_to_screen((300.0, 150.0)), (800.0, 400.0));
+
+        // Panning and zooming don't move a locked view
+        state.zoom_in();
+        state.drag((0.0, 0.0), (10.0, 10.0));
+        assert_eq!(state.viewport((800.0, 400.0)), t);
+    }
+
+    #[test]
+    fn locking_keeps_current_view() {
+        let state = ImageState::default();
+        state.set_scale(2.5);
+        state.offset.set((30.0, -20.0));
+        let before = state.viewport((800.0, 400.0));
+        state.toggle_lock((800.0, 400.0));
+        let locked = state.viewport((800.0, 400.0));
+        assert_close(locked.origin(), before.origin());
+        assert!((locked.scale - before.scale).abs() < 1e-9);
+
+        state.toggle_lock((800.0, 400.0));
+        assert_eq!(state.lock.get(), None);
+    }
+
+    #[test]
+    fn zoom_is_clamped() {
+        let state = ImageState::default();
+        (0..100).for_each(|_| state.zoom_in());
+        assert_eq!(state.scale.get(), SCALE_MAX);
+        (0..100).for_each(|_| state.zoom_out());
+        assert_eq!(state.scale.get(), SCALE_MIN);
+    }
+
+    #[test]
+    fn zoom_steps_are_relative_to_the_scale() {
+        let state = ImageState::default();
+        state.scale.set(10.0);
+        state.zoom_in();
+        assert_close((state.scale.get(), 0.0), (11.0, 0.0));
+        state.zoom_out();
+        assert_close((state.scale.get(), 0.0), (10.0, 0.0));
+        state.scale.set(0.5);
+        assert_close((state.relative_step(0.1), 0.0), (0.05, 0.0));
+    }
+
+    #[test]
+    fn highlight_resizes_within_range() {
+        let state = ImageState::default();
+        state.highlight_radius.set(100.0);
+        state.resize_highlight(0.1);
+        assert_close((state.highlight_radius.get(), 0.0), (110.0, 0.0));
+        (0..100).for_each(|_| state.resize_highlight(-0.5));
+        assert_eq!(state.highlight_radius.get(), HIGHLIGHT_RADIUS_MIN);
+    }
+
+    #[test]
+    fn zooming_at_a_point_keeps_it_in_place() {
+        let state = ImageState::default();
+        let size = (800.0, 600.0);
+        let under = |screen| state.viewport(size).screen_to_image(screen);
+        let before = under((100.0, 500.0));
+        state.zoom_at(1.0, (100.0, 500.0), size);
+        assert_eq!(state.scale.get(), 2.0);
+        assert_close(under((100.0, 500.0)), before);
+
+        let before = under((700.0, 20.0));
+        state.zoom_at(-0.5, (700.0, 20.0), size);
+        assert_eq!(state.scale.get(), 1.5);
+        assert_close(under((700.0, 20.0)), before);
+    }
+
+    #[test]
+    fn configured_zoom_steps() {
+        let state = ImageState::default();
+        state.configure(&ViewConfig {
+            zoom_step: 0.5,
+            min_scale: 0.25,
+            max_scale: 8.0,
+            ..ViewConfig::default()
+        });
+        state.zoom_in();
+        assert_eq!(state.scale.get(), 1.5);
+        (0..100).for_each(|_| state.zoom_in());
+        assert_eq!(state.scale.get(), 8.0);
+        // No longer bound by the zoom step
+        (0..100).for_each(|_| state.zoom_out());
+        assert_eq!(state.scale.get(), 0.25);
+    }
+
+    #[test]
+    fn flung_view_slows_down() {
+        let state = ImageState::default();
+        let mut velocity = (400.0, 0.0);
+        for _ in 0..600 {
+            velocity = state.glide(velocity, 1.0 / 60.0);
+        }
+        assert!(velocity.0.abs() < 1.0);
+        // Moves along with the pointer, and no further than all the speed friction takes
+        let (x, y) = state.offset.get();
+        assert!(x < -90.0 && x > -400.0 / FLING_FRICTION);
+        assert_eq!(y, 0.0);
+    }
+
+    #[test]
+    fn drag_moves_content_with_pointer() {
+        let state = ImageState::default();
+        state.drag((100.0, 100.0), (110.0, 95.0));
+        let t = state.viewport((200.0, 200.0));
+        assert_eq!(t.image_to_screen((0.0, 0.0)), (10.0, -5.0));
+    }
+
+    proptest! {
+        #[test]
+        fn screen_image_round_trip(
+            scale in 0.1f64..10.0,
+            ox in -5000f64..5000.0,
+            oy in -5000f64..5000.0,
+            w in 1f64..8000.0,
+            h in 1f64..8000.0,
+            x in -10000f64..10000.0,
+            y in -10000f64..10000.0,
+        ) {
+            let t = Viewport {
+                scale,
+                offset: (ox, oy),
+                source_size: (w, h),
+                widget_size: (h, w),
+            };
+            assert_close(t.screen_to_image(t.image_to_screen((x, y))), (x, y));
+            assert_close(t.image_to_screen(t.screen_to_image((x, y))), (x, y));
+        }
+
+        #[test]
+        fn center_is_fixed_without_offset(
+            scale in 0.1f64..10.0,
+            w in 1f64..8000.0,
+            h in 1f64..8000.0,
+        ) {
+            let t = Viewport {
+                scale,
+                offset: (0.0, 0.0),
+                source_size: (w, h),
+                widget_size: (w, h),
+            };
+            assert_close(t.image_to_screen((w / 2.0, h / 2.0)), (w / 2.0, h / 2.0));
+        }
+    }
+}
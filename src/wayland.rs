@@ -0,0 +1,603 @@
+//! `--backend wayland`: show the overlay straight on wlr-layer-shell with shared memory buffers,
+//! without GTK's windows or main loop.
+//!
+//! The view is painted by [`render::paint`] and keys, buttons and scrolling become the same
+//! [`Action`]s as in the GTK overlay, carried out with [`input::apply`], so neither is specific
+//! to GTK. Without xkbcommon keys are read by where they are on a US layout, and only the names
+//! [`keysym`] knows can be bound in `[keys]`. Zooming doesn't animate and flinging doesn't glide,
+//! and none of the tools that open windows of their own are there.
+
+use {
+    crate::{
+        capture::Frame,
+        config::{ConfigFile, KeyNames, Keys, Sensitivity, SCROLL_PAN_STEP},
+        input::{self, Action, Controller, PanButton},
+        render,
+        view::ImageState,
+        Error,
+    },
+    gtk::{
+        cairo::{Context, Format, ImageSurface},
+        gdk_pixbuf::Pixbuf,
+    },
+    memmap2::MmapMut,
+    std::{
+        fmt,
+        fs::File,
+        io,
+        os::fd::{AsFd, FromRawFd, OwnedFd},
+    },
+    wayland_client::{
+        delegate_noop,
+        globals::{registry_queue_init, GlobalListContents},
+        protocol::{
+            wl_buffer, wl_callback, wl_compositor, wl_keyboard, wl_output, wl_pointer, wl_registry,
+            wl_seat, wl_shm, wl_shm_pool, wl_surface,
+        },
+        Connection, Dispatch, Proxy, QueueHandle, WEnum,
+    },
+    wayland_protocols_wlr::layer_shell::v1::client::{
+        zwlr_layer_shell_v1::{self, ZwlrLayerShellV1},
+        zwlr_layer_surface_v1::{self, ZwlrLayerSurfaceV1},
+    },
+};
+
+/// evdev codes of the keys in each row of a US layout, from the first one on.
+const ROWS: [(u32, &str); 4] = [
+    (2, "1234567890-="),
+    (16, "qwertyuiop[]"),
+    (30, "asdfghjkl;'`"),
+    (44, "zxcvbnm,./"),
+];
+
+/// evdev code, keysym and name of the keys outside [`ROWS`] worth binding.
+const NAMED: &[(u32, u32, &str)] = &[
+    (1, 0xff1b, "Escape"),
+    (14, 0xff08, "BackSpace"),
+    (15, 0xff09, "Tab"),
+    (28, 0xff0d, "Return"),
+    (42, 0xffe1, "Shift_L"),
+    (54, 0xffe2, "Shift_R"),
+    (57, ' ' as u32, "space"),
+    (59, 0xffbe, "F1"),
+    (60, 0xffbf, "F2"),
+    (61, 0xffc0, "F3"),
+    (62, 0xffc1, "F4"),
+    (63, 0xffc2, "F5"),
+    (64, 0xffc3, "F6"),
+    (65, 0xffc4, "F7"),
+    (66, 0xffc5, "F8"),
+    (67, 0xffc6, "F9"),
+    (68, 0xffc7, "F10"),
+    (74, 0xffad, "KP_Subtract"),
+    (78, 0xffab, "KP_Add"),
+    (82, 0xffb0, "KP_0"),
+    (87, 0xffc8, "F11"),
+    (88, 0xffc9, "F12"),
+    (99, 0xff61, "Print"),
+    (102, 0xff50, "Home"),
+    (103, 0xff52, "Up"),
+    (104, 0xff55, "Prior"),
+    (105, 0xff51, "Left"),
+    (106, 0xff53, "Right"),
+    (107, 0xff57, "End"),
+    (108, 0xff54, "Down"),
+    (109, 0xff56, "Next"),
+];
+
+/// `wl_pointer` axis value of one notch of a mouse wheel, as most compositors send it.
+const WHEEL_NOTCH: f64 = 15.0;
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+const BTN_MIDDLE: u32 = 0x112;
+
+/// evdev code of `button`.
+fn button_code(button: PanButton) -> u32 {
+    match button {
+        PanButton::Left => BTN_LEFT,
+        PanButton::Middle => BTN_MIDDLE,
+        PanButton::Right => BTN_RIGHT,
+    }
+}
+
+/// Keysym of the key with evdev code `code` on a US layout, unshifted.
+fn keysym(code: u32) -> Option<u32> {
+    ROWS.iter()
+        .filter_map(|(first, row)| row.chars().nth(code.checked_sub(*first)? as usize))
+        .map(|c| c as u32)
+        .next()
+        .or_else(|| NAMED.iter().find(|(c, _, _)| *c == code).map(|k| k.1))
+}
+
+/// Keysyms for the keys `names` binds, for the names of single characters and those in
+/// [`NAMED`]. The others keep their default.
+pub fn keys(names: &KeyNames) -> Keys {
+    names.resolve(|name| {
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_graphic() => Some(c.to_ascii_lowercase() as u32),
+            _ => NAMED.iter().find(|k| k.2 == name).map(|k| k.1),
+        }
+    })
+}
+
+/// Shared memory buffer a frame is painted into before the compositor gets it.
+struct Slot {
+    buffer: wl_buffer::WlBuffer,
+    map: MmapMut,
+    size: (i32, i32),
+    /// Whether the compositor still reads from it.
+    busy: bool,
+}
+
+struct Overlay {
+    state: ImageState,
+    keys: Keys,
+    sensitivity: Sensitivity,
+    /// evdev code of the button that pans besides the middle one.
+    pan_button: u32,
+    pixbuf: Pixbuf,
+    /// Whole physical pixels per logical pixel the buffers have.
+    buffer_scale: i32,
+    controller: Controller,
+    /// Buttons held down that pan.
+    held: Vec<u32>,
+    /// Scrolling that didn't add up to a step yet.
+    scrolled: f64,
+    shift: bool,
+
+    shm: wl_shm::WlShm,
+    surface: wl_surface::WlSurface,
+    /// Names of the bound outputs, by the index they were bound with.
+    names: Vec<Option<String>>,
+    pointer: Option<wl_pointer::WlPointer>,
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+    slots: Vec<Slot>,
+    /// Logical size the layer surface was given, once it was.
+    size: Option<(i32, i32)>,
+    dirty: bool,
+    frame_pending: bool,
+    quit: bool,
+}
+
+fn failed(e: impl fmt::Display) -> Error {
+    Error::Wayland(e.to_string())
+}
+
+/// Show `frame` with `state` over the output called `output`, or the one the compositor picks,
+/// until the overlay is quit. Dragging with `pan_button` or the middle one pans.
+#[tracing::instrument(skip(frame, state, config))]
+pub fn show(
+    frame: Frame,
+    output: Option<&str>,
+    state: ImageState,
+    config: &ConfigFile,
+    pan_button: PanButton,
+) -> Result<(), Error> {
+    let conn = Connection::connect_to_env().map_err(failed)?;
+    let (globals, mut queue) = registry_queue_init::<Overlay>(&conn).map_err(failed)?;
+    let qh = queue.handle();
+    let compositor: wl_compositor::WlCompositor = globals.bind(&qh, 3..=4, ()).map_err(failed)?;
+    let shm: wl_shm::WlShm = globals.bind(&qh, 1..=1, ()).map_err(failed)?;
+    let layer_shell: ZwlrLayerShellV1 = globals.bind(&qh, 1..=4, ()).map_err(failed)?;
+    let _seat: wl_seat::WlSeat = globals.bind(&qh, 1..=5, ()).map_err(failed)?;
+
+    // Version 4 adds names
+    let outputs: Vec<wl_output::WlOutput> = globals
+        .contents()
+        .clone_list()
+        .into_iter()
+        .filter(|g| g.interface == wl_output::WlOutput::interface().name && g.version >= 4)
+        .enumerate()
+        .map(|(i, g)| globals.registry().bind(g.name, 4, &qh, i))
+        .collect();
+
+    state.pixels.set(Some((frame.width(), frame.height())));
+    let buffer_scale = state.density.get().round().max(1.0) as i32;
+    let mut overlay = Overlay {
+        keys: keys(&config.keys),
+        sensitivity: config.sensitivity.mouse,
+        pan_button: button_code(pan_button),
+        state,
+        pixbuf: frame.to_pixbuf(),
+        buffer_scale,
+        controller: Controller::default(),
+        held: Vec::new(),
+        scrolled: 0.0,
+        shift: false,
+        shm,
+        surface: compositor.create_surface(&qh, ()),
+        names: vec![None; outputs.len()],
+        pointer: None,
+        keyboard: None,
+        slots: Vec::new(),
+        size: None,
+        dirty: true,
+        frame_pending: false,
+        quit: false,
+    };
+    queue.roundtrip(&mut overlay).map_err(failed)?;
+    let target = match output {
+        Some(name) => Some(
+            overlay
+                .names
+                .iter()
+                .position(|n| n.as_deref() == Some(name))
+                .map(|i| &outputs[i])
+                .ok_or_else(|| Error::UnknownOutput(name.to_string()))?,
+        ),
+        None => None,
+    };
+
+    let layer_surface = layer_shell.get_layer_surface(
+        &overlay.surface,
+        target,
+        zwlr_layer_shell_v1::Layer::Overlay,
+        env!("CARGO_PKG_NAME").to_string(),
+        &qh,
+        (),
+    );
+    use zwlr_layer_surface_v1::Anchor;
+    layer_surface.set_anchor(Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right);
+    layer_surface.set_exclusive_zone(-1);
+    layer_surface
+        .set_keyboard_interactivity(zwlr_layer_surface_v1::KeyboardInteractivity::Exclusive);
+    overlay.surface.set_buffer_scale(buffer_scale);
+    overlay.surface.commit();
+
+    while !overlay.quit {
+        queue.blocking_dispatch(&mut overlay).map_err(failed)?;
+        if overlay.dirty && !overlay.frame_pending {
+            overlay.draw(&qh)?;
+        }
+    }
+    layer_surface.destroy();
+    overlay.surface.destroy();
+    Ok(())
+}
+
+impl Overlay {
+    /// Paint the view into a buffer the compositor is done with and show it, if there is one
+    /// and the surface has a size yet.
+    fn draw(&mut self, qh: &QueueHandle<Self>) -> Result<(), Error> {
+        let Some((width, height)) = self.size else {
+            return Ok(());
+        };
+        let size = (width * self.buffer_scale, height * self.buffer_scale);
+        // Buffers of an old size aren't used again
+        self.slots.retain(|slot| match slot.size == size {
+            true => true,
+            false => {
+                slot.buffer.destroy();
+                false
+            }
+        });
+        let index = match self.slots.iter().position(|slot| !slot.busy) {
+            Some(index) => index,
+            // Two are enough for one to be painted while the compositor shows the other
+            None if self.slots.len() < 2 => {
+                let slot = self.allocate(size, qh)?;
+                self.slots.push(slot);
+                self.slots.len() - 1
+            }
+            // Drawn once one is released
+            None => return Ok(()),
+        };
+
+        let mut surface = ImageSurface::create(Format::Rgb24, size.0, size.1)?;
+        surface.set_device_scale(self.buffer_scale as f64, self.buffer_scale as f64);
+        {
+            let ctx = Context::new(&surface)?;
+            render::paint(
+                &ctx,
+                Some(&self.pixbuf),
+                self.screen_size(),
+                &self.state,
+                None,
+            );
+        }
+        surface.flush();
+        let slot = &mut self.slots[index];
+        // Cairo's xRGB in native byte order is wl_shm's XRGB8888, so rows copy over as they are
+        let data = surface.data().map_err(failed)?;
+        slot.map.copy_from_slice(&data);
+        slot.busy = true;
+
+        self.surface.attach(Some(&slot.buffer), 0, 0);
+        self.surface.damage_buffer(0, 0, size.0, size.1);
+        self.surface.frame(qh, ());
+        self.surface.commit();
+        self.dirty = false;
+        self.frame_pending = true;
+        Ok(())
+    }
+
+    /// A new shared memory buffer of `size` physical pixels.
+    fn allocate(&self, size: (i32, i32), qh: &QueueHandle<Self>) -> Result<Slot, Error> {
+        let stride = Format::Rgb24.stride_for_width(size.0 as u32)?;
+        let len = stride as usize * size.1 as usize;
+        let fd = unsafe { libc::memfd_create(c"sway-boomer-overlay".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        file.set_len(len as u64)?;
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        let pool = self.shm.create_pool(file.as_fd(), len as i32, qh, ());
+        let buffer =
+            pool.create_buffer(0, size.0, size.1, stride, wl_shm::Format::Xrgb8888, qh, ());
+        // The buffer keeps the memory it needs
+        pool.destroy();
+        Ok(Slot {
+            buffer,
+            map,
+            size,
+            busy: false,
+        })
+    }
+
+    /// Size of the surface in logical pixels, nothing until it's configured.
+    fn screen_size(&self) -> (f64, f64) {
+        let (width, height) = self.size.unwrap_or_default();
+        (width as f64, height as f64)
+    }
+
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.quit = true,
+            action => self.dirty |= input::apply(action, &self.state, self.screen_size()),
+        }
+    }
+
+    fn pointer_moved(&mut self, pos: (f64, f64), time: u32) {
+        let size = self.screen_size();
+        self.state.mouse_pos.set(pos);
+        if self.state.follow_mouse.get() {
+            self.state.follow(size);
+            self.dirty = true;
+        } else if !self.held.is_empty() && self.controller.drag_to(&self.state, pos, time, size) {
+            self.dirty = true;
+        }
+        // Whatever is drawn at the pointer has to move along with it
+        if self.state.highlight.get()
+            || self.state.dock.get().is_some()
+            || self.state.lens.get().is_some()
+        {
+            self.dirty = true;
+        }
+    }
+
+    /// Scrolling by `value` along `axis`, zooming around the pointer or panning like the GTK
+    /// overlay does with a mouse wheel.
+    fn scroll(&mut self, axis: wl_pointer::Axis, value: f64) {
+        self.scrolled += value;
+        let steps = (self.scrolled / WHEEL_NOTCH).trunc();
+        if steps == 0.0 {
+            return;
+        }
+        self.scrolled -= steps * WHEEL_NOTCH;
+        // Shift may be what holds the highlight, which scrolling resizes then
+        let shift = self.shift && !self.state.highlight.get();
+        let action = match (axis, shift) {
+            (wl_pointer::Axis::HorizontalScroll, _) | (_, true) => {
+                Action::Pan(steps * SCROLL_PAN_STEP, 0.0)
+            }
+            _ => Action::Zoom(-steps * self.state.zoom_step.get()),
+        };
+        let pos = self.state.mouse_pos.get();
+        self.perform(input::scrolled(action, self.sensitivity, &self.state, pos));
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Overlay {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, usize> for Overlay {
+    fn event(
+        overlay: &mut Self,
+        _: &wl_output::WlOutput,
+        event: wl_output::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Name { name } = event {
+            overlay.names[*index] = Some(name);
+        }
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, ()> for Overlay {
+    fn event(
+        overlay: &mut Self,
+        layer_surface: &ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_layer_surface_v1::Event::Configure {
+                serial,
+                width,
+                height,
+            } => {
+                layer_surface.ack_configure(serial);
+                overlay.size = Some((width as i32, height as i32));
+                overlay.dirty = true;
+            }
+            zwlr_layer_surface_v1::Event::Closed => overlay.quit = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_buffer::WlBuffer, ()> for Overlay {
+    fn event(
+        overlay: &mut Self,
+        buffer: &wl_buffer::WlBuffer,
+        event: wl_buffer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_buffer::Event::Release = event {
+            if let Some(slot) = overlay.slots.iter_mut().find(|s| &s.buffer == buffer) {
+                slot.busy = false;
+            }
+        }
+    }
+}
+
+impl Dispatch<wl_callback::WlCallback, ()> for Overlay {
+    fn event(
+        overlay: &mut Self,
+        _: &wl_callback::WlCallback,
+        event: wl_callback::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event {
+            overlay.frame_pending = false;
+        }
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for Overlay {
+    fn event(
+        overlay: &mut Self,
+        seat: &wl_seat::WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(capabilities),
+        } = event
+        {
+            if capabilities.contains(wl_seat::Capability::Pointer) && overlay.pointer.is_none() {
+                overlay.pointer = Some(seat.get_pointer(qh, ()));
+            }
+            if capabilities.contains(wl_seat::Capability::Keyboard) && overlay.keyboard.is_none() {
+                overlay.keyboard = Some(seat.get_keyboard(qh, ()));
+            }
+        }
+    }
+}
+
+impl Dispatch<wl_pointer::WlPointer, ()> for Overlay {
+    fn event(
+        overlay: &mut Self,
+        _: &wl_pointer::WlPointer,
+        event: wl_pointer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_pointer::Event::Enter {
+                surface_x,
+                surface_y,
+                ..
+            } => overlay.state.mouse_pos.set((surface_x, surface_y)),
+            wl_pointer::Event::Motion {
+                time,
+                surface_x,
+                surface_y,
+            } => overlay.pointer_moved((surface_x, surface_y), time),
+            wl_pointer::Event::Button {
+                time,
+                button,
+                state: WEnum::Value(state),
+                ..
+            } if button == overlay.pan_button || button == BTN_MIDDLE => match state {
+                wl_pointer::ButtonState::Pressed => overlay.held.push(button),
+                _ => {
+                    overlay.held.retain(|&b| b != button);
+                    if overlay.held.is_empty() {
+                        // Nothing keeps the view gliding here, so a fling just stops
+                        overlay.controller.release(time);
+                    }
+                }
+            },
+            wl_pointer::Event::Axis {
+                axis: WEnum::Value(axis),
+                value,
+                ..
+            } => overlay.scroll(axis, value),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_keyboard::WlKeyboard, ()> for Overlay {
+    fn event(
+        overlay: &mut Self,
+        _: &wl_keyboard::WlKeyboard,
+        event: wl_keyboard::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let wl_keyboard::Event::Key {
+            key,
+            state: WEnum::Value(state),
+            ..
+        } = event
+        else {
+            return;
+        };
+        let Some(sym) = keysym(key) else {
+            return;
+        };
+        let pressed = state == wl_keyboard::KeyState::Pressed;
+        if matches!(sym, 0xffe1 | 0xffe2) {
+            overlay.shift = pressed;
+        }
+        let action = input::key_action(&overlay.keys, sym, pressed)
+            .or_else(|| pressed.then(|| input::zoom_keys(sym)).flatten());
+        if let Some(action) = action {
+            overlay.perform(action);
+        }
+    }
+}
+
+delegate_noop!(Overlay: wl_compositor::WlCompositor);
+delegate_noop!(Overlay: wl_shm::WlShm);
+delegate_noop!(Overlay: wl_shm_pool::WlShmPool);
+delegate_noop!(Overlay: wl_surface::WlSurface);
+delegate_noop!(Overlay: ZwlrLayerShellV1);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_read_by_where_they_are_on_a_us_layout() {
+        assert_eq!(keysym(16), Some('q' as u32));
+        assert_eq!(keysym(11), Some('0' as u32));
+        assert_eq!(keysym(53), Some('/' as u32));
+        assert_eq!(keysym(1), Some(0xff1b));
+        assert_eq!(keysym(104), Some(0xff55));
+        assert_eq!(keysym(29), None);
+
+        let names: KeyNames =
+            toml::from_str("quit = \"q\"\nlens = \"F1\"\nzoom_in = \"Hyper_L\"").unwrap();
+        let keys = keys(&names);
+        assert_eq!(keys.quit, 'q' as u32);
+        assert_eq!(keys.lens, 0xffbe);
+        // Unknown, so still the default
+        assert_eq!(keys.zoom_in, Keys::default().zoom_in);
+    }
+}
@@ -0,0 +1,888 @@
+//! `--backend winit`: show the overlay in a fullscreen winit window drawn with wgpu, without
+//! GTK's windows or main loop.
+//!
+//! Like [`crate::gpu`], the capture is uploaded to a texture once and every frame is a single
+//! triangle covering the window, whose fragment shader looks the source pixel up through the
+//! [`Viewport`]s of the view, the dock and the lens. Keys, buttons and scrolling become the same
+//! [`Action`]s as in the GTK overlay and are carried out with [`input::apply`]. winit has no
+//! wlr-layer-shell surfaces, so the compositor sees an ordinary fullscreen window. Zooming
+//! doesn't animate and flinging doesn't glide, and none of the tools that open windows of their
+//! own are there.
+//!
+//! [`Viewport`]: crate::view::Viewport
+
+use {
+    crate::{
+        capture::Frame,
+        config::{
+            ConfigFile, Keys, Sensitivity, CURSOR_FILL, CURSOR_OUTLINE, DOCK_BORDER,
+            DOCK_BORDER_WIDTH, HIGH_CONTRAST,
+        },
+        cursor,
+        filter::{ColorMatrix, Polarity},
+        input::{self, Action, Controller, PanButton},
+        view::{ImageState, Lens},
+        Error,
+    },
+    gtk::gdk::{self, keys::constants as k, ModifierType, ScrollDirection},
+    std::{fmt, sync::Arc, time::Instant},
+    winit::{
+        application::ApplicationHandler,
+        event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+        event_loop::{ActiveEventLoop, EventLoop},
+        keyboard::{Key, ModifiersState, NamedKey},
+        platform::wayland::WindowAttributesExtWayland,
+        window::{Fullscreen, Window, WindowId},
+    },
+};
+
+const SHADER: &str = r#"
+struct View {
+    // Capture size in logical pixels, device pixels per logical pixel and the cursor's size, 0
+    // when it isn't drawn
+    image: vec4<f32>,
+    // Origin and scale of the view, see `view::Viewport`, and the shape of the lens
+    view: vec4<f32>,
+    // Origin and scale of the docked band, and the width of the borders
+    dock_view: vec4<f32>,
+    lens_view: vec4<f32>,
+    // Top and bottom of the docked band, and half the size of the lens
+    dock: vec4<f32>,
+    // The pointer and the middle of the highlight
+    pointer: vec4<f32>,
+    // Radius of the highlight and how much is dimmed outside it
+    spot: vec4<f32>,
+    background: vec4<f32>,
+    highlight: vec4<f32>,
+    border: vec4<f32>,
+    // Rows of the color matrix, with the offset last
+    color: array<vec4<f32>, 3>,
+    high_contrast: vec4<f32>,
+    cursor_fill: vec4<f32>,
+    cursor_outline: vec4<f32>,
+    cursor_shape: array<vec4<f32>, CURSOR_POINTS>,
+}
+
+@group(0) @binding(0) var<uniform> v: View;
+@group(0) @binding(1) var image: texture_2d<f32>;
+@group(0) @binding(2) var image_sampler: sampler;
+
+@vertex
+fn vertex(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+    let corner = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    return vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fragment(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    // Unlike GL's, the framebuffer starts at the top like the screen
+    let screen = position.xy / v.image.z;
+    let mouse = v.pointer.xy;
+    let docked = screen.y >= v.dock.x && screen.y < v.dock.y;
+    let lens = i32(v.view.w);
+    // Signed distance from the edge of the lens, negative inside
+    var lens_edge = length(screen - mouse) - v.dock.z;
+    if lens == 2 {
+        let d = abs(screen - mouse) - v.dock.zw;
+        lens_edge = max(d.x, d.y);
+    }
+    let in_lens = lens > 0 && lens_edge < 0.0;
+    var pixel = (screen - v.view.xy) / v.view.z;
+    if in_lens {
+        pixel = (screen - v.lens_view.xy) / v.lens_view.z;
+    } else if docked {
+        pixel = (screen - v.dock_view.xy) / v.dock_view.z;
+    }
+
+    var color = v.background.rgb;
+    if all(pixel >= vec2<f32>(0.0)) && all(pixel < v.image.xy) {
+        let source = textureSampleLevel(image, image_sampler, pixel / v.image.xy, 0.0).rgb;
+        let offset = vec3<f32>(v.color[0].w, v.color[1].w, v.color[2].w);
+        color = vec3<f32>(
+            dot(v.color[0].xyz, source),
+            dot(v.color[1].xyz, source),
+            dot(v.color[2].xyz, source),
+        );
+        color = clamp(color + offset, vec3<f32>(0.0), vec3<f32>(1.0));
+        if v.high_contrast.w > 0.5 {
+            let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+            let c = clamp((luminance - v.high_contrast.x) / v.high_contrast.y + 0.5, 0.0, 1.0);
+            color = vec3<f32>(mix(c, 1.0 - c, v.high_contrast.z));
+        }
+    }
+
+    // The border runs along the edge of the band that faces the middle of the screen
+    let dock_edge = select(v.dock.y, v.dock.x, v.dock.x > 0.0);
+    let border = v.dock_view.w / 2.0;
+    if v.dock.y > v.dock.x && abs(screen.y - dock_edge) < border {
+        color = v.border.rgb;
+    }
+    if lens > 0 && abs(lens_edge) < border {
+        color = v.border.rgb;
+    }
+
+    if distance(screen, v.pointer.zw) < v.spot.x {
+        color = mix(color, v.highlight.rgb, v.highlight.a);
+    } else {
+        color *= 1.0 - v.spot.y;
+    }
+
+    let cursor_size = v.image.w;
+    if cursor_size > 0.0 {
+        var inside = false;
+        var edge = 1e9;
+        var j = CURSOR_POINTS - 1u;
+        for (var i = 0u; i < CURSOR_POINTS; i++) {
+            let a = mouse + v.cursor_shape[i].xy * cursor_size;
+            let b = mouse + v.cursor_shape[j].xy * cursor_size;
+            if (a.y > screen.y) != (b.y > screen.y)
+                && screen.x < (b.x - a.x) * (screen.y - a.y) / (b.y - a.y) + a.x {
+                inside = !inside;
+            }
+            let ab = b - a;
+            let t = clamp(dot(screen - a, ab) / dot(ab, ab), 0.0, 1.0);
+            edge = min(edge, distance(screen, a + t * ab));
+            j = i;
+        }
+        if edge < cursor_size * CURSOR_OUTLINE_WIDTH {
+            color = v.cursor_outline.rgb;
+        } else if inside {
+            color = v.cursor_fill.rgb;
+        }
+    }
+    return vec4<f32>(color, 1.0);
+}
+"#;
+
+/// `vec4`s in the shader's `View` before the cursor's shape.
+const VIEW_VECTORS: usize = 16;
+
+/// Scrolling in pixels, as touchpads do, that makes one step of a mouse wheel.
+const PIXELS_PER_STEP: f64 = 15.0;
+
+/// Keys outside those that type a character worth binding, and their keysyms.
+const NAMED: &[(NamedKey, gdk::keys::Key)] = &[
+    (NamedKey::Escape, k::Escape),
+    (NamedKey::Backspace, k::BackSpace),
+    (NamedKey::Tab, k::Tab),
+    (NamedKey::Enter, k::Return),
+    (NamedKey::Shift, k::Shift_L),
+    (NamedKey::Space, k::space),
+    (NamedKey::F1, k::F1),
+    (NamedKey::F2, k::F2),
+    (NamedKey::F3, k::F3),
+    (NamedKey::F4, k::F4),
+    (NamedKey::F5, k::F5),
+    (NamedKey::F6, k::F6),
+    (NamedKey::F7, k::F7),
+    (NamedKey::F8, k::F8),
+    (NamedKey::F9, k::F9),
+    (NamedKey::F10, k::F10),
+    (NamedKey::F11, k::F11),
+    (NamedKey::F12, k::F12),
+    (NamedKey::PrintScreen, k::Print),
+    (NamedKey::Home, k::Home),
+    (NamedKey::End, k::End),
+    (NamedKey::PageUp, k::Prior),
+    (NamedKey::PageDown, k::Next),
+    (NamedKey::ArrowUp, k::Up),
+    (NamedKey::ArrowDown, k::Down),
+    (NamedKey::ArrowLeft, k::Left),
+    (NamedKey::ArrowRight, k::Right),
+];
+
+/// [`SHADER`] with the constants it needs.
+fn shader() -> String {
+    format!(
+        "const CURSOR_POINTS: u32 = {}u;\nconst CURSOR_OUTLINE_WIDTH: f32 = {:.6};\n{}",
+        cursor::SHAPE.len(),
+        cursor::OUTLINE_WIDTH,
+        SHADER
+    )
+}
+
+fn windowing(e: impl fmt::Display) -> Error {
+    Error::Winit(e.to_string())
+}
+
+fn failed(e: impl fmt::Display) -> Error {
+    Error::Wgpu(e.to_string())
+}
+
+/// Keysym of `key`, lowercase for letters like the bindings.
+fn keysym(key: &Key) -> Option<u32> {
+    match key {
+        Key::Character(text) => {
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(*gdk::keys::Key::from_unicode(c).to_lower()),
+                _ => None,
+            }
+        }
+        Key::Named(named) => NAMED.iter().find(|(n, _)| n == named).map(|(_, key)| **key),
+        _ => None,
+    }
+}
+
+/// The shader's `View` for drawing `state` on a screen of `screen` logical pixels with
+/// `device_scale` physical ones each, with a capture of `image_size` logical pixels.
+fn view(
+    state: &ImageState,
+    screen: (f64, f64),
+    device_scale: f64,
+    image_size: (f64, f64),
+) -> Vec<[f32; 4]> {
+    // Docked, the band is drawn through its own viewport and the rest as it is
+    let (t, (dock_top, dock_bottom), dock_t) = match state.dock.get() {
+        None => (state.viewport(screen), (0.0, 0.0), state.unscaled(screen)),
+        Some(dock) => {
+            let (top, height) = dock.band(screen.1);
+            (
+                state.unscaled(screen),
+                (top, top + height),
+                state.dock_viewport(screen, (top, height)),
+            )
+        }
+    };
+    let lens_t = state.lens_viewport(screen);
+    let lens = state.lens.get();
+    let (lw, lh) = lens.map_or((0.0, 0.0), |lens| lens.half_size(state.lens_radius.get()));
+    let lens = match lens {
+        None => 0.0,
+        Some(Lens::Circle) => 1.0,
+        Some(Lens::Rectangle) => 2.0,
+    };
+    let highlight = state.highlight_color.get();
+    let (alpha, dim) = match (state.highlight.get(), state.dim_outside.get()) {
+        (true, Some(dim)) => (0.0, dim),
+        (true, None) => (highlight.3, 0.0),
+        (false, _) => (0.0, 0.0),
+    };
+    let m = state
+        .filters
+        .get()
+        .matrix()
+        .unwrap_or(ColorMatrix::IDENTITY)
+        .0;
+    let background = state.background.get();
+    let (mx, my) = state.pointer(screen);
+    let (hx, hy) = state.highlight_pos(screen);
+    let rgb = |(r, g, b): (f64, f64, f64)| [r, g, b, 1.0];
+
+    let mut vectors = vec![
+        [
+            image_size.0,
+            image_size.1,
+            device_scale,
+            state.cursor_size.get().unwrap_or(0.0),
+        ],
+        [t.origin().0, t.origin().1, t.scale, lens],
+        [
+            dock_t.origin().0,
+            dock_t.origin().1,
+            dock_t.scale,
+            DOCK_BORDER_WIDTH,
+        ],
+        [lens_t.origin().0, lens_t.origin().1, lens_t.scale, 0.0],
+        [dock_top, dock_bottom, lw, lh],
+        [mx, my, hx, hy],
+        [state.highlight_radius.get(), dim, 0.0, 0.0],
+        rgb(background),
+        [highlight.0, highlight.1, highlight.2, alpha],
+        rgb(DOCK_BORDER),
+        m[0],
+        m[1],
+        m[2],
+        [
+            HIGH_CONTRAST.threshold,
+            HIGH_CONTRAST.softness,
+            (HIGH_CONTRAST.polarity == Polarity::Inverted) as u8 as f64,
+            state.high_contrast.get() as u8 as f64,
+        ],
+        rgb(CURSOR_FILL),
+        rgb(CURSOR_OUTLINE),
+    ];
+    debug_assert_eq!(vectors.len(), VIEW_VECTORS);
+    vectors.extend(cursor::SHAPE.map(|(x, y)| [x, y, 0.0, 0.0]));
+    vectors
+        .into_iter()
+        .map(|vector| vector.map(|x| x as f32))
+        .collect()
+}
+
+/// Pixels of `frame` as tightly packed RGBA, the only layout of 8-bit textures wgpu has.
+fn rgba(frame: &Frame) -> Vec<u8> {
+    let channels = if frame.has_alpha() { 4 } else { 3 };
+    let width = frame.width() as usize * channels;
+    frame
+        .data()
+        .chunks(frame.rowstride() as usize)
+        .flat_map(|row| row[..width].chunks(channels))
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+        .collect()
+}
+
+/// The window's surface, the capture's texture and what draws it.
+struct Renderer {
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::RenderPipeline,
+    view: wgpu::Buffer,
+    /// Sampling the capture by its nearest pixel and smoothly.
+    nearest: wgpu::BindGroup,
+    smooth: wgpu::BindGroup,
+    /// Of the capture in the texture, in pixels.
+    image_size: (i32, i32),
+}
+
+impl Renderer {
+    /// Set up drawing `frame` in `window`. This is the only time pixel data is transferred.
+    fn new(window: Arc<Window>, frame: &Frame) -> Result<Self, Error> {
+        let instance = wgpu::Instance::default();
+        let surface = instance.create_surface(window.clone()).map_err(failed)?;
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            compatible_surface: Some(&surface),
+            ..Default::default()
+        }))
+        .map_err(failed)?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            // Captures of large outputs need all the texture size there is
+            required_limits: adapter.limits(),
+            ..Default::default()
+        }))
+        .map_err(failed)?;
+
+        let max = device.limits().max_texture_dimension_2d;
+        if frame.width() as u32 > max || frame.height() as u32 > max {
+            return Err(Error::Wgpu(format!(
+                "capture of {}x{} exceeds the maximum texture size of {}",
+                frame.width(),
+                frame.height(),
+                max
+            )));
+        }
+        let size = wgpu::Extent3d {
+            width: frame.width() as u32,
+            height: frame.height() as u32,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("capture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            texture.as_image_copy(),
+            &rgba(frame),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(size.width * 4),
+                rows_per_image: None,
+            },
+            size,
+        );
+
+        let view = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("view"),
+            size: ((VIEW_VECTORS + cursor::SHAPE.len()) * 16) as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let texture_view = texture.create_view(&Default::default());
+        let bind_group = |filter| {
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                mag_filter: filter,
+                min_filter: filter,
+                ..Default::default()
+            });
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: view.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&texture_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+            })
+        };
+        let nearest = bind_group(wgpu::FilterMode::Nearest);
+        let smooth = bind_group(wgpu::FilterMode::Linear);
+
+        let (width, height) = window.inner_size().into();
+        let mut config = surface
+            .get_default_config(&adapter, width, height)
+            .ok_or_else(|| Error::Wgpu("the window can't be drawn in".into()))?;
+        // Colors are computed as they are stored, like cairo and GL do
+        if let Some(&format) = surface
+            .get_capabilities(&adapter)
+            .formats
+            .iter()
+            .find(|f| !f.is_srgb())
+        {
+            config.format = format;
+        }
+        config.width = config.width.max(1);
+        config.height = config.height.max(1);
+        surface.configure(&device, &config);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("view"),
+            source: wgpu::ShaderSource::Wgsl(shader().into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[Some(&layout)],
+            immediate_size: 0,
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("view"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vertex"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fragment"),
+                compilation_options: Default::default(),
+                targets: &[Some(config.format.into())],
+            }),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Ok(Self {
+            window,
+            surface,
+            config,
+            device,
+            queue,
+            pipeline,
+            view,
+            nearest,
+            smooth,
+            image_size: (frame.width(), frame.height()),
+        })
+    }
+
+    /// Size of the window in logical pixels.
+    fn screen_size(&self) -> (f64, f64) {
+        self.window
+            .inner_size()
+            .to_logical::<f64>(self.window.scale_factor())
+            .into()
+    }
+
+    /// Make the surface the size of the window again.
+    fn resize(&mut self) {
+        let size = self.window.inner_size();
+        self.config.width = size.width.max(1);
+        self.config.height = size.height.max(1);
+        self.surface.configure(&self.device, &self.config);
+    }
+
+    fn draw(&mut self, state: &ImageState) -> Result<(), Error> {
+        let _span = tracing::trace_span!("render", scale = state.scale.get()).entered();
+        let frame = match self.surface.get_current_texture() {
+            wgpu::CurrentSurfaceTexture::Success(frame)
+            | wgpu::CurrentSurfaceTexture::Suboptimal(frame) => frame,
+            wgpu::CurrentSurfaceTexture::Timeout | wgpu::CurrentSurfaceTexture::Occluded => {
+                return Ok(())
+            }
+            wgpu::CurrentSurfaceTexture::Outdated | wgpu::CurrentSurfaceTexture::Lost => {
+                self.resize();
+                self.window.request_redraw();
+                return Ok(());
+            }
+            wgpu::CurrentSurfaceTexture::Validation => {
+                return Err(Error::Wgpu("could not get a frame to draw".into()))
+            }
+        };
+
+        let view = view(
+            state,
+            self.screen_size(),
+            self.window.scale_factor(),
+            state.logical_size(self.image_size),
+        );
+        let bytes: Vec<u8> = view
+            .iter()
+            .flatten()
+            .flat_map(|x| x.to_ne_bytes())
+            .collect();
+        self.queue.write_buffer(&self.view, 0, &bytes);
+        // The magnified band decides when docked
+        let bind_group = match state.interpolation.get().smooth(state.scale.get()) {
+            true => &self.smooth,
+            false => &self.nearest,
+        };
+
+        let target = frame.texture.create_view(&Default::default());
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        self.queue.submit([encoder.finish()]);
+        self.window.pre_present_notify();
+        self.queue.present(frame);
+        Ok(())
+    }
+}
+
+struct Overlay {
+    /// Taken once it's uploaded when the window opens.
+    frame: Option<Frame>,
+    output: Option<String>,
+    state: ImageState,
+    keys: Keys,
+    sensitivity: Sensitivity,
+    pan_button: MouseButton,
+    controller: Controller,
+    /// When the overlay started, that pointer motion is timed from.
+    start: Instant,
+    /// Buttons held down that pan.
+    held: Vec<MouseButton>,
+    /// Scrolling that didn't add up to a step yet.
+    scrolled: (f64, f64),
+    modifiers: ModifiersState,
+    renderer: Option<Renderer>,
+    error: Option<Error>,
+}
+
+/// Show `frame` with `state` fullscreen on the output called `output`, or the one the compositor
+/// picks, until the overlay is quit. Dragging with `pan_button` or the middle one pans.
+#[tracing::instrument(skip(frame, state, config))]
+pub fn show(
+    frame: Frame,
+    output: Option<&str>,
+    state: ImageState,
+    config: &ConfigFile,
+    pan_button: PanButton,
+) -> Result<(), Error> {
+    let event_loop = EventLoop::new().map_err(windowing)?;
+    state.pixels.set(Some((frame.width(), frame.height())));
+    let mut overlay = Overlay {
+        frame: Some(frame),
+        output: output.map(str::to_string),
+        state,
+        keys: input::keys(&config.keys),
+        sensitivity: config.sensitivity.mouse,
+        pan_button: match pan_button {
+            PanButton::Left => MouseButton::Left,
+            PanButton::Middle => MouseButton::Middle,
+            PanButton::Right => MouseButton::Right,
+        },
+        controller: Controller::default(),
+        start: Instant::now(),
+        held: Vec::new(),
+        scrolled: (0.0, 0.0),
+        modifiers: ModifiersState::empty(),
+        renderer: None,
+        error: None,
+    };
+    event_loop.run_app(&mut overlay).map_err(windowing)?;
+    overlay.error.map_or(Ok(()), Err)
+}
+
+impl Overlay {
+    /// Open the window on the chosen output and upload the capture to it.
+    fn open(&mut self, event_loop: &ActiveEventLoop) -> Result<(), Error> {
+        let monitor = match &self.output {
+            Some(name) => Some(
+                event_loop
+                    .available_monitors()
+                    .find(|m| m.name().as_deref() == Some(name))
+                    .ok_or_else(|| Error::UnknownOutput(name.clone()))?,
+            ),
+            None => None,
+        };
+        let attributes = Window::default_attributes()
+            .with_name(env!("CARGO_PKG_NAME"), "")
+            .with_title(env!("CARGO_PKG_NAME"))
+            .with_fullscreen(Some(Fullscreen::Borderless(monitor)));
+        let window = Arc::new(event_loop.create_window(attributes).map_err(windowing)?);
+        let frame = self.frame.take().expect("the window opens once");
+        self.renderer = Some(Renderer::new(window, &frame)?);
+        Ok(())
+    }
+
+    fn screen_size(&self) -> (f64, f64) {
+        self.renderer
+            .as_ref()
+            .map_or((0.0, 0.0), Renderer::screen_size)
+    }
+
+    fn redraw(&self) {
+        if let Some(renderer) = &self.renderer {
+            renderer.window.request_redraw();
+        }
+    }
+
+    /// Milliseconds since the overlay started, which winit doesn't time events with.
+    fn time(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+
+    fn perform(&mut self, event_loop: &ActiveEventLoop, action: Action) {
+        match action {
+            Action::Quit => event_loop.exit(),
+            action => {
+                if input::apply(action, &self.state, self.screen_size()) {
+                    self.redraw();
+                }
+            }
+        }
+    }
+
+    fn key(&mut self, event_loop: &ActiveEventLoop, event: KeyEvent) {
+        let Some(sym) = keysym(&event.logical_key) else {
+            return;
+        };
+        let pressed = event.state == ElementState::Pressed;
+        let action = input::key_action(&self.keys, sym, pressed)
+            .or_else(|| pressed.then(|| input::zoom_keys(sym)).flatten());
+        if let Some(action) = action {
+            self.perform(event_loop, action);
+        }
+    }
+
+    fn pointer_moved(&mut self, pos: (f64, f64)) {
+        let size = self.screen_size();
+        self.state.mouse_pos.set(pos);
+        if self.state.follow_mouse.get() {
+            self.state.follow(size);
+            self.redraw();
+        } else if !self.held.is_empty()
+            && self.controller.drag_to(&self.state, pos, self.time(), size)
+        {
+            self.redraw();
+        }
+        // Whatever is drawn at the pointer has to move along with it
+        if self.state.highlight.get()
+            || self.state.dock.get().is_some()
+            || self.state.lens.get().is_some()
+            || self.state.cursor_size.get().is_some()
+        {
+            self.redraw();
+        }
+    }
+
+    fn button(&mut self, button: MouseButton, state: ElementState) {
+        if button != self.pan_button && button != MouseButton::Middle {
+            return;
+        }
+        match state {
+            ElementState::Pressed => self.held.push(button),
+            ElementState::Released => {
+                self.held.retain(|&b| b != button);
+                if self.held.is_empty() {
+                    // Nothing keeps the view gliding here, so a fling just stops
+                    self.controller.release(self.time());
+                }
+            }
+        }
+    }
+
+    /// Scrolling by `delta`, zooming around the pointer or panning like the GTK overlay does
+    /// with a mouse wheel.
+    fn scroll(&mut self, event_loop: &ActiveEventLoop, delta: MouseScrollDelta) {
+        let (x, y) = match delta {
+            MouseScrollDelta::LineDelta(x, y) => (x as f64, y as f64),
+            MouseScrollDelta::PixelDelta(pos) => (pos.x / PIXELS_PER_STEP, pos.y / PIXELS_PER_STEP),
+        };
+        let (x, y) = (self.scrolled.0 + x, self.scrolled.1 + y);
+        let steps = (x.trunc(), y.trunc());
+        self.scrolled = (x - steps.0, y - steps.1);
+
+        let mut modifiers = ModifierType::empty();
+        modifiers.set(ModifierType::SHIFT_MASK, self.modifiers.shift_key());
+        modifiers.set(ModifierType::CONTROL_MASK, self.modifiers.control_key());
+        // Positive deltas move the content right and down, which scrolling left and up does
+        let directions = [
+            (steps.0, ScrollDirection::Left, ScrollDirection::Right),
+            (steps.1, ScrollDirection::Up, ScrollDirection::Down),
+        ];
+        for (steps, positive, negative) in directions {
+            let direction = if steps > 0.0 { positive } else { negative };
+            for _ in 0..steps.abs() as u32 {
+                let highlight = self.state.highlight.get();
+                if let Some(action) = input::wheel_step(direction, modifiers, highlight) {
+                    let pos = self.state.mouse_pos.get();
+                    let action = input::scrolled(action, self.sensitivity, &self.state, pos);
+                    self.perform(event_loop, action);
+                }
+            }
+        }
+    }
+}
+
+impl ApplicationHandler for Overlay {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.renderer.is_some() {
+            return;
+        }
+        if let Err(e) = self.open(event_loop) {
+            self.error = Some(e);
+            event_loop.exit();
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                if let Some(renderer) = &mut self.renderer {
+                    renderer.resize();
+                }
+                self.redraw();
+            }
+            WindowEvent::RedrawRequested => {
+                let Some(renderer) = &mut self.renderer else {
+                    return;
+                };
+                if let Err(e) = renderer.draw(&self.state) {
+                    self.error = Some(e);
+                    event_loop.exit();
+                }
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::KeyboardInput { event, .. } => self.key(event_loop, event),
+            WindowEvent::CursorMoved { position, .. } => {
+                let scale = self
+                    .renderer
+                    .as_ref()
+                    .map_or(1.0, |r| r.window.scale_factor());
+                self.pointer_moved(position.to_logical::<f64>(scale).into());
+            }
+            WindowEvent::MouseInput { state, button, .. } => self.button(button, state),
+            WindowEvent::MouseWheel { delta, .. } => self.scroll(event_loop, delta),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::view::Viewport};
+
+    #[test]
+    fn named_keys_are_the_keysyms_bindings_use() {
+        assert_eq!(keysym(&Key::Named(NamedKey::Escape)), Some(*k::Escape));
+        assert_eq!(keysym(&Key::Named(NamedKey::PageUp)), Some(*k::Prior));
+        assert_eq!(keysym(&Key::Named(NamedKey::Hyper)), None);
+    }
+
+    #[test]
+    fn shader_is_valid_and_its_view_is_what_is_written() {
+        use wgpu::naga::{
+            front::wgsl,
+            valid::{Capabilities, ValidationFlags, Validator},
+            TypeInner,
+        };
+        let module = wgsl::parse_str(&shader()).unwrap();
+        Validator::new(ValidationFlags::all(), Capabilities::empty())
+            .validate(&module)
+            .unwrap();
+        let span = module
+            .types
+            .iter()
+            .find(|(_, ty)| ty.name.as_deref() == Some("View"))
+            .map(|(_, ty)| match ty.inner {
+                TypeInner::Struct { span, .. } => span,
+                _ => unreachable!(),
+            })
+            .unwrap();
+        let view = view(&ImageState::default(), (100.0, 100.0), 1.0, (100.0, 100.0));
+        assert_eq!(span as usize, std::mem::size_of_val(&view[..]));
+    }
+
+    #[test]
+    fn view_maps_the_screen_through_the_viewport() {
+        let state = ImageState::default();
+        state.set_scale(2.0);
+        state.offset.set((10.0, -5.0));
+        let screen = (200.0, 100.0);
+        let image_size = (200.0, 100.0);
+        let view = view(&state, screen, 2.0, image_size);
+        assert_eq!(view.len(), VIEW_VECTORS + cursor::SHAPE.len());
+
+        let t: Viewport = state.viewport(screen);
+        let (ox, oy) = t.origin();
+        assert_eq!(view[0], [200.0, 100.0, 2.0, 0.0]);
+        assert_eq!(view[1][..3], [ox as f32, oy as f32, 2.0]);
+        // Nothing is docked, so the band is empty
+        assert_eq!(view[4][..2], [0.0, 0.0]);
+    }
+}
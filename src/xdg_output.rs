@@ -0,0 +1,164 @@
+//! Outputs as any Wayland compositor describes them through `wl_output` and xdg-output, for
+//! compositors without an IPC socket this knows.
+
+use {
+    crate::{outputs::Output, tree::Rect, Error},
+    std::fmt,
+    wayland_client::{
+        delegate_noop,
+        globals::{registry_queue_init, GlobalListContents},
+        protocol::{wl_output, wl_registry},
+        Connection, Dispatch, Proxy, QueueHandle, WEnum,
+    },
+    wayland_protocols::xdg::xdg_output::zv1::client::{
+        zxdg_output_manager_v1::ZxdgOutputManagerV1,
+        zxdg_output_v1::{self, ZxdgOutputV1},
+    },
+};
+
+#[derive(Debug, Clone, Default)]
+struct Described {
+    name: Option<String>,
+    position: (i32, i32),
+    size: (i32, i32),
+    /// Current mode of the `wl_output`, in physical pixels and not rotated.
+    mode: (i32, i32),
+    /// Whether the output is rotated by 90 or 270 degrees.
+    sideways: bool,
+}
+
+impl Described {
+    /// Physical pixels per logical pixel. `wl_output` only has a whole number for it, so this
+    /// compares the mode with the logical size instead.
+    fn scale(&self) -> f64 {
+        let width = match self.sideways {
+            true => self.mode.1,
+            false => self.mode.0,
+        };
+        match (width, self.size.0) {
+            (0, _) | (_, 0) => 1.0,
+            (physical, logical) => physical as f64 / logical as f64,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// What each xdg-output said, by the index it was created with.
+    outputs: Vec<Described>,
+}
+
+fn failed(e: impl fmt::Display) -> Error {
+    Error::Wayland(e.to_string())
+}
+
+/// Every output the compositor announces, none of them focused.
+#[tracing::instrument]
+pub fn outputs() -> Result<Vec<Output>, Error> {
+    let conn = Connection::connect_to_env().map_err(failed)?;
+    let (globals, mut queue) = registry_queue_init::<State>(&conn).map_err(failed)?;
+    let qh = queue.handle();
+    // Version 2 adds names
+    let manager: ZxdgOutputManagerV1 = globals.bind(&qh, 2..=3, ()).map_err(failed)?;
+
+    let xdg_outputs: Vec<ZxdgOutputV1> = globals
+        .contents()
+        .clone_list()
+        .into_iter()
+        .filter(|g| g.interface == wl_output::WlOutput::interface().name)
+        .enumerate()
+        .map(|(i, g)| {
+            let output: wl_output::WlOutput = globals.registry().bind(g.name, 1, &qh, i);
+            manager.get_xdg_output(&output, &qh, i)
+        })
+        .collect();
+    let mut state = State {
+        outputs: vec![Described::default(); xdg_outputs.len()],
+    };
+    queue.roundtrip(&mut state).map_err(failed)?;
+
+    xdg_outputs.iter().for_each(ZxdgOutputV1::destroy);
+    manager.destroy();
+    Ok(state
+        .outputs
+        .into_iter()
+        .filter_map(|o| {
+            let scale = o.scale();
+            Some(Output {
+                name: o.name?,
+                focused: false,
+                active: true,
+                rect: Rect {
+                    x: o.position.0,
+                    y: o.position.1,
+                    width: o.size.0,
+                    height: o.size.1,
+                },
+                scale,
+            })
+        })
+        .collect())
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, usize> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_output::WlOutput,
+        event: wl_output::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let output = &mut state.outputs[*index];
+        match event {
+            wl_output::Event::Geometry {
+                transform: WEnum::Value(transform),
+                ..
+            } => {
+                use wl_output::Transform as T;
+                output.sideways =
+                    matches!(transform, T::_90 | T::_270 | T::Flipped90 | T::Flipped270);
+            }
+            wl_output::Event::Mode {
+                flags: WEnum::Value(flags),
+                width,
+                height,
+                ..
+            } if flags.contains(wl_output::Mode::Current) => output.mode = (width, height),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZxdgOutputV1, usize> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZxdgOutputV1,
+        event: zxdg_output_v1::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let output = &mut state.outputs[*index];
+        match event {
+            zxdg_output_v1::Event::LogicalPosition { x, y } => output.position = (x, y),
+            zxdg_output_v1::Event::LogicalSize { width, height } => output.size = (width, height),
+            zxdg_output_v1::Event::Name { name } => output.name = Some(name),
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(State: ZxdgOutputManagerV1);
@@ -0,0 +1,97 @@
+//! Zoom level shown for a moment whenever the scale changes, so what's seen can be reported
+//! along with how far in it was.
+
+use {
+    crate::{
+        config::{FADE_MS, ZOOM_LEVEL_SHOWN_MS},
+        schedule::Scheduler,
+        view::{screen_size, ImageState},
+    },
+    gtk::{
+        cairo::{Context, FontSlant, FontWeight},
+        glib::{self, clone, Continue},
+        prelude::*,
+    },
+    std::{cell::Cell, rc::Rc, time::Duration},
+};
+
+const FONT_SIZE: f64 = 28.0;
+/// Screen pixels around the text, and between it and the bottom of the screen.
+const PADDING: f64 = 12.0;
+const MARGIN: f64 = 48.0;
+
+/// How `scale` reads on screen.
+fn level(scale: f64) -> String {
+    format!("{:.1}x", scale)
+}
+
+/// Draw `text` on a box at the bottom middle of a `screen` sized window.
+fn draw(ctx: &Context, text: &str, screen: (f64, f64)) {
+    ctx.select_font_face("monospace", FontSlant::Normal, FontWeight::Bold);
+    ctx.set_font_size(FONT_SIZE);
+    let extents = match ctx.text_extents(text) {
+        Ok(extents) => extents,
+        Err(_) => return,
+    };
+    let (w, h) = (
+        extents.width + 2.0 * PADDING,
+        extents.height + 2.0 * PADDING,
+    );
+    let (x, y) = ((screen.0 - w) / 2.0, screen.1 - MARGIN - h);
+    ctx.set_source_rgba(0.0, 0.0, 0.0, 0.75);
+    ctx.rectangle(x, y, w, h);
+    let _ = ctx.fill();
+    ctx.set_source_rgb(1.0, 1.0, 1.0);
+    ctx.move_to(
+        x + PADDING - extents.x_bearing,
+        y + PADDING - extents.y_bearing,
+    );
+    let _ = ctx.show_text(text);
+}
+
+/// Show the zoom level on `area` for a moment each time it changes.
+pub fn connect(area: &gtk::DrawingArea, scheduler: &Rc<Scheduler>, state: &Rc<ImageState>) {
+    // The scale the overlay opened at isn't a change
+    let last = Rc::new(Cell::new(state.scale.get()));
+    // Only the timeout of the latest change starts fading out
+    let changes = Rc::new(Cell::new(0u32));
+
+    scheduler.redraw_above(area);
+    area.set_opacity(0.0);
+    area.show();
+    area.connect_draw(clone!(@strong scheduler, @strong state => move |area, ctx| {
+        let scale = state.scale.get();
+        if last.replace(scale) != scale {
+            area.set_opacity(1.0);
+            let change = changes.get().wrapping_add(1);
+            changes.set(change);
+            glib::timeout_add_local(
+                Duration::from_millis(ZOOM_LEVEL_SHOWN_MS),
+                clone!(@weak area, @weak scheduler, @strong changes => @default-return Continue(false), move || {
+                    if changes.get() == change {
+                        let changes = changes.clone();
+                        scheduler.fade_out(&area, FADE_MS, move || changes.get() == change);
+                    }
+                    Continue(false)
+                }),
+            );
+        }
+        if area.opacity() > 0.0 {
+            let screen = screen_size(area);
+            draw(ctx, &level(scale), screen);
+        }
+        Inhibit(false)
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_are_rounded_to_a_tenth() {
+        assert_eq!(level(1.0), "1.0x");
+        assert_eq!(level(2.3456), "2.3x");
+        assert_eq!(level(12.96), "13.0x");
+    }
+}